@@ -0,0 +1,52 @@
+use std::ops::Deref;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage};
+use mongodb::bson::oid::ObjectId;
+
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+/// The id of the user logged into the current session.
+///
+/// Available as a request extension on every route wrapped by
+/// [`reject_anonymous_users`].
+#[derive(Copy, Clone, Debug)]
+pub struct UserId(ObjectId);
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Deref for UserId {
+    type Target = ObjectId;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub async fn reject_anonymous_users(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    match session.get_user_id().map_err(e500)? {
+        Some(user_id) => {
+            req.extensions_mut().insert(UserId(user_id));
+            next.call(req).await.map(|res| res.map_into_left_body())
+        }
+        None => {
+            let response = see_other("/login");
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
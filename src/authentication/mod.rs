@@ -0,0 +1,9 @@
+mod basic;
+mod middleware;
+mod password;
+
+pub use basic::BasicAuthUser;
+pub use middleware::{reject_anonymous_users, UserId};
+pub use password::{
+    change_password, create_user, get_username, validate_credentials, AuthError, Credentials,
+};
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Context, Result};
+use mongodb::bson::oid::ObjectId;
+use secrecy::Secret;
+
+use crate::domain::{HashedPassword, Password};
+use crate::storage::UserRepository;
+
+// Verified against when the username is unknown, so that a missing user costs
+// as much time as a wrong password.
+const FALLBACK_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$\
+    CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+#[derive(Debug)]
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidCredentials(anyhow::Error),
+    UnexpectedError(anyhow::Error),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::InvalidCredentials(e) => write!(f, "Invalid credentials: {}", e),
+            AuthError::UnexpectedError(e) => write!(f, "Authentication failed: {}", e),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, users))]
+pub async fn validate_credentials(
+    users: &dyn UserRepository,
+    credentials: Credentials,
+) -> Result<ObjectId, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = HashedPassword::parse(FALLBACK_PASSWORD_HASH.to_string())
+        .map_err(|e| AuthError::UnexpectedError(anyhow!(e)))?;
+    if let Some(stored_user) = users
+        .find_by_username(&credentials.username)
+        .await
+        .map_err(AuthError::UnexpectedError)?
+    {
+        user_id = Some(stored_user.id);
+        expected_password_hash = stored_user.password_hash;
+    }
+
+    let password_matches = tokio::task::spawn_blocking(move || {
+        tracing::info_span!("Verify password hash")
+            .in_scope(|| expected_password_hash.verify(&credentials.password))
+    })
+    .await
+    .context("Failed to spawn blocking task.")
+    .map_err(AuthError::UnexpectedError)?;
+
+    match user_id {
+        Some(user_id) if password_matches => Ok(user_id),
+        Some(_) => Err(AuthError::InvalidCredentials(anyhow!("Invalid password."))),
+        None => Err(AuthError::InvalidCredentials(anyhow!("Unknown username."))),
+    }
+}
+
+async fn compute_password_hash(password: Password) -> Result<HashedPassword> {
+    tokio::task::spawn_blocking(move || HashedPassword::compute(password.as_ref()))
+        .await
+        .context("Failed to spawn blocking task.")?
+        .map_err(|e| anyhow!(e))
+}
+
+#[tracing::instrument(name = "Create user", skip(password, users))]
+pub async fn create_user(
+    users: &dyn UserRepository,
+    username: &str,
+    password: Password,
+) -> Result<ObjectId> {
+    let password_hash = compute_password_hash(password).await?;
+    users.insert(username, &password_hash).await
+}
+
+#[tracing::instrument(name = "Change password", skip(password, users))]
+pub async fn change_password(
+    users: &dyn UserRepository,
+    user_id: ObjectId,
+    password: Password,
+) -> Result<()> {
+    let password_hash = compute_password_hash(password).await?;
+    users.update_password_hash(user_id, &password_hash).await
+}
+
+#[tracing::instrument(name = "Get username", skip(users))]
+pub async fn get_username(users: &dyn UserRepository, user_id: ObjectId) -> Result<String> {
+    let user = users
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| anyhow!("No user with id {}", user_id))?;
+    Ok(user.username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FALLBACK_PASSWORD_HASH;
+    use crate::domain::HashedPassword;
+    use claim::assert_ok;
+
+    #[test]
+    fn the_fallback_hash_is_a_valid_argon2id_hash() {
+        assert_ok!(HashedPassword::parse(FALLBACK_PASSWORD_HASH.into()));
+    }
+}
@@ -1,8 +1,15 @@
-use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 
 use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    CircuitBreaker, DevSender, EmailClient, EmailSender, HttpApiSender, PostmarkSender,
+    RetryPolicy, SmtpSender,
+};
 
 #[derive(serde::Deserialize)]
 pub struct Settings {
@@ -16,6 +23,35 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
+    pub base_url: String,
+    /// Signs the session cookie; must be at least 64 bytes long.
+    pub hmac_secret: Secret<String>,
+    /// How long in-flight requests get to complete once a shutdown signal
+    /// is received.
+    #[serde(
+        default = "default_shutdown_timeout_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub shutdown_timeout_seconds: u64,
+    /// Whether the application delivers queued and scheduled newsletter
+    /// issues itself.
+    #[serde(default = "default_run_delivery_worker")]
+    pub run_delivery_worker: bool,
+    /// Where the email templates live.
+    #[serde(default = "default_templates_directory")]
+    pub templates_directory: String,
+}
+
+fn default_shutdown_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_run_delivery_worker() -> bool {
+    true
+}
+
+fn default_templates_directory() -> String {
+    "configuration/templates".into()
 }
 
 #[derive(serde::Deserialize)]
@@ -40,9 +76,102 @@ impl DatabaseSettings {
 
 #[derive(serde::Deserialize)]
 pub struct EmailClientSettings {
+    #[serde(default)]
+    pub provider: EmailProvider,
+    /// The API root for `http_api` and `postmark`, the connection URL of the
+    /// relay for `smtp` (e.g. `smtps://smtp.example.com`).
     pub base_url: String,
+    /// The API key, server token or SMTP password.
     pub client_secret: Secret<String>,
     pub sender_email: String,
+    /// Shown to recipients instead of the bare sender address.
+    #[serde(default)]
+    pub sender_name: Option<String>,
+    /// Where replies go instead of the sender address.
+    #[serde(default)]
+    pub reply_to_email: Option<String>,
+    /// Authenticates against the SMTP relay together with `client_secret`.
+    #[serde(default)]
+    pub smtp_username: Option<String>,
+    /// Verifies the signature of the provider's email event webhooks, which
+    /// are all rejected when unset.
+    #[serde(default)]
+    pub webhook_secret: Option<Secret<String>>,
+    /// Where the `dev` provider writes emails; stdout when unset.
+    #[serde(default)]
+    pub dev_output_dir: Option<String>,
+    /// How long a single attempt to hand an email over may take.
+    #[serde(
+        default = "default_email_timeout_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub timeout_milliseconds: u64,
+    /// How many times a send is retried when the provider is overloaded or
+    /// failing.
+    #[serde(
+        default = "default_email_max_retries",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_retries: u32,
+    /// The first retry delay, doubled on every further retry.
+    #[serde(
+        default = "default_email_retry_base_delay_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_base_delay_milliseconds: u64,
+    #[serde(
+        default = "default_email_retry_max_delay_milliseconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub retry_max_delay_milliseconds: u64,
+    /// How many sends in a row may fail before sending is suspended; 0
+    /// never suspends it.
+    #[serde(
+        default = "default_email_circuit_breaker_threshold",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub circuit_breaker_threshold: u32,
+    /// How long sending stays suspended before it is tried again.
+    #[serde(
+        default = "default_email_circuit_breaker_cooldown_seconds",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub circuit_breaker_cooldown_seconds: u64,
+}
+
+fn default_email_timeout_milliseconds() -> u64 {
+    10_000
+}
+
+fn default_email_max_retries() -> u32 {
+    3
+}
+
+fn default_email_retry_base_delay_milliseconds() -> u64 {
+    500
+}
+
+fn default_email_retry_max_delay_milliseconds() -> u64 {
+    10_000
+}
+
+fn default_email_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_email_circuit_breaker_cooldown_seconds() -> u64 {
+    30
+}
+
+/// The backend that delivers emails.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailProvider {
+    #[default]
+    HttpApi,
+    Postmark,
+    Smtp,
+    Dev,
 }
 
 impl EmailClientSettings {
@@ -53,6 +182,70 @@ impl EmailClientSettings {
     pub fn client_secret(&self) -> Secret<String> {
         Secret::new(self.client_secret.expose_secret().clone())
     }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            base_delay: Duration::from_millis(self.retry_base_delay_milliseconds),
+            max_delay: Duration::from_millis(self.retry_max_delay_milliseconds),
+        }
+    }
+
+    pub fn circuit_breaker(&self) -> CircuitBreaker {
+        CircuitBreaker::new(
+            self.circuit_breaker_threshold,
+            Duration::from_secs(self.circuit_breaker_cooldown_seconds),
+        )
+    }
+
+    pub fn sender_backend(&self) -> Result<Arc<dyn EmailSender>> {
+        Ok(match self.provider {
+            EmailProvider::HttpApi => Arc::new(HttpApiSender::new(
+                self.base_url.clone(),
+                self.client_secret(),
+                self.timeout(),
+            )?),
+            EmailProvider::Postmark => Arc::new(PostmarkSender::new(
+                self.base_url.clone(),
+                self.client_secret(),
+                self.timeout(),
+            )?),
+            EmailProvider::Smtp => Arc::new(SmtpSender::new(
+                &self.base_url,
+                self.smtp_username
+                    .clone()
+                    .map(|username| (username, self.client_secret())),
+                self.timeout(),
+            )?),
+            EmailProvider::Dev => {
+                Arc::new(DevSender::new(self.dev_output_dir.as_ref().map(Into::into)))
+            }
+        })
+    }
+
+    pub fn client(&self) -> Result<EmailClient> {
+        let sender = self
+            .sender()
+            .map_err(anyhow::Error::msg)
+            .context("Invalid sender email")?;
+        let mut client = EmailClient::new(sender, self.sender_backend()?)
+            .with_retry_policy(self.retry_policy())
+            .with_circuit_breaker(self.circuit_breaker());
+        if let Some(name) = &self.sender_name {
+            client = client.with_sender_name(name.clone());
+        }
+        if let Some(reply_to) = &self.reply_to_email {
+            let reply_to = SubscriberEmail::parse(reply_to.clone())
+                .map_err(anyhow::Error::msg)
+                .context("Invalid reply-to email")?;
+            client = client.with_reply_to(reply_to);
+        }
+        Ok(client)
+    }
 }
 
 pub fn get_configuration() -> Result<Settings> {
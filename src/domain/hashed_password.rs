@@ -0,0 +1,85 @@
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use secrecy::{ExposeSecret, Secret};
+
+#[derive(Debug, Clone)]
+pub struct HashedPassword(Secret<String>);
+
+impl HashedPassword {
+    pub fn compute(password: &Secret<String>) -> Result<HashedPassword, String> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let params = Params::new(15000, 2, 1, None).map_err(|e| e.to_string())?;
+        let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.expose_secret().as_bytes(), &salt)
+            .map_err(|e| format!("Failed to hash password: {}", e))?;
+        Ok(Self(Secret::new(hash.to_string())))
+    }
+
+    pub fn parse(s: String) -> Result<HashedPassword, String> {
+        let hash = PasswordHash::new(&s).map_err(|_| "Not a valid PHC string.".to_string())?;
+        if hash.algorithm != Algorithm::Argon2id.ident() {
+            return Err(format!("{} is not an Argon2id hash.", hash.algorithm));
+        }
+        Ok(Self(Secret::new(s)))
+    }
+
+    pub fn verify(&self, candidate: &Secret<String>) -> bool {
+        // `parse` and `compute` guarantee the PHC string is well-formed
+        let expected = PasswordHash::new(self.0.expose_secret()).expect("Invalid PHC string");
+        Argon2::default()
+            .verify_password(candidate.expose_secret().as_bytes(), &expected)
+            .is_ok()
+    }
+}
+
+impl ExposeSecret<String> for HashedPassword {
+    fn expose_secret(&self) -> &String {
+        self.0.expose_secret()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashedPassword;
+    use claim::{assert_err, assert_ok};
+    use secrecy::{ExposeSecret, Secret};
+
+    #[test]
+    fn computed_hashes_use_argon2id() {
+        let hash = HashedPassword::compute(&Secret::new("hunter2".into())).unwrap();
+        assert!(hash.expose_secret().starts_with("$argon2id$"));
+    }
+
+    #[test]
+    fn the_original_password_is_verified() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let hash = HashedPassword::compute(&password).unwrap();
+        assert!(hash.verify(&password));
+    }
+
+    #[test]
+    fn a_wrong_password_is_rejected() {
+        let hash = HashedPassword::compute(&Secret::new("hunter2".into())).unwrap();
+        assert!(!hash.verify(&Secret::new("hunter3".into())));
+    }
+
+    #[test]
+    fn a_stored_hash_round_trips_through_parse() {
+        let password = Secret::new("hunter2".to_string());
+        let hash = HashedPassword::compute(&password).unwrap();
+        let parsed = HashedPassword::parse(hash.expose_secret().clone());
+        assert_ok!(&parsed);
+        assert!(parsed.unwrap().verify(&password));
+    }
+
+    #[test]
+    fn non_phc_strings_are_rejected() {
+        assert_err!(HashedPassword::parse("hunter2".into()));
+    }
+
+    #[test]
+    fn non_argon2id_hashes_are_rejected() {
+        let argon2i = "$argon2i$v=19$m=16,t=2,p=1$c29tZXNhbHQ$8lB3mzDoCoOuOmmh6Q5eVg";
+        assert_err!(HashedPassword::parse(argon2i.into()));
+    }
+}
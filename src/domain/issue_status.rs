@@ -0,0 +1,59 @@
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueStatus {
+    /// Waits for its `send_at` time before it is delivered.
+    Scheduled,
+    /// Handed over to the delivery queue.
+    Published,
+    /// Was scheduled, but will never be delivered.
+    Cancelled,
+}
+
+impl IssueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueStatus::Scheduled => "scheduled",
+            IssueStatus::Published => "published",
+            IssueStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl TryFrom<String> for IssueStatus {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "scheduled" => Ok(Self::Scheduled),
+            "published" => Ok(Self::Published),
+            "cancelled" => Ok(Self::Cancelled),
+            other => Err(format!("{} is not a valid issue status.", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueStatus;
+    use claim::assert_err;
+
+    #[test]
+    fn statuses_round_trip_through_their_string_form() {
+        for status in [
+            IssueStatus::Scheduled,
+            IssueStatus::Published,
+            IssueStatus::Cancelled,
+        ] {
+            assert_eq!(
+                IssueStatus::try_from(status.as_str().to_string()),
+                Ok(status)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        assert_err!(IssueStatus::try_from("draft".to_string()));
+    }
+}
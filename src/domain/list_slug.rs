@@ -0,0 +1,44 @@
+/// The identifier of a mailing list, as used in URLs and forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    /// Accepts 1 to 64 lowercase ASCII letters, digits and dashes.
+    pub fn parse(s: String) -> Result<ListSlug, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= 64
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid list identifier.", s))
+        }
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ListSlug;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn lowercase_letters_digits_and_dashes_are_valid() {
+        assert_ok!(ListSlug::parse("weekly-digest-2".into()));
+        assert_ok!(ListSlug::parse("a".repeat(64)));
+    }
+
+    #[test]
+    fn empty_long_and_unusual_slugs_are_rejected() {
+        for slug in ["", "Weekly", "weekly digest", "weekly/digest", "ünï"] {
+            assert_err!(ListSlug::parse(slug.into()));
+        }
+        assert_err!(ListSlug::parse("a".repeat(65)));
+    }
+}
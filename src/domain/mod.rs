@@ -1,7 +1,21 @@
+mod hashed_password;
+mod issue_status;
+mod list_slug;
 mod new_subscriber;
+mod password;
+mod send_time;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_status;
+mod subscriber_time_zone;
 
+pub use hashed_password::HashedPassword;
+pub use issue_status::IssueStatus;
+pub use list_slug::ListSlug;
 pub use new_subscriber::NewSubscriber;
+pub use password::Password;
+pub use send_time::SendTime;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_status::SubscriberStatus;
+pub use subscriber_time_zone::SubscriberTimeZone;
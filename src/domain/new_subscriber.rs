@@ -1,7 +1,9 @@
 use super::SubscriberName;
 use super::SubscriberEmail;
+use super::SubscriberTimeZone;
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub time_zone: Option<SubscriberTimeZone>,
 }
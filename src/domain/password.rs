@@ -0,0 +1,55 @@
+use secrecy::{ExposeSecret, Secret};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct Password(Secret<String>);
+
+impl Password {
+    pub fn parse(s: Secret<String>) -> Result<Password, String> {
+        let length = s.expose_secret().graphemes(true).count();
+        if length < 12 {
+            Err("The new password must be at least 12 characters long.".into())
+        } else if length > 128 {
+            Err("The new password must be at most 128 characters long.".into())
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<Secret<String>> for Password {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Password;
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_12_grapheme_long_password_is_valid() {
+        let password = Secret::new("ё".repeat(12));
+        assert_ok!(Password::parse(password));
+    }
+
+    #[test]
+    fn a_password_shorter_than_12_graphemes_is_rejected() {
+        let password = Secret::new("a".repeat(11));
+        assert_err!(Password::parse(password));
+    }
+
+    #[test]
+    fn a_128_grapheme_long_password_is_valid() {
+        let password = Secret::new("a".repeat(128));
+        assert_ok!(Password::parse(password));
+    }
+
+    #[test]
+    fn a_password_longer_than_128_graphemes_is_rejected() {
+        let password = Secret::new("a".repeat(129));
+        assert_err!(Password::parse(password));
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+use super::SubscriberTimeZone;
+
+/// When a newsletter issue goes out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendTime {
+    /// The same instant for every subscriber.
+    At(DateTime<Utc>),
+    /// This wall-clock time in each subscriber's time zone, or in UTC for
+    /// subscribers who have not set one.
+    Local(NaiveDateTime),
+}
+
+impl SendTime {
+    /// Accepts at most one of an absolute and a local send time.
+    pub fn parse(
+        send_at: Option<DateTime<Utc>>,
+        send_at_local: Option<NaiveDateTime>,
+    ) -> Result<Option<SendTime>, String> {
+        match (send_at, send_at_local) {
+            (Some(_), Some(_)) => Err("Set either send_at or send_at_local, not both.".into()),
+            (Some(at), None) => Ok(Some(Self::At(at))),
+            (None, Some(local)) => Ok(Some(Self::Local(local))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// The first instant any subscriber is due the issue.
+    pub fn earliest(&self) -> DateTime<Utc> {
+        match self {
+            SendTime::At(at) => *at,
+            // UTC+14 is the earliest offset in use
+            SendTime::Local(local) => local.and_utc() - Duration::hours(14),
+        }
+    }
+
+    /// The instant a subscriber in `time_zone` is due the issue.
+    pub fn instant_in(&self, time_zone: Option<&SubscriberTimeZone>) -> DateTime<Utc> {
+        match (self, time_zone) {
+            (SendTime::At(at), _) => *at,
+            (SendTime::Local(local), Some(time_zone)) => time_zone.instant(*local),
+            (SendTime::Local(local), None) => local.and_utc(),
+        }
+    }
+
+    pub fn local(&self) -> Option<NaiveDateTime> {
+        match self {
+            SendTime::At(_) => None,
+            SendTime::Local(local) => Some(*local),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{SendTime, SubscriberTimeZone};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use claim::assert_err;
+
+    #[test]
+    fn only_one_kind_of_send_time_may_be_given() {
+        let local = NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let at = local.and_utc();
+
+        assert_err!(SendTime::parse(Some(at), Some(local)));
+        assert_eq!(SendTime::parse(None, None), Ok(None));
+        assert_eq!(SendTime::parse(Some(at), None), Ok(Some(SendTime::At(at))));
+        assert_eq!(
+            SendTime::parse(None, Some(local)),
+            Ok(Some(SendTime::Local(local)))
+        );
+    }
+
+    #[test]
+    fn local_send_times_start_in_the_earliest_time_zone() {
+        let local = NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let send_time = SendTime::Local(local);
+        let kiritimati = SubscriberTimeZone::parse("Pacific/Kiritimati".into()).unwrap();
+
+        assert_eq!(
+            send_time.earliest(),
+            send_time.instant_in(Some(&kiritimati))
+        );
+        assert_eq!(
+            send_time.instant_in(None),
+            Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap()
+        );
+    }
+}
@@ -8,7 +8,10 @@ impl SubscriberName {
         let is_empty_or_whitespace = s.trim().is_empty();
         let is_too_long = s.graphemes(true).count() > 256;
         let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let contains_forbidden_characters = s.chars().any(|g| forbidden_characters.contains(&g));
+        // Names end up in email headers, where a line break would start a new one
+        let contains_forbidden_characters = s
+            .chars()
+            .any(|g| forbidden_characters.contains(&g) || g.is_control());
 
         if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
             Err(format!("{} is not a valid subscriber name.", s))
@@ -61,6 +64,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn names_containing_line_breaks_or_control_characters_are_rejected() {
+        for name in [
+            "le guin\r\nBcc: a@example.com",
+            "le\nguin",
+            "le\tguin",
+            "le\0guin",
+        ] {
+            assert_err!(SubscriberName::parse(name.to_string()));
+        }
+    }
+
     #[test]
     fn a_valid_name_is_parsed_successfully() {
         let name = "This is a good name".to_string();
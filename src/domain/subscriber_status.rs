@@ -0,0 +1,136 @@
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+    /// Mail to the address bounced permanently.
+    Bounced,
+    /// The subscriber marked an email as spam.
+    Complained,
+}
+
+impl SubscriberStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriberStatus::PendingConfirmation => "pending_confirmation",
+            SubscriberStatus::Confirmed => "confirmed",
+            SubscriberStatus::Unsubscribed => "unsubscribed",
+            SubscriberStatus::Bounced => "bounced",
+            SubscriberStatus::Complained => "complained",
+        }
+    }
+
+    /// Bounced and complained addresses get no email at all.
+    pub fn is_suppressed(&self) -> bool {
+        matches!(
+            self,
+            SubscriberStatus::Bounced | SubscriberStatus::Complained
+        )
+    }
+
+    /// The statuses a subscriber can move to `self` from. Only pending
+    /// subscribers get confirmed, unsubscribed ones can sign up again and
+    /// suppressed addresses stay suppressed.
+    pub fn allowed_from(&self) -> &'static [SubscriberStatus] {
+        use SubscriberStatus::*;
+        match self {
+            PendingConfirmation => &[Unsubscribed],
+            Confirmed => &[PendingConfirmation],
+            Unsubscribed => &[PendingConfirmation, Confirmed],
+            Bounced | Complained => &[PendingConfirmation, Confirmed, Unsubscribed],
+        }
+    }
+
+    pub fn can_become(&self, next: SubscriberStatus) -> bool {
+        next.allowed_from().contains(self)
+    }
+}
+
+impl TryFrom<String> for SubscriberStatus {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "pending_confirmation" => Ok(Self::PendingConfirmation),
+            "confirmed" => Ok(Self::Confirmed),
+            "unsubscribed" => Ok(Self::Unsubscribed),
+            "bounced" => Ok(Self::Bounced),
+            "complained" => Ok(Self::Complained),
+            other => Err(format!("{} is not a valid subscriber status.", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberStatus;
+    use claim::assert_err;
+
+    #[test]
+    fn statuses_round_trip_through_their_string_form() {
+        for status in [
+            SubscriberStatus::PendingConfirmation,
+            SubscriberStatus::Confirmed,
+            SubscriberStatus::Unsubscribed,
+            SubscriberStatus::Bounced,
+            SubscriberStatus::Complained,
+        ] {
+            assert_eq!(
+                SubscriberStatus::try_from(status.as_str().to_string()),
+                Ok(status)
+            );
+        }
+    }
+
+    #[test]
+    fn serde_uses_the_string_form() {
+        let status = SubscriberStatus::PendingConfirmation;
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            format!("\"{}\"", status.as_str())
+        );
+    }
+
+    #[test]
+    fn suppressed_addresses_stay_suppressed() {
+        use SubscriberStatus::*;
+        for suppressed in [Bounced, Complained] {
+            for next in [
+                PendingConfirmation,
+                Confirmed,
+                Unsubscribed,
+                Bounced,
+                Complained,
+            ] {
+                assert!(!suppressed.can_become(next));
+            }
+        }
+        assert!(Confirmed.can_become(Bounced));
+        assert!(Unsubscribed.can_become(Complained));
+    }
+
+    #[test]
+    fn only_pending_subscribers_are_confirmed() {
+        use SubscriberStatus::*;
+        assert!(PendingConfirmation.can_become(Confirmed));
+        assert!(!Unsubscribed.can_become(Confirmed));
+        assert!(!Confirmed.can_become(Confirmed));
+        assert!(Confirmed.can_become(Unsubscribed));
+        assert!(!Unsubscribed.can_become(Unsubscribed));
+    }
+
+    #[test]
+    fn only_unsubscribed_subscribers_sign_up_again() {
+        use SubscriberStatus::*;
+        assert!(Unsubscribed.can_become(PendingConfirmation));
+        assert!(!Confirmed.can_become(PendingConfirmation));
+        assert!(!PendingConfirmation.can_become(PendingConfirmation));
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        assert_err!(SubscriberStatus::try_from("deleted".to_string()));
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// An IANA time zone name, such as `Europe/Berlin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberTimeZone(Tz);
+
+impl SubscriberTimeZone {
+    pub fn parse(s: String) -> Result<SubscriberTimeZone, String> {
+        s.parse()
+            .map(Self)
+            .map_err(|_| format!("{} is not a valid IANA time zone.", s))
+    }
+
+    /// The instant the wall clocks in this zone show `local`. A time skipped
+    /// by a daylight saving change is moved an hour later, a repeated one
+    /// resolves to its first occurrence.
+    pub fn instant(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        self.0
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                self.0
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|instant| instant.with_timezone(&Utc))
+            .unwrap_or_else(|| local.and_utc())
+    }
+}
+
+impl AsRef<str> for SubscriberTimeZone {
+    fn as_ref(&self) -> &str {
+        self.0.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberTimeZone;
+    use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+    use claim::{assert_err, assert_ok};
+
+    fn local(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn iana_names_are_accepted() {
+        for name in ["UTC", "Europe/Berlin", "America/Los_Angeles", "Asia/Tokyo"] {
+            assert_ok!(SubscriberTimeZone::parse(name.into()));
+        }
+    }
+
+    #[test]
+    fn unknown_names_and_offsets_are_rejected() {
+        for name in ["", "Mars/Olympus_Mons", "+02:00", "europe berlin"] {
+            assert_err!(SubscriberTimeZone::parse(name.into()));
+        }
+    }
+
+    #[test]
+    fn local_times_are_converted_to_the_matching_instant() {
+        let tokyo = SubscriberTimeZone::parse("Asia/Tokyo".into()).unwrap();
+        let berlin = SubscriberTimeZone::parse("Europe/Berlin".into()).unwrap();
+
+        assert_eq!(
+            tokyo.instant(local(10, 19, 9)),
+            Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap()
+        );
+        // Summer and winter time
+        assert_eq!(
+            berlin.instant(local(10, 19, 9)),
+            Utc.with_ymd_and_hms(2026, 10, 19, 7, 0, 0).unwrap()
+        );
+        assert_eq!(
+            berlin.instant(local(11, 2, 9)),
+            Utc.with_ymd_and_hms(2026, 11, 2, 8, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn skipped_local_times_are_moved_an_hour_later() {
+        let berlin = SubscriberTimeZone::parse("Europe/Berlin".into()).unwrap();
+        // Clocks jumped from 2:00 to 3:00 on 29 March 2026
+        assert_eq!(
+            berlin.instant(local(3, 29, 2)),
+            Utc.with_ymd_and_hms(2026, 3, 29, 1, 0, 0).unwrap()
+        );
+    }
+}
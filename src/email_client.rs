@@ -1,190 +0,0 @@
-use anyhow::Result;
-use reqwest::Client;
-use secrecy::{ExposeSecret, Secret};
-
-use crate::domain::SubscriberEmail;
-
-pub struct EmailClient {
-    http_client: Client,
-    base_url: String,
-    client_secret: Secret<String>,
-    sender: SubscriberEmail,
-}
-
-impl EmailClient {
-    pub fn new(base_url: String, client_secret: Secret<String>, sender: SubscriberEmail) -> Self {
-        Self {
-            http_client: Client::new(),
-            base_url,
-            client_secret,
-            sender,
-        }
-    }
-
-    pub async fn send_email(
-        &self,
-        recipient: SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<()> {
-        let url = format!("{}/v3/mail/send", self.base_url);
-        let request_body = SendEmailRequest {
-            from: FromField {
-                email: self.sender.as_ref(),
-                name: self.sender.as_ref(),
-            },
-            personalizations: vec![Personalization {
-                to: recipient.as_ref(),
-            }],
-            subject,
-            content: vec![
-                ContentField {
-                    content_type: "text/html",
-                    content: html_content,
-                },
-                ContentField {
-                    content_type: "text/plain",
-                    content: text_content,
-                },
-            ],
-        };
-
-        self.http_client
-            .post(&url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.client_secret.expose_secret()),
-            )
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
-    }
-}
-
-#[derive(serde::Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct SendEmailRequest<'a> {
-    from: FromField<'a>,
-    personalizations: Vec<Personalization<'a>>,
-    subject: &'a str,
-    content: Vec<ContentField<'a>>,
-}
-
-#[derive(serde::Serialize)]
-struct FromField<'a> {
-    email: &'a str,
-    name: &'a str,
-}
-
-#[derive(serde::Serialize)]
-struct ContentField<'a> {
-    content_type: &'a str,
-    content: &'a str,
-}
-
-#[derive(serde::Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Personalization<'a> {
-    to: &'a str,
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
-    use claim::{assert_err, assert_ok};
-    use fake::faker::internet::en::SafeEmail;
-    use fake::faker::lorem::en::{Paragraph, Sentence};
-    use fake::{Fake, Faker};
-    use secrecy::Secret;
-    use wiremock::matchers::{any, header, header_exists, method, path};
-    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
-
-    struct SendEmailBodyMatcher;
-
-    impl wiremock::Match for SendEmailBodyMatcher {
-        fn matches(&self, request: &Request) -> bool {
-            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
-            if let Ok(body) = result {
-                body.get("From").is_some()
-                    && body.get("Subject").is_some()
-                    && body.get("Content").is_some()
-                    && body.get("Personalizations").is_some()
-            } else {
-                false
-            }
-        }
-    }
-
-    #[tokio::test]
-    async fn send_email_fires_a_request_to_base_url() {
-        let mock_server = MockServer::start().await;
-        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let email_client = EmailClient::new(mock_server.uri(), Secret::new(Faker.fake()), sender);
-        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let subject: String = Sentence(1..2).fake();
-        let content: String = Paragraph(1..10).fake();
-
-        Mock::given(header_exists("Authorization"))
-            .and(header("Content-Type", "application/json"))
-            .and(path("/v3/mail/send"))
-            .and(method("POST"))
-            .and(SendEmailBodyMatcher)
-            .respond_with(ResponseTemplate::new(202))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let _ = email_client
-            .send_email(subscriber_email, &subject, &content, &content)
-            .await;
-    }
-
-    #[tokio::test]
-    async fn send_email_succeeds_if_the_server_returns_202() {
-        let mock_server = MockServer::start().await;
-        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let email_client = EmailClient::new(mock_server.uri(), Secret::new(Faker.fake()), sender);
-        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let subject: String = Sentence(1..2).fake();
-        let content: String = Paragraph(1..10).fake();
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(202))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let outcome = email_client
-            .send_email(subscriber_email, &subject, &content, &content)
-            .await;
-
-        assert_ok!(outcome);
-    }
-
-    #[tokio::test]
-    async fn send_email_fails_if_the_server_returns_500() {
-        let mock_server = MockServer::start().await;
-        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let email_client = EmailClient::new(mock_server.uri(), Secret::new(Faker.fake()), sender);
-        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
-        let subject: String = Sentence(1..2).fake();
-        let content: String = Paragraph(1..10).fake();
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(500))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let outcome = email_client
-            .send_email(subscriber_email, &subject, &content, &content)
-            .await;
-
-        assert_err!(outcome);
-    }
-}
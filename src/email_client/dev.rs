@@ -0,0 +1,81 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::email_client::smtp::mime_message;
+use crate::email_client::{Email, EmailSender};
+
+/// Delivers nothing: writes every email as an `.eml` file into a directory,
+/// or to stdout when there is none, for local development.
+pub struct DevSender {
+    output_dir: Option<PathBuf>,
+}
+
+impl DevSender {
+    pub fn new(output_dir: Option<PathBuf>) -> Self {
+        Self { output_dir }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for DevSender {
+    async fn send(&self, email: &Email<'_>) -> Result<()> {
+        let message = mime_message(email)?.formatted();
+        match &self.output_dir {
+            Some(output_dir) => {
+                tokio::fs::create_dir_all(output_dir)
+                    .await
+                    .context("Failed to create the email output directory")?;
+                let file_name = format!(
+                    "{}-{}.eml",
+                    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+                    uuid::Uuid::new_v4()
+                );
+                tokio::fs::write(output_dir.join(file_name), message)
+                    .await
+                    .context("Failed to write the email")?;
+            }
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&message)?;
+                writeln!(stdout)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{DevSender, EmailClient};
+    use claim::assert_ok;
+
+    #[tokio::test]
+    async fn emails_are_written_to_the_output_directory() {
+        let output_dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let sender = SubscriberEmail::parse("newsletter@example.com".into()).unwrap();
+        let email_client =
+            EmailClient::new(sender, Arc::new(DevSender::new(Some(output_dir.clone()))));
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let outcome = email_client
+            .send_email(recipient, "Welcome!", "<p>Hi</p>", "Hi")
+            .await;
+
+        assert_ok!(outcome);
+        let files: Vec<_> = std::fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let message = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(message.contains("To: ursula@example.com"));
+        assert!(message.contains("Subject: Welcome!"));
+        std::fs::remove_dir_all(output_dir).unwrap();
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::{Client, Response};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    send_http_request, substitute, Attachment, Disposition, Email, EmailBatch, EmailSender,
+    SenderIdentity,
+};
+
+/// How many personalizations the API accepts in a single request.
+const MAX_PERSONALIZATIONS: usize = 1000;
+
+/// Sends emails through the `/v3/mail/send` JSON API.
+pub struct HttpApiSender {
+    http_client: Client,
+    base_url: String,
+    client_secret: Secret<String>,
+}
+
+impl HttpApiSender {
+    pub fn new(base_url: String, client_secret: Secret<String>, timeout: Duration) -> Result<Self> {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build the HTTP client")?;
+        Ok(Self {
+            http_client,
+            base_url,
+            client_secret,
+        })
+    }
+
+    async fn post(&self, request_body: &SendEmailRequest<'_>) -> Result<Response> {
+        let request = self
+            .http_client
+            .post(format!("{}/v3/mail/send", self.base_url))
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.client_secret.expose_secret()),
+            )
+            .json(request_body);
+        send_http_request(request).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for HttpApiSender {
+    async fn send(&self, email: &Email<'_>) -> Result<()> {
+        let request_body = SendEmailRequest {
+            from: Mailbox::sender(email.from),
+            reply_to: Mailbox::reply_to(email.from),
+            personalizations: vec![Personalization {
+                to: email.to.as_ref(),
+                cc: addresses(email.cc),
+                bcc: addresses(email.bcc),
+                substitutions: BTreeMap::new(),
+                headers: BTreeMap::new(),
+            }],
+            subject: email.subject,
+            content: vec![
+                ContentField {
+                    content_type: "text/html",
+                    content: email.html_content,
+                },
+                ContentField {
+                    content_type: "text/plain",
+                    content: email.text_content,
+                },
+            ],
+            headers: email.headers.clone(),
+            attachments: email
+                .attachments
+                .iter()
+                .map(AttachmentField::from)
+                .collect(),
+        };
+        self.post(&request_body).await?;
+        Ok(())
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_PERSONALIZATIONS
+    }
+
+    /// The API substitutes the subject and content itself, so the batch goes
+    /// out as a single request; per-recipient header values are filled in
+    /// here. The API puts the same value into every part, so the HTML part
+    /// refers to HTML-escaped copies of the values under placeholders of its
+    /// own.
+    async fn send_batch(&self, batch: &EmailBatch<'_>) -> Result<Vec<Result<()>>> {
+        let html_placeholders: BTreeMap<String, String> = batch
+            .personalizations
+            .iter()
+            .flat_map(|personalization| personalization.substitutions.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .enumerate()
+            .map(|(index, placeholder)| (placeholder.clone(), format!("{{{{html_{}}}}}", index)))
+            .collect();
+        let html_content = substitute(batch.html_content, &html_placeholders, str::to_owned);
+        let request_body = SendEmailRequest {
+            from: Mailbox::sender(batch.from),
+            reply_to: Mailbox::reply_to(batch.from),
+            personalizations: batch
+                .personalizations
+                .iter()
+                .map(|personalization| Personalization {
+                    to: personalization.to.as_ref(),
+                    cc: vec![],
+                    bcc: vec![],
+                    substitutions: personalization
+                        .substitutions
+                        .iter()
+                        .flat_map(|(placeholder, value)| {
+                            [
+                                (placeholder.as_str(), value.clone()),
+                                (
+                                    html_placeholders[placeholder].as_str(),
+                                    htmlescape::encode_minimal(value),
+                                ),
+                            ]
+                        })
+                        .collect(),
+                    headers: batch
+                        .headers
+                        .iter()
+                        .map(|(name, value)| (*name, personalization.substitute(value)))
+                        .collect(),
+                })
+                .collect(),
+            subject: batch.subject,
+            content: vec![
+                ContentField {
+                    content_type: "text/html",
+                    content: &html_content,
+                },
+                ContentField {
+                    content_type: "text/plain",
+                    content: batch.text_content,
+                },
+            ],
+            headers: BTreeMap::new(),
+            attachments: vec![],
+        };
+        self.post(&request_body).await?;
+        Ok(batch.personalizations.iter().map(|_| Ok(())).collect())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: Mailbox<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<Mailbox<'a>>,
+    personalizations: Vec<Personalization<'a>>,
+    subject: &'a str,
+    content: Vec<ContentField<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<&'a str, &'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<AttachmentField<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct AttachmentField<'a> {
+    content: String,
+    filename: &'a str,
+    #[serde(rename = "type")]
+    content_type: &'a str,
+    disposition: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_id: Option<&'a str>,
+}
+
+impl<'a> From<&'a Attachment> for AttachmentField<'a> {
+    fn from(attachment: &'a Attachment) -> Self {
+        Self {
+            content: attachment.base64_content(),
+            filename: &attachment.filename,
+            content_type: &attachment.content_type,
+            disposition: match attachment.disposition {
+                Disposition::Attachment => "attachment",
+                Disposition::Inline { .. } => "inline",
+            },
+            content_id: attachment.content_id(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Mailbox<'a> {
+    email: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+}
+
+impl<'a> Mailbox<'a> {
+    fn sender(identity: &'a SenderIdentity) -> Self {
+        Self {
+            email: identity.email.as_ref(),
+            name: identity.name.as_deref(),
+        }
+    }
+
+    fn reply_to(identity: &'a SenderIdentity) -> Option<Self> {
+        identity.reply_to.as_ref().map(|reply_to| Self {
+            email: reply_to.as_ref(),
+            name: None,
+        })
+    }
+}
+
+fn addresses(emails: &[SubscriberEmail]) -> Vec<&str> {
+    emails.iter().map(AsRef::as_ref).collect()
+}
+
+#[derive(serde::Serialize)]
+struct ContentField<'a> {
+    content_type: &'a str,
+    content: &'a str,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Personalization<'a> {
+    to: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cc: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bcc: Vec<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    substitutions: BTreeMap<&'a str, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<&'a str, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use std::collections::BTreeMap;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        Attachment, Disposition, EmailClient, HttpApiSender, MessageOptions, Personalization,
+    };
+    use claim::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn email_client(base_url: String) -> EmailClient {
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let transport = HttpApiSender::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        EmailClient::new(sender, Arc::new(transport))
+    }
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("Content").is_some()
+                    && body.get("Personalizations").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_a_request_to_base_url() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..10).fake();
+
+        Mock::given(header_exists("Authorization"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(subscriber_email, &subject, &content, &content)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_newsletter_email_sets_list_unsubscribe_headers() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..10).fake();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        email_client
+            .send_newsletter_email(
+                subscriber_email,
+                &subject,
+                &content,
+                &content,
+                "https://example.com/unsubscribe?token=abc",
+                &MessageOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["Headers"]["List-Unsubscribe"],
+            "<https://example.com/unsubscribe?token=abc>"
+        );
+        assert_eq!(
+            body["Headers"]["List-Unsubscribe-Post"],
+            "List-Unsubscribe=One-Click"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_202() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..10).fake();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..10).fake();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(subscriber_email, &subject, &content, &content)
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_includes_the_sender_identity_and_message_options() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_sender_name("Zero Newsletter".into())
+            .with_reply_to(SubscriberEmail::parse("editor@example.com".into()).unwrap());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let recipient = || SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        email_client
+            .send_email(recipient(), "Hi", "<p>Hi</p>", "Hi")
+            .await
+            .unwrap();
+        let options = MessageOptions {
+            sender_name: Some("The Editors".into()),
+            reply_to: Some(SubscriberEmail::parse("letters@example.com".into()).unwrap()),
+            cc: vec![SubscriberEmail::parse("octavia@example.com".into()).unwrap()],
+            bcc: vec![SubscriberEmail::parse("archive@example.com".into()).unwrap()],
+            headers: BTreeMap::from([("X-Campaign".into(), "spring".into())]),
+            ..MessageOptions::default()
+        };
+        email_client
+            .send_email_with_options(recipient(), "Hi", "<p>Hi</p>", "Hi", &options)
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["From"]["name"], "Zero Newsletter");
+        assert_eq!(body["ReplyTo"]["email"], "editor@example.com");
+        assert!(body["Personalizations"][0].get("Cc").is_none());
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(body["From"]["name"], "The Editors");
+        assert_eq!(body["ReplyTo"]["email"], "letters@example.com");
+        assert_eq!(body["Personalizations"][0]["Cc"][0], "octavia@example.com");
+        assert_eq!(body["Personalizations"][0]["Bcc"][0], "archive@example.com");
+        assert_eq!(body["Headers"]["X-Campaign"], "spring");
+    }
+
+    #[tokio::test]
+    async fn send_email_encodes_attachments_and_inline_images() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let options = MessageOptions {
+            attachments: vec![
+                Attachment {
+                    filename: "issue.pdf".into(),
+                    content_type: "application/pdf".into(),
+                    content: b"%PDF".to_vec(),
+                    disposition: Disposition::Attachment,
+                },
+                Attachment {
+                    filename: "logo.png".into(),
+                    content_type: "image/png".into(),
+                    content: vec![0x89, b'P', b'N', b'G'],
+                    disposition: Disposition::Inline {
+                        content_id: "logo".into(),
+                    },
+                },
+            ],
+            ..MessageOptions::default()
+        };
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        email_client(mock_server.uri())
+            .send_email_with_options(recipient, "Hi", r#"<img src="cid:logo">"#, "Hi", &options)
+            .await
+            .unwrap();
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let attachments = &body["Attachments"];
+        assert_eq!(attachments[0]["content"], "JVBERg==");
+        assert_eq!(attachments[0]["filename"], "issue.pdf");
+        assert_eq!(attachments[0]["type"], "application/pdf");
+        assert_eq!(attachments[0]["disposition"], "attachment");
+        assert!(attachments[0].get("content_id").is_none());
+        assert_eq!(attachments[1]["disposition"], "inline");
+        assert_eq!(attachments[1]["content_id"], "logo");
+    }
+
+    fn personalizations(names: &[&str]) -> Vec<Personalization> {
+        names
+            .iter()
+            .map(|name| Personalization {
+                to: SubscriberEmail::parse(format!("{}@example.com", name)).unwrap(),
+                substitutions: BTreeMap::from([("{{name}}".into(), name.to_string())]),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn send_batch_packs_every_recipient_into_one_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(path("/v3/mail/send"))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client
+            .send_batch(
+                &personalizations(&["ursula", "octavia"]),
+                "Hi {{name}}",
+                "<p>Hello {{name}}</p>",
+                "Hello {{name}}",
+                BTreeMap::from([("X-Recipient", "{{name}}")]),
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.is_ok()));
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["Subject"], "Hi {{name}}");
+        assert_eq!(body["Content"][0]["content"], "<p>Hello {{html_0}}</p>");
+        assert_eq!(body["Content"][1]["content"], "Hello {{name}}");
+        let personalizations = body["Personalizations"].as_array().unwrap();
+        assert_eq!(personalizations.len(), 2);
+        assert_eq!(personalizations[1]["To"], "octavia@example.com");
+        assert_eq!(personalizations[1]["Substitutions"]["{{name}}"], "octavia");
+        assert_eq!(
+            personalizations[1]["Substitutions"]["{{html_0}}"],
+            "octavia"
+        );
+        assert_eq!(personalizations[1]["Headers"]["X-Recipient"], "octavia");
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_a_failed_request_for_every_recipient() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client
+            .send_batch(
+                &personalizations(&["ursula", "octavia"]),
+                "Hi",
+                "<p>Hello</p>",
+                "Hello",
+                BTreeMap::new(),
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.is_err()));
+    }
+
+    #[tokio::test]
+    async fn send_batch_escapes_the_values_that_go_into_the_html_part() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let personalizations = [Personalization {
+            to: SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            substitutions: BTreeMap::from([(
+                "{{link}}".into(),
+                "https://example.com/?a=1&b=2".into(),
+            )]),
+        }];
+
+        email_client
+            .send_batch(
+                &personalizations,
+                "Hi",
+                r#"<a href="{{link}}">Read</a>"#,
+                "Read: {{link}}",
+                BTreeMap::new(),
+                &MessageOptions::default(),
+            )
+            .await;
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let substitutions = &body["Personalizations"][0]["Substitutions"];
+        assert_eq!(
+            body["Content"][0]["content"],
+            r#"<a href="{{html_0}}">Read</a>"#
+        );
+        assert_eq!(
+            substitutions["{{html_0}}"],
+            "https://example.com/?a=1&amp;b=2"
+        );
+        assert_eq!(substitutions["{{link}}"], "https://example.com/?a=1&b=2");
+    }
+}
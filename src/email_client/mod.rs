@@ -0,0 +1,944 @@
+mod dev;
+mod http_api;
+mod postmark;
+mod resilience;
+mod smtp;
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use chrono::Utc;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use crate::domain::SubscriberEmail;
+
+pub use dev::DevSender;
+pub use http_api::HttpApiSender;
+pub use postmark::PostmarkSender;
+pub use resilience::{CircuitBreaker, RetryPolicy};
+pub use smtp::SmtpSender;
+
+/// Who emails come from, as their recipients see it.
+#[derive(Clone)]
+pub struct SenderIdentity {
+    pub email: SubscriberEmail,
+    /// Shown instead of the bare address.
+    pub name: Option<String>,
+    /// Where replies go instead of `email`.
+    pub reply_to: Option<SubscriberEmail>,
+}
+
+impl SenderIdentity {
+    pub fn new(email: SubscriberEmail) -> Self {
+        Self {
+            email,
+            name: None,
+            reply_to: None,
+        }
+    }
+
+    /// The `From` header value: `"Name" <address>`, or the bare address
+    /// without a name.
+    pub fn mailbox(&self) -> String {
+        match &self.name {
+            Some(name) => format!(
+                "\"{}\" <{}>",
+                name.replace('\\', "\\\\").replace('"', "\\\""),
+                self.email.as_ref()
+            ),
+            None => self.email.as_ref().to_string(),
+        }
+    }
+}
+
+/// How much attachment data, before encoding, a single email may carry.
+pub const MAX_ATTACHMENTS_SIZE: usize = 10 * 1024 * 1024;
+
+/// A file sent along with an email.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub filename: String,
+    /// The MIME type, e.g. `application/pdf`.
+    pub content_type: String,
+    pub content: Vec<u8>,
+    pub disposition: Disposition,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Disposition {
+    /// Offered for download.
+    Attachment,
+    /// Embedded in the HTML content, which refers to it as `cid:<content_id>`.
+    Inline { content_id: String },
+}
+
+impl Attachment {
+    pub fn base64_content(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.content)
+    }
+
+    pub fn content_id(&self) -> Option<&str> {
+        match &self.disposition {
+            Disposition::Attachment => None,
+            Disposition::Inline { content_id } => Some(content_id),
+        }
+    }
+}
+
+/// Per-message changes to what [`EmailClient`] sends by default.
+#[derive(Clone, Default)]
+pub struct MessageOptions {
+    /// Replaces the configured sender name.
+    pub sender_name: Option<String>,
+    /// Replaces the configured reply-to address.
+    pub reply_to: Option<SubscriberEmail>,
+    pub cc: Vec<SubscriberEmail>,
+    pub bcc: Vec<SubscriberEmail>,
+    /// Added to the email's own headers, replacing those with the same name.
+    pub headers: BTreeMap<String, String>,
+    /// At most [`MAX_ATTACHMENTS_SIZE`] bytes in total.
+    pub attachments: Vec<Attachment>,
+}
+
+/// A single message, ready to be handed over to an [`EmailSender`].
+pub struct Email<'a> {
+    pub from: &'a SenderIdentity,
+    pub to: &'a SubscriberEmail,
+    pub cc: &'a [SubscriberEmail],
+    pub bcc: &'a [SubscriberEmail],
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: BTreeMap<&'a str, &'a str>,
+    pub attachments: &'a [Attachment],
+}
+
+/// One recipient of an [`EmailBatch`], with the values that replace the
+/// batch's placeholders in their copy of the email.
+pub struct Personalization {
+    pub to: SubscriberEmail,
+    pub substitutions: BTreeMap<String, String>,
+}
+
+impl Personalization {
+    fn substitute(&self, template: &str) -> String {
+        substitute(template, &self.substitutions, str::to_owned)
+    }
+
+    /// Substitutes HTML-escaped values, for the HTML part.
+    fn substitute_html(&self, template: &str) -> String {
+        substitute(template, &self.substitutions, htmlescape::encode_minimal)
+    }
+}
+
+/// Replaces every placeholder in `template` with its `escape`d value in a
+/// single pass, so placeholders that turn up in the values are left alone.
+/// Where placeholders overlap, the longest one wins.
+fn substitute(
+    template: &str,
+    substitutions: &BTreeMap<String, String>,
+    escape: fn(&str) -> String,
+) -> String {
+    let first_chars: Vec<char> = substitutions
+        .keys()
+        .filter_map(|placeholder| placeholder.chars().next())
+        .collect();
+    let mut substituted = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(first_chars.as_slice()) {
+        let (before, candidate) = rest.split_at(start);
+        substituted.push_str(before);
+        let matched = substitutions
+            .iter()
+            .filter(|(placeholder, _)| {
+                !placeholder.is_empty() && candidate.starts_with(placeholder.as_str())
+            })
+            .max_by_key(|(placeholder, _)| placeholder.len());
+        rest = match matched {
+            Some((placeholder, value)) => {
+                substituted.push_str(&escape(value));
+                &candidate[placeholder.len()..]
+            }
+            None => {
+                let width = candidate.chars().next().map_or(0, char::len_utf8);
+                substituted.push_str(&candidate[..width]);
+                &candidate[width..]
+            }
+        };
+    }
+    substituted.push_str(rest);
+    substituted
+}
+
+/// The same email, sent to several recipients at once, without attachments.
+pub struct EmailBatch<'a> {
+    pub from: &'a SenderIdentity,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: BTreeMap<&'a str, &'a str>,
+    pub personalizations: &'a [Personalization],
+}
+
+impl EmailBatch<'_> {
+    /// The copy of the email `personalization`'s recipient gets.
+    fn personalize(&self, personalization: &Personalization) -> PersonalizedEmail {
+        let substitute = |template: &str| personalization.substitute(template);
+        PersonalizedEmail {
+            subject: substitute(self.subject),
+            html_content: personalization.substitute_html(self.html_content),
+            text_content: substitute(self.text_content),
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), substitute(value)))
+                .collect(),
+        }
+    }
+}
+
+struct PersonalizedEmail {
+    subject: String,
+    html_content: String,
+    text_content: String,
+    headers: BTreeMap<String, String>,
+}
+
+impl PersonalizedEmail {
+    fn email<'a>(&'a self, from: &'a SenderIdentity, to: &'a SubscriberEmail) -> Email<'a> {
+        Email {
+            from,
+            to,
+            cc: &[],
+            bcc: &[],
+            attachments: &[],
+            subject: &self.subject,
+            html_content: &self.html_content,
+            text_content: &self.text_content,
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect(),
+        }
+    }
+}
+
+/// Delivers emails through one provider.
+///
+/// Failures caused by the provider rather than by the email itself are
+/// reported as a [`ProviderError`], so that they can be retried.
+#[async_trait::async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, email: &Email<'_>) -> Result<()>;
+
+    /// How many recipients a single [`EmailSender::send_batch`] call accepts.
+    fn max_batch_size(&self) -> usize {
+        1
+    }
+
+    /// Sends `batch` in one go, returning the outcome for every recipient in
+    /// order. An `Err` means that nothing was sent.
+    ///
+    /// Providers without a batch API send batches of a single recipient.
+    async fn send_batch(&self, batch: &EmailBatch<'_>) -> Result<Vec<Result<()>>> {
+        let [personalization] = batch.personalizations else {
+            bail!("This provider sends one email at a time");
+        };
+        let personalized = batch.personalize(personalization);
+        self.send(&personalized.email(batch.from, &personalization.to))
+            .await?;
+        Ok(vec![Ok(())])
+    }
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    /// The provider is overloaded or failing (HTTP 429 or 5xx, SMTP 4xx);
+    /// sending again later may succeed.
+    Transient {
+        retry_after: Option<Duration>,
+        reason: String,
+    },
+    /// The provider could not be reached or did not answer in time.
+    Unreachable(anyhow::Error),
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::Transient { reason, .. } => write!(f, "{}", reason),
+            ProviderError::Unreachable(_) => write!(f, "The email provider is unreachable"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProviderError::Transient { .. } => None,
+            ProviderError::Unreachable(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+/// Sends `request` to an HTTP provider, telling provider failures apart from
+/// rejected emails.
+async fn send_http_request(request: RequestBuilder) -> Result<Response> {
+    let response = request.send().await.map_err(|e| {
+        if e.is_builder() {
+            anyhow::Error::from(e)
+        } else {
+            ProviderError::Unreachable(e.into()).into()
+        }
+    })?;
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        return Err(ProviderError::Transient {
+            retry_after: retry_after(response.headers()),
+            reason: format!("The email provider responded with {}", status),
+        }
+        .into());
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Reads `Retry-After`, given either in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Composes the application's emails and sends them as `sender` through
+/// whichever [`EmailSender`] it was configured with, retrying transient
+/// provider failures and failing fast while the provider keeps failing.
+#[derive(Clone)]
+pub struct EmailClient {
+    sender: SenderIdentity,
+    transport: Arc<dyn EmailSender>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl EmailClient {
+    /// A client that neither retries nor trips; see
+    /// [`EmailClient::with_retry_policy`] and
+    /// [`EmailClient::with_circuit_breaker`].
+    pub fn new(sender: SubscriberEmail, transport: Arc<dyn EmailSender>) -> Self {
+        Self {
+            sender: SenderIdentity::new(sender),
+            transport,
+            retry_policy: RetryPolicy::none(),
+            circuit_breaker: Arc::new(CircuitBreaker::disabled()),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Arc::new(circuit_breaker);
+        self
+    }
+
+    pub fn with_sender_name(mut self, name: String) -> Self {
+        self.sender.name = Some(name);
+        self
+    }
+
+    pub fn with_reply_to(mut self, reply_to: SubscriberEmail) -> Self {
+        self.sender.reply_to = Some(reply_to);
+        self
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<()> {
+        self.send_email_with_options(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            &MessageOptions::default(),
+        )
+        .await
+    }
+
+    pub async fn send_email_with_options(
+        &self,
+        recipient: SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        options: &MessageOptions,
+    ) -> Result<()> {
+        self.send(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            BTreeMap::new(),
+            options,
+        )
+        .await
+    }
+
+    /// Sends a newsletter issue, advertising `unsubscribe_link` through the
+    /// `List-Unsubscribe` headers so that mail clients can offer one-click
+    /// unsubscription (RFC 8058).
+    pub async fn send_newsletter_email(
+        &self,
+        recipient: SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: &str,
+        options: &MessageOptions,
+    ) -> Result<()> {
+        let list_unsubscribe = format!("<{}>", unsubscribe_link);
+        let headers = BTreeMap::from([
+            ("List-Unsubscribe", list_unsubscribe.as_str()),
+            ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+        ]);
+        self.send(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+            options,
+        )
+        .await
+    }
+
+    /// Like [`EmailClient::send_newsletter_email`], for every recipient of a
+    /// batch; see [`EmailClient::send_batch`]. `unsubscribe_link` is usually a
+    /// placeholder.
+    pub async fn send_newsletter_batch(
+        &self,
+        personalizations: &[Personalization],
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: &str,
+        options: &MessageOptions,
+    ) -> Vec<Result<()>> {
+        let list_unsubscribe = format!("<{}>", unsubscribe_link);
+        let headers = BTreeMap::from([
+            ("List-Unsubscribe", list_unsubscribe.as_str()),
+            ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+        ]);
+        self.send_batch(
+            personalizations,
+            subject,
+            html_content,
+            text_content,
+            headers,
+            options,
+        )
+        .await
+    }
+
+    /// How many recipients the provider takes in a single batch request.
+    pub fn max_batch_size(&self) -> usize {
+        self.transport.max_batch_size().max(1)
+    }
+
+    /// Sends the same email to every recipient, in as few requests as the
+    /// provider allows, replacing each placeholder in the subject, content and
+    /// header values with the recipient's substitution. Values are
+    /// HTML-escaped in the HTML content. Returns the outcome for every
+    /// recipient, in order, so that failed ones can be retried alone.
+    ///
+    /// Batches carry neither copies nor attachments.
+    pub async fn send_batch(
+        &self,
+        personalizations: &[Personalization],
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: BTreeMap<&str, &str>,
+        options: &MessageOptions,
+    ) -> Vec<Result<()>> {
+        let composed =
+            if options.cc.is_empty() && options.bcc.is_empty() && options.attachments.is_empty() {
+                self.compose(headers, options)
+            } else {
+                Err(anyhow!("Batches cannot carry copies or attachments"))
+            };
+        let (sender, headers) = match composed {
+            Ok(composed) => composed,
+            Err(e) => {
+                return personalizations
+                    .iter()
+                    .map(|_| Err(anyhow!("{:#}", e)))
+                    .collect()
+            }
+        };
+        let mut outcomes = Vec::with_capacity(personalizations.len());
+        for chunk in personalizations.chunks(self.max_batch_size()) {
+            let batch = EmailBatch {
+                from: &sender,
+                subject,
+                html_content,
+                text_content,
+                headers: headers.clone(),
+                personalizations: chunk,
+            };
+            match self
+                .with_resilience(|| self.transport.send_batch(&batch))
+                .await
+            {
+                Ok(chunk_outcomes) => outcomes.extend(chunk_outcomes),
+                Err(e) => outcomes.extend(chunk.iter().map(|_| Err(anyhow!("{:#}", e)))),
+            }
+        }
+        outcomes
+    }
+
+    async fn send(
+        &self,
+        recipient: SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: BTreeMap<&str, &str>,
+        options: &MessageOptions,
+    ) -> Result<()> {
+        let (sender, headers) = self.compose(headers, options)?;
+        // A line break would let these start headers of their own
+        let mut attachment_texts = options.attachments.iter().flat_map(|attachment| {
+            [
+                Some(attachment.filename.as_str()),
+                Some(attachment.content_type.as_str()),
+                attachment.content_id(),
+            ]
+            .into_iter()
+            .flatten()
+        });
+        if attachment_texts.any(|text| text.contains(['\r', '\n'])) {
+            bail!("Attachment details must not contain line breaks");
+        }
+        let attachments_size: usize = options
+            .attachments
+            .iter()
+            .map(|attachment| attachment.content.len())
+            .sum();
+        if attachments_size > MAX_ATTACHMENTS_SIZE {
+            bail!(
+                "The attachments take up {} bytes, more than the limit of {}",
+                attachments_size,
+                MAX_ATTACHMENTS_SIZE
+            );
+        }
+        let email = Email {
+            from: &sender,
+            to: &recipient,
+            cc: &options.cc,
+            bcc: &options.bcc,
+            subject,
+            html_content,
+            text_content,
+            headers,
+            attachments: &options.attachments,
+        };
+        self.with_resilience(|| self.transport.send(&email)).await
+    }
+
+    /// The sender and headers of an email sent with `options`, on top of the
+    /// configured sender and `headers`.
+    fn compose<'a>(
+        &self,
+        headers: BTreeMap<&'a str, &'a str>,
+        options: &'a MessageOptions,
+    ) -> Result<(SenderIdentity, BTreeMap<&'a str, &'a str>)> {
+        let mut sender = self.sender.clone();
+        if let Some(name) = &options.sender_name {
+            sender.name = Some(name.clone());
+        }
+        if let Some(reply_to) = &options.reply_to {
+            sender.reply_to = Some(reply_to.clone());
+        }
+        let headers: BTreeMap<&str, &str> = headers
+            .into_iter()
+            .chain(
+                options
+                    .headers
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            )
+            .collect();
+        // A line break would let these start headers of their own
+        let header_texts = headers.iter().flat_map(|(name, value)| [*name, *value]);
+        if sender
+            .name
+            .iter()
+            .map(String::as_str)
+            .chain(header_texts)
+            .any(|text| text.contains(['\r', '\n']))
+        {
+            bail!("Sender names and headers must not contain line breaks");
+        }
+        Ok((sender, headers))
+    }
+
+    /// Runs `attempt` unless the circuit breaker is open, retrying it as long
+    /// as it fails transiently and the retry policy allows.
+    async fn with_resilience<T, F, Fut>(&self, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if !self.circuit_breaker.allow() {
+            return Err(anyhow!(
+                "Not sending: the email provider failed too many times in a row"
+            ));
+        }
+        let mut retry = 0;
+        loop {
+            let e = match attempt().await {
+                Ok(outcome) => {
+                    self.circuit_breaker.record_success();
+                    return Ok(outcome);
+                }
+                Err(e) => e,
+            };
+            let retry_after = match e.downcast_ref::<ProviderError>() {
+                Some(ProviderError::Transient { retry_after, .. }) => *retry_after,
+                Some(ProviderError::Unreachable(_)) => {
+                    self.circuit_breaker.record_failure();
+                    return Err(e);
+                }
+                // The provider is fine, it rejected this particular email.
+                None => {
+                    self.circuit_breaker.record_success();
+                    return Err(e);
+                }
+            };
+            match self.retry_policy.delay(retry, retry_after) {
+                Some(delay) => {
+                    tracing::warn!(error = %e, ?delay, "Retrying a failed email send");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                None => {
+                    self.circuit_breaker.record_failure();
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        retry_after, Attachment, CircuitBreaker, Disposition, Email, EmailClient, EmailSender,
+        HttpApiSender, MessageOptions, Personalization, RetryPolicy, SenderIdentity,
+        MAX_ATTACHMENTS_SIZE,
+    };
+    use claim::{assert_err, assert_ok};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use secrecy::Secret;
+    use wiremock::matchers::any;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn email_client(base_url: String) -> EmailClient {
+        let sender = SubscriberEmail::parse("newsletter@example.com".into()).unwrap();
+        let transport = HttpApiSender::new(
+            base_url,
+            Secret::new("secret".into()),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        EmailClient::new(sender, Arc::new(transport)).with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+        })
+    }
+
+    async fn send(email_client: &EmailClient) -> anyhow::Result<()> {
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+        email_client
+            .send_email(recipient, "Welcome!", "<p>Hi</p>", "Hi")
+            .await
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_err!(send(&email_client(mock_server.uri())).await);
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_after_the_requested_delay() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let started = std::time::Instant::now();
+        let outcome = send(&email_client(mock_server.uri())).await;
+
+        assert_ok!(outcome);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn retries_are_bounded() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        assert_err!(send(&email_client(mock_server.uri())).await);
+    }
+
+    #[tokio::test]
+    async fn rejected_emails_are_not_retried() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_err!(send(&email_client(mock_server.uri())).await);
+    }
+
+    #[tokio::test]
+    async fn sends_fail_fast_once_the_circuit_breaker_has_tripped() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        let email_client = email_client(mock_server.uri())
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+
+        assert_err!(send(&email_client).await);
+        assert_err!(send(&email_client).await);
+    }
+
+    #[test]
+    fn the_sender_name_is_quoted_in_the_mailbox() {
+        let mut sender =
+            SenderIdentity::new(SubscriberEmail::parse("newsletter@example.com".into()).unwrap());
+        assert_eq!(sender.mailbox(), "newsletter@example.com");
+
+        sender.name = Some(r#"Zero "Weekly""#.into());
+        assert_eq!(
+            sender.mailbox(),
+            r#""Zero \"Weekly\"" <newsletter@example.com>"#
+        );
+    }
+
+    #[tokio::test]
+    async fn line_breaks_in_names_and_headers_are_rejected() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let email_client = email_client(mock_server.uri());
+        let recipient = || SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        for options in [
+            MessageOptions {
+                sender_name: Some("Zero\r\nBcc: everyone@example.com".into()),
+                ..MessageOptions::default()
+            },
+            MessageOptions {
+                headers: BTreeMap::from([("X-Campaign".into(), "a\nBcc: b".into())]),
+                ..MessageOptions::default()
+            },
+        ] {
+            let outcome = email_client
+                .send_email_with_options(recipient(), "Hi", "<p>Hi</p>", "Hi", &options)
+                .await;
+            assert_err!(outcome);
+        }
+    }
+
+    #[tokio::test]
+    async fn emails_with_oversized_attachments_are_not_sent() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(202))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let attachment = |size| Attachment {
+            filename: "issue.pdf".into(),
+            content_type: "application/pdf".into(),
+            content: vec![0; size],
+            disposition: Disposition::Attachment,
+        };
+        let options = MessageOptions {
+            attachments: vec![
+                attachment(MAX_ATTACHMENTS_SIZE / 2),
+                attachment(MAX_ATTACHMENTS_SIZE / 2 + 1),
+            ],
+            ..MessageOptions::default()
+        };
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let outcome = email_client(mock_server.uri())
+            .send_email_with_options(recipient, "Hi", "<p>Hi</p>", "Hi", &options)
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        let in_a_minute = chrono::Utc::now() + chrono::Duration::seconds(61);
+        let date = in_a_minute.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&date).unwrap());
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(61));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    fn personalization(substitutions: &[(&str, &str)]) -> Personalization {
+        Personalization {
+            to: SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            substitutions: substitutions
+                .iter()
+                .map(|(placeholder, value)| (placeholder.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn placeholders_are_substituted_in_a_single_pass() {
+        let personalization = personalization(&[("{{a}}", "{{b}}"), ("{{b}}", "b")]);
+
+        assert_eq!(personalization.substitute("{{a}} {{b}}"), "{{b}} b");
+    }
+
+    #[test]
+    fn the_longest_overlapping_placeholder_wins() {
+        let personalization =
+            personalization(&[("$name", "Ursula"), ("$name_full", "Ursula Le Guin")]);
+
+        assert_eq!(
+            personalization.substitute("$name_full, $name, $nam"),
+            "Ursula Le Guin, Ursula, $nam"
+        );
+    }
+
+    #[test]
+    fn values_are_html_escaped_in_the_html_part() {
+        let personalization = personalization(&[("{{name}}", "<b>Ursula</b> & co")]);
+
+        assert_eq!(
+            personalization.substitute_html("<p>{{name}}</p>"),
+            "<p>&lt;b&gt;Ursula&lt;/b&gt; &amp; co</p>"
+        );
+        assert_eq!(personalization.substitute("{{name}}"), "<b>Ursula</b> & co");
+    }
+
+    /// Records the subject of every email it sends, rejecting those addressed
+    /// to `rejected`.
+    struct RecordingSender {
+        rejected: &'static str,
+        subjects: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailSender for RecordingSender {
+        async fn send(&self, email: &Email<'_>) -> anyhow::Result<()> {
+            if email.to.as_ref() == self.rejected {
+                anyhow::bail!("Rejected");
+            }
+            self.subjects.lock().unwrap().push(email.subject.into());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_sent_one_by_one_to_providers_without_a_batch_api() {
+        let transport = Arc::new(RecordingSender {
+            rejected: "octavia@example.com",
+            subjects: Mutex::new(vec![]),
+        });
+        let sender = SubscriberEmail::parse("newsletter@example.com".into()).unwrap();
+        let email_client = EmailClient::new(sender, transport.clone());
+        let personalizations: Vec<_> = ["ursula", "octavia", "nk"]
+            .iter()
+            .map(|name| Personalization {
+                to: SubscriberEmail::parse(format!("{}@example.com", name)).unwrap(),
+                substitutions: BTreeMap::from([("{{name}}".into(), name.to_string())]),
+            })
+            .collect();
+
+        let outcomes = email_client
+            .send_batch(
+                &personalizations,
+                "Hi {{name}}",
+                "<p>Hi</p>",
+                "Hi",
+                BTreeMap::new(),
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_ok!(&outcomes[0]);
+        assert_err!(&outcomes[1]);
+        assert_ok!(&outcomes[2]);
+        assert_eq!(
+            *transport.subjects.lock().unwrap(),
+            vec!["Hi ursula".to_string(), "Hi nk".to_string()]
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, Response};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::SubscriberEmail;
+use crate::email_client::{send_http_request, Attachment, Email, EmailBatch, EmailSender};
+
+/// How many messages the `/email/batch` endpoint accepts in a single request.
+const MAX_BATCH_SIZE: usize = 500;
+
+/// Sends emails through a Postmark-style `/email` API, authenticated with a
+/// server token.
+pub struct PostmarkSender {
+    http_client: Client,
+    base_url: String,
+    server_token: Secret<String>,
+}
+
+impl PostmarkSender {
+    pub fn new(base_url: String, server_token: Secret<String>, timeout: Duration) -> Result<Self> {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build the HTTP client")?;
+        Ok(Self {
+            http_client,
+            base_url,
+            server_token,
+        })
+    }
+
+    async fn post<T: serde::Serialize + ?Sized>(&self, path: &str, body: &T) -> Result<Response> {
+        let request = self
+            .http_client
+            .post(format!("{}{}", self.base_url, path))
+            .header("Accept", "application/json")
+            .header("X-Postmark-Server-Token", self.server_token.expose_secret())
+            .json(body);
+        send_http_request(request).await
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for PostmarkSender {
+    async fn send(&self, email: &Email<'_>) -> Result<()> {
+        self.post("/email", &SendEmailRequest::from(email)).await?;
+        Ok(())
+    }
+
+    fn max_batch_size(&self) -> usize {
+        MAX_BATCH_SIZE
+    }
+
+    /// Every recipient gets their own, fully substituted message; the
+    /// response reports on each of them.
+    async fn send_batch(&self, batch: &EmailBatch<'_>) -> Result<Vec<Result<()>>> {
+        let personalized: Vec<_> = batch
+            .personalizations
+            .iter()
+            .map(|personalization| batch.personalize(personalization))
+            .collect();
+        let emails: Vec<_> = personalized
+            .iter()
+            .zip(batch.personalizations)
+            .map(|(email, personalization)| email.email(batch.from, &personalization.to))
+            .collect();
+        let request_body: Vec<_> = emails.iter().map(SendEmailRequest::from).collect();
+
+        let results: Vec<SendEmailResult> = self
+            .post("/email/batch", &request_body)
+            .await?
+            .json()
+            .await
+            .context("Invalid batch response")?;
+        if results.len() != emails.len() {
+            return Err(anyhow!(
+                "Expected {} batch results, got {}",
+                emails.len(),
+                results.len()
+            ));
+        }
+        Ok(results
+            .into_iter()
+            .map(|result| match result.error_code {
+                0 => Ok(()),
+                code => Err(anyhow!("Rejected with error {}: {}", code, result.message)),
+            })
+            .collect())
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
+    to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bcc: Option<String>,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<Header<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<AttachmentField<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct AttachmentField<'a> {
+    name: &'a str,
+    content: String,
+    content_type: &'a str,
+    /// Marks the attachment as inline, referenced as `cid:<id>`.
+    #[serde(rename = "ContentID", skip_serializing_if = "Option::is_none")]
+    content_id: Option<String>,
+}
+
+impl<'a> From<&'a Attachment> for AttachmentField<'a> {
+    fn from(attachment: &'a Attachment) -> Self {
+        Self {
+            name: &attachment.filename,
+            content: attachment.base64_content(),
+            content_type: &attachment.content_type,
+            content_id: attachment
+                .content_id()
+                .map(|content_id| format!("cid:{}", content_id)),
+        }
+    }
+}
+
+impl<'a> From<&'a Email<'a>> for SendEmailRequest<'a> {
+    fn from(email: &'a Email<'a>) -> Self {
+        Self {
+            from: email.from.mailbox(),
+            reply_to: email.from.reply_to.as_ref().map(AsRef::as_ref),
+            to: email.to.as_ref(),
+            cc: address_list(email.cc),
+            bcc: address_list(email.bcc),
+            subject: email.subject,
+            html_body: email.html_content,
+            text_body: email.text_content,
+            headers: email
+                .headers
+                .iter()
+                .map(|(name, value)| Header { name, value })
+                .collect(),
+            attachments: email
+                .attachments
+                .iter()
+                .map(AttachmentField::from)
+                .collect(),
+        }
+    }
+}
+
+/// Joins `emails` into a comma separated list, `None` if there are none.
+fn address_list(emails: &[SubscriberEmail]) -> Option<String> {
+    (!emails.is_empty()).then(|| {
+        emails
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Header<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailResult {
+    error_code: u32,
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use std::collections::BTreeMap;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        Attachment, Disposition, EmailClient, MessageOptions, Personalization, PostmarkSender,
+    };
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn email_client(base_url: String) -> EmailClient {
+        let sender = SubscriberEmail::parse("newsletter@example.com".into()).unwrap();
+        let transport = PostmarkSender::new(
+            base_url,
+            Secret::new("server-token".into()),
+            Duration::from_millis(200),
+        )
+        .unwrap();
+        EmailClient::new(sender, Arc::new(transport))
+    }
+
+    fn recipient() -> SubscriberEmail {
+        SubscriberEmail::parse("ursula@example.com".into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_posts_the_message_with_the_server_token() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/email"))
+            .and(header("X-Postmark-Server-Token", "server-token"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client(mock_server.uri())
+            .send_newsletter_email(
+                recipient(),
+                "Issue #1",
+                "<p>Hello</p>",
+                "Hello",
+                "https://example.com/unsubscribe?token=abc",
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["From"], "newsletter@example.com");
+        assert_eq!(body["To"], "ursula@example.com");
+        assert_eq!(body["Subject"], "Issue #1");
+        assert_eq!(body["HtmlBody"], "<p>Hello</p>");
+        assert_eq!(body["TextBody"], "Hello");
+        assert_eq!(body["Headers"][0]["Name"], "List-Unsubscribe");
+        assert_eq!(
+            body["Headers"][0]["Value"],
+            "<https://example.com/unsubscribe?token=abc>"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_includes_the_display_name_reply_to_and_copies() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let email_client = email_client(mock_server.uri())
+            .with_sender_name("Zero \"Weekly\"".into())
+            .with_reply_to(SubscriberEmail::parse("editor@example.com".into()).unwrap());
+        let options = MessageOptions {
+            cc: ["octavia", "nk"]
+                .iter()
+                .map(|name| SubscriberEmail::parse(format!("{}@example.com", name)).unwrap())
+                .collect(),
+            bcc: vec![SubscriberEmail::parse("archive@example.com".into()).unwrap()],
+            ..MessageOptions::default()
+        };
+
+        email_client
+            .send_email_with_options(recipient(), "Hi", "<p>Hi</p>", "Hi", &options)
+            .await
+            .unwrap();
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["From"],
+            r#""Zero \"Weekly\"" <newsletter@example.com>"#
+        );
+        assert_eq!(body["ReplyTo"], "editor@example.com");
+        assert_eq!(body["Cc"], "octavia@example.com, nk@example.com");
+        assert_eq!(body["Bcc"], "archive@example.com");
+    }
+
+    #[tokio::test]
+    async fn inline_images_are_sent_with_their_content_id() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let options = MessageOptions {
+            attachments: vec![Attachment {
+                filename: "logo.png".into(),
+                content_type: "image/png".into(),
+                content: b"PNG".to_vec(),
+                disposition: Disposition::Inline {
+                    content_id: "logo".into(),
+                },
+            }],
+            ..MessageOptions::default()
+        };
+
+        email_client(mock_server.uri())
+            .send_email_with_options(recipient(), "Hi", r#"<img src="cid:logo">"#, "Hi", &options)
+            .await
+            .unwrap();
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["Attachments"][0]["Name"], "logo.png");
+        assert_eq!(body["Attachments"][0]["Content"], "UE5H");
+        assert_eq!(body["Attachments"][0]["ContentType"], "image/png");
+        assert_eq!(body["Attachments"][0]["ContentID"], "cid:logo");
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_rejects_the_message() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client(mock_server.uri())
+            .send_email(recipient(), "Welcome!", "<p>Hi</p>", "Hi")
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_the_outcome_for_every_recipient() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"ErrorCode": 0, "Message": "OK", "To": "ursula@example.com"},
+                {"ErrorCode": 406, "Message": "Inactive recipient", "To": "octavia@example.com"},
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let personalizations: Vec<_> = ["ursula", "octavia"]
+            .iter()
+            .map(|name| Personalization {
+                to: SubscriberEmail::parse(format!("{}@example.com", name)).unwrap(),
+                substitutions: BTreeMap::from([("{{name}}".into(), name.to_string())]),
+            })
+            .collect();
+
+        let outcomes = email_client(mock_server.uri())
+            .send_batch(
+                &personalizations,
+                "Hi {{name}}",
+                "<p>Hello {{name}}</p>",
+                "Hello {{name}}",
+                BTreeMap::new(),
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_ok!(&outcomes[0]);
+        assert_err!(&outcomes[1]);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body[1]["To"], "octavia@example.com");
+        assert_eq!(body[1]["Subject"], "Hi octavia");
+        assert_eq!(body[1]["TextBody"], "Hello octavia");
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often, and how long apart, sends failing with a
+/// [`ProviderError::Transient`](crate::email_client::ProviderError) are retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    /// Caps the exponential backoff. Providers asking to wait longer than
+    /// this through `Retry-After` are not retried at all.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// How long to wait before retry number `retry` (starting at 0), or `None`
+    /// to give up.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_delay => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(
+                self.base_delay
+                    .saturating_mul(2u32.saturating_pow(retry))
+                    .min(self.max_delay),
+            ),
+        }
+    }
+}
+
+/// Stops sending for `cooldown` once the provider failed `failure_threshold`
+/// sends in a row, then lets a single trial send through to find out whether
+/// it has recovered.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// A `failure_threshold` of 0 disables the breaker.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    /// Whether a send may go ahead. Once the cooldown has passed, only the
+    /// first caller is let through until its outcome is recorded.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if Instant::now() < open_until => false,
+            Some(_) => {
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::email_client::{CircuitBreaker, RetryPolicy};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+        }
+    }
+
+    #[test]
+    fn delays_grow_exponentially_up_to_the_maximum() {
+        let policy = policy();
+        assert_eq!(policy.delay(0, None), Some(Duration::from_millis(100)));
+        assert_eq!(policy.delay(1, None), Some(Duration::from_millis(200)));
+        assert_eq!(policy.delay(2, None), Some(Duration::from_millis(250)));
+        assert_eq!(policy.delay(3, None), None);
+    }
+
+    #[test]
+    fn retry_after_is_honored_unless_it_exceeds_the_maximum() {
+        let policy = policy();
+        let short = Duration::from_millis(50);
+        assert_eq!(policy.delay(0, Some(short)), Some(short));
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(60))), None);
+    }
+
+    #[test]
+    fn the_breaker_opens_after_repeated_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        assert!(breaker.allow());
+        breaker.record_failure();
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn a_single_trial_is_let_through_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(10));
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        breaker.record_success();
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn a_disabled_breaker_never_opens() {
+        let breaker = CircuitBreaker::disabled();
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.allow());
+    }
+}
@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::SubscriberEmail;
+use crate::email_client::{Attachment, Disposition, Email, EmailSender, ProviderError};
+
+/// Sends emails to an SMTP relay.
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpSender {
+    /// `url` follows lettre's connection URL format: `smtp://host:port` talks
+    /// plain text (add `?tls=required` for STARTTLS), `smtps://host:port`
+    /// uses implicit TLS.
+    pub fn new(
+        url: &str,
+        credentials: Option<(String, Secret<String>)>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+            .context("Invalid SMTP connection URL")?
+            .timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder =
+                builder.credentials(Credentials::new(username, password.expose_secret().clone()));
+        }
+        Ok(Self {
+            transport: builder.build(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SmtpSender {
+    async fn send(&self, email: &Email<'_>) -> Result<()> {
+        match self.transport.send(mime_message(email)?).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.is_transient() => Err(ProviderError::Transient {
+                retry_after: None,
+                reason: format!("The SMTP relay deferred the email: {}", e),
+            }
+            .into()),
+            Err(e) if e.is_permanent() || e.is_client() => {
+                Err(anyhow::Error::from(e).context("The SMTP relay rejected the email"))
+            }
+            Err(e) => Err(ProviderError::Unreachable(e.into()).into()),
+        }
+    }
+}
+
+/// Renders `email` as a `multipart/alternative` RFC 5322 message, wrapped in
+/// `multipart/related` for inline images and `multipart/mixed` for
+/// attachments.
+pub(super) fn mime_message(email: &Email<'_>) -> Result<Message> {
+    let mut builder = Message::builder()
+        .from(Mailbox::new(
+            email.from.name.clone(),
+            address(&email.from.email)?,
+        ))
+        .to(Mailbox::new(None, address(email.to)?))
+        .subject(email.subject);
+    if let Some(reply_to) = &email.from.reply_to {
+        builder = builder.reply_to(Mailbox::new(None, address(reply_to)?));
+    }
+    for cc in email.cc {
+        builder = builder.cc(Mailbox::new(None, address(cc)?));
+    }
+    // Only added to the envelope, the header is left out of the message
+    for bcc in email.bcc {
+        builder = builder.bcc(Mailbox::new(None, address(bcc)?));
+    }
+    for (name, value) in &email.headers {
+        let name = HeaderName::new_from_ascii(name.to_string())
+            .with_context(|| format!("{} is not a valid header name", name))?;
+        builder = builder.raw_header(HeaderValue::new(name, value.to_string()));
+    }
+    let mut body = MultiPart::alternative_plain_html(
+        email.text_content.to_string(),
+        email.html_content.to_string(),
+    );
+    let (inline, attached): (Vec<_>, Vec<_>) = email
+        .attachments
+        .iter()
+        .partition(|attachment| attachment.disposition != Disposition::Attachment);
+    if !inline.is_empty() {
+        body = inline.into_iter().try_fold(
+            MultiPart::related().multipart(body),
+            |related, attachment| {
+                Ok::<_, anyhow::Error>(related.singlepart(attachment_part(attachment)?))
+            },
+        )?;
+    }
+    if !attached.is_empty() {
+        body = attached.into_iter().try_fold(
+            MultiPart::mixed().multipart(body),
+            |mixed, attachment| {
+                Ok::<_, anyhow::Error>(mixed.singlepart(attachment_part(attachment)?))
+            },
+        )?;
+    }
+    builder.multipart(body).context("Failed to build the email")
+}
+
+fn attachment_part(attachment: &Attachment) -> Result<SinglePart> {
+    let content_type = ContentType::parse(&attachment.content_type)
+        .with_context(|| format!("{} is not a valid MIME type", attachment.content_type))?;
+    let part = match &attachment.disposition {
+        Disposition::Attachment => lettre::message::Attachment::new(attachment.filename.clone()),
+        Disposition::Inline { content_id } => lettre::message::Attachment::new_inline_with_name(
+            content_id.clone(),
+            attachment.filename.clone(),
+        ),
+    };
+    Ok(part.body(attachment.content.clone(), content_type))
+}
+
+fn address(email: &SubscriberEmail) -> Result<Address> {
+    email
+        .as_ref()
+        .parse()
+        .with_context(|| format!("{} is not a valid address", email.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{Attachment, Disposition, EmailClient, MessageOptions, SmtpSender};
+    use claim::{assert_err, assert_ok};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    /// Accepts a single SMTP session, answering `RCPT TO` with
+    /// `reply_to_rcpt` and every other command with success, and reports the
+    /// message it received.
+    async fn smtp_stand_in(reply_to_rcpt: &'static str) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("smtp://{}", listener.local_addr().unwrap());
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut tx = Some(tx);
+            let mut data: Option<String> = None;
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply = match data.as_mut() {
+                    Some(_) if line == "." => {
+                        if let Some(tx) = tx.take() {
+                            let _ = tx.send(data.take().unwrap());
+                        }
+                        "250 OK"
+                    }
+                    Some(data) => {
+                        data.push_str(&line);
+                        data.push('\n');
+                        continue;
+                    }
+                    None if line.starts_with("EHLO") => "250 localhost",
+                    None if line.starts_with("RCPT TO") => reply_to_rcpt,
+                    None if line == "DATA" => {
+                        data = Some(String::new());
+                        "354 Go ahead"
+                    }
+                    None if line == "QUIT" => "221 Bye",
+                    None => "250 OK",
+                };
+                let _ = writer.write_all(format!("{}\r\n", reply).as_bytes()).await;
+            }
+        });
+        (url, rx)
+    }
+
+    fn email_client(url: &str) -> EmailClient {
+        let sender = SubscriberEmail::parse("newsletter@example.com".into()).unwrap();
+        let transport = SmtpSender::new(url, None, Duration::from_secs(1)).unwrap();
+        EmailClient::new(sender, Arc::new(transport))
+    }
+
+    fn recipient() -> SubscriberEmail {
+        SubscriberEmail::parse("ursula@example.com".into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_delivers_a_multipart_message_to_the_relay() {
+        let (url, message) = smtp_stand_in("250 OK").await;
+
+        let outcome = email_client(&url)
+            .send_newsletter_email(
+                recipient(),
+                "Issue #1",
+                "<p>Hello</p>",
+                "Hello",
+                "https://example.com/unsubscribe?token=abc",
+                &MessageOptions::default(),
+            )
+            .await;
+
+        assert_ok!(outcome);
+        let message = message.await.unwrap();
+        assert!(message.contains("From: newsletter@example.com"));
+        assert!(message.contains("To: ursula@example.com"));
+        assert!(message.contains("Subject: Issue #1"));
+        assert!(message.contains("List-Unsubscribe: <https://example.com/unsubscribe?token=abc>"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("<p>Hello</p>"));
+    }
+
+    #[tokio::test]
+    async fn send_email_sets_the_display_name_reply_to_and_copies() {
+        let (url, message) = smtp_stand_in("250 OK").await;
+        let email_client = email_client(&url)
+            .with_sender_name("Zero Newsletter".into())
+            .with_reply_to(SubscriberEmail::parse("editor@example.com".into()).unwrap());
+        let options = MessageOptions {
+            cc: vec![SubscriberEmail::parse("octavia@example.com".into()).unwrap()],
+            bcc: vec![SubscriberEmail::parse("archive@example.com".into()).unwrap()],
+            ..MessageOptions::default()
+        };
+
+        let outcome = email_client
+            .send_email_with_options(recipient(), "Welcome!", "<p>Hi</p>", "Hi", &options)
+            .await;
+
+        assert_ok!(outcome);
+        let message = message.await.unwrap();
+        assert!(message.contains("From: \"Zero Newsletter\" <newsletter@example.com>"));
+        assert!(message.contains("Reply-To: editor@example.com"));
+        assert!(message.contains("Cc: octavia@example.com"));
+        assert!(!message.contains("archive@example.com"));
+    }
+
+    #[tokio::test]
+    async fn attachments_and_inline_images_get_their_own_parts() {
+        let (url, message) = smtp_stand_in("250 OK").await;
+        let options = MessageOptions {
+            attachments: vec![
+                Attachment {
+                    filename: "issue.pdf".into(),
+                    content_type: "application/pdf".into(),
+                    content: b"%PDF".to_vec(),
+                    disposition: Disposition::Attachment,
+                },
+                Attachment {
+                    filename: "logo.png".into(),
+                    content_type: "image/png".into(),
+                    content: b"PNG".to_vec(),
+                    disposition: Disposition::Inline {
+                        content_id: "logo".into(),
+                    },
+                },
+            ],
+            ..MessageOptions::default()
+        };
+
+        let outcome = email_client(&url)
+            .send_email_with_options(
+                recipient(),
+                "Issue #1",
+                r#"<img src="cid:logo">"#,
+                "Hi",
+                &options,
+            )
+            .await;
+
+        assert_ok!(outcome);
+        let message = message.await.unwrap();
+        assert!(message.contains("multipart/mixed"));
+        assert!(message.contains("multipart/related"));
+        assert!(message.contains("Content-Disposition: attachment; filename=\"issue.pdf\""));
+        assert!(message.contains("Content-ID: <logo>"));
+        assert!(message.contains("%PDF"));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_relay_rejects_the_recipient() {
+        let (url, _) = smtp_stand_in("550 No such user").await;
+
+        let outcome = email_client(&url)
+            .send_email(recipient(), "Welcome!", "<p>Hi</p>", "Hi")
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[test]
+    fn invalid_connection_urls_are_rejected() {
+        assert!(SmtpSender::new("not a url", None, Duration::from_secs(1)).is_err());
+    }
+}
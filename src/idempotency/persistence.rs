@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::{anyhow, Result};
+use mongodb::bson::oid::ObjectId;
+
+use super::IdempotencyKey;
+use crate::storage::{IdempotencyRepository, SavedResponse};
+
+/// How long a duplicate request waits for the first one to finish before
+/// giving up with a `409 Conflict`.
+const WAIT_FOR_RESPONSE: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub enum NextAction {
+    StartProcessing,
+    ReturnSavedResponse(HttpResponse),
+}
+
+/// Claims `idempotency_key` for `user_id`, or returns the response stored by
+/// the request that claimed it first.
+#[tracing::instrument(name = "Try processing idempotent request", skip(repository))]
+pub async fn try_processing(
+    repository: &dyn IdempotencyRepository,
+    idempotency_key: &IdempotencyKey,
+    user_id: ObjectId,
+) -> Result<NextAction> {
+    if repository.try_claim(user_id, idempotency_key).await? {
+        return Ok(NextAction::StartProcessing);
+    }
+    let deadline = tokio::time::Instant::now() + WAIT_FOR_RESPONSE;
+    loop {
+        if let Some(saved) = repository.get_response(user_id, idempotency_key).await? {
+            return Ok(NextAction::ReturnSavedResponse(into_http_response(saved)?));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            let response = HttpResponse::Conflict().body(
+                "A request with the same idempotency key is still being processed. Retry later.",
+            );
+            return Ok(NextAction::ReturnSavedResponse(response));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn into_http_response(saved: SavedResponse) -> Result<HttpResponse> {
+    let mut response = HttpResponse::build(StatusCode::from_u16(saved.status_code)?);
+    for (name, value) in saved.headers {
+        response.append_header((name, value));
+    }
+    Ok(response.body(saved.body))
+}
+
+/// Stores `http_response` against the claimed key and hands it back so that it
+/// can be returned to the caller.
+#[tracing::instrument(name = "Save idempotent response", skip(repository, http_response))]
+pub async fn save_response(
+    repository: &dyn IdempotencyRepository,
+    idempotency_key: &IdempotencyKey,
+    user_id: ObjectId,
+    http_response: HttpResponse,
+) -> Result<HttpResponse> {
+    let (response_head, body) = http_response.into_parts();
+    let body = to_bytes(body).await.map_err(|e| anyhow!("{}", e))?;
+    let saved = SavedResponse {
+        status_code: response_head.status().as_u16(),
+        headers: response_head
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_owned(), value.as_bytes().to_owned()))
+            .collect(),
+        body: body.to_vec(),
+    };
+    repository
+        .save_response(user_id, idempotency_key, &saved)
+        .await?;
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
+
+/// Releases a claimed key whose request failed before producing a response,
+/// so that the client can retry it.
+#[tracing::instrument(name = "Release idempotency key", skip(repository))]
+pub async fn delete_pending(
+    repository: &dyn IdempotencyRepository,
+    idempotency_key: &IdempotencyKey,
+    user_id: ObjectId,
+) -> Result<()> {
+    repository.release(user_id, idempotency_key).await
+}
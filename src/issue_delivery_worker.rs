@@ -0,0 +1,204 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::watch;
+
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailClient, MessageOptions, Personalization};
+use crate::storage::{DeliveryQueue, SubscriberRepository};
+use crate::templates::{EmailTemplate, Templates};
+use crate::tracking::{InstrumentedHtml, TrackingSigner};
+use crate::unsubscribe::UnsubscribeSigner;
+
+/// How long dequeued tasks stay invisible to other workers. A worker that
+/// dies mid-send gives its tasks back once the lease runs out.
+const LEASE: chrono::Duration = chrono::Duration::minutes(5);
+const MAX_RETRIES: u32 = 5;
+const BASE_BACKOFF: chrono::Duration = chrono::Duration::seconds(2);
+/// How long a delivery waits while the rest of its issue is still being
+/// queued.
+const ENQUEUE_WAIT: chrono::Duration = chrono::Duration::seconds(10);
+/// How many deliveries of an issue a worker sends in one go, at most.
+const MAX_BATCH_SIZE: usize = 100;
+/// Stands for each recipient's unsubscribe link in a batch.
+const UNSUBSCRIBE_LINK: &str = "{{unsubscribe_link}}";
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// Sends a batch of due deliveries of one issue, as many as the email
+/// provider takes in a single request.
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id = tracing::field::Empty, recipients = tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    delivery_queue: &dyn DeliveryQueue,
+    subscribers: &dyn SubscriberRepository,
+    email_client: &EmailClient,
+    templates: &Templates,
+    unsubscribe_signer: &UnsubscribeSigner,
+    tracking_signer: &TrackingSigner,
+) -> Result<ExecutionOutcome> {
+    let batch_size = email_client.max_batch_size().min(MAX_BATCH_SIZE);
+    let tasks = delivery_queue.dequeue(LEASE, batch_size).await?;
+    let Some(issue_id) = tasks.first().map(|task| task.newsletter_issue_id) else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    tracing::Span::current()
+        .record("newsletter_issue_id", tracing::field::display(&issue_id))
+        .record("recipients", tasks.len());
+
+    let Some(issue) = delivery_queue.get_issue(issue_id).await? else {
+        // Retrying cannot bring the issue back
+        tracing::error!("Dropping delivery tasks: their newsletter issue does not exist");
+        for task in &tasks {
+            delivery_queue.delete_task(task.id).await?;
+        }
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+    if issue.enqueuing_until.is_some() {
+        // A scheduler that starts over would queue these deliveries again
+        // once they are sent
+        for task in &tasks {
+            delivery_queue
+                .postpone(task.id, chrono::Utc::now() + ENQUEUE_WAIT)
+                .await?;
+        }
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+    let options = MessageOptions {
+        sender_name: issue.sender_name.clone(),
+        reply_to: issue
+            .reply_to
+            .clone()
+            .and_then(|reply_to| SubscriberEmail::parse(reply_to).ok()),
+        ..MessageOptions::default()
+    };
+    // Only the issue's own content is tracked, not the unsubscribe link
+    let html_content = InstrumentedHtml::new(&issue.html_content);
+    let content = templates.render(&EmailTemplate::Newsletter {
+        title: &issue.title,
+        html_content: &html_content.html,
+        text_content: &issue.text_content,
+        unsubscribe_link: UNSUBSCRIBE_LINK,
+    })?;
+
+    let mut deliveries = Vec::with_capacity(tasks.len());
+    let mut personalizations = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        // Local send times queue deliveries up to a day ahead, so recipients
+        // may have unsubscribed or bounced since
+        let receives = subscribers
+            .find_by_id(task.subscriber_id)
+            .await?
+            .is_some_and(|subscriber| subscriber.receives(issue.list.as_deref()));
+        if !receives {
+            tracing::info!(
+                subscriber_email = %task.subscriber_email,
+                "Dropping a delivery: the subscriber no longer receives the issue"
+            );
+            delivery_queue.delete_task(task.id).await?;
+            continue;
+        }
+        let email = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(
+                    subscriber_email = %task.subscriber_email,
+                    "Skipping a confirmed subscriber. Their stored contact details are invalid: {}",
+                    e
+                );
+                delivery_queue.delete_task(task.id).await?;
+                continue;
+            }
+        };
+        let mut substitutions =
+            tracking_signer.substitutions(&html_content, issue.id, task.subscriber_id);
+        substitutions.insert(
+            UNSUBSCRIBE_LINK.into(),
+            unsubscribe_signer.link(task.subscriber_id, issue.list.as_deref()),
+        );
+        personalizations.push(Personalization {
+            to: email,
+            substitutions,
+        });
+        deliveries.push(task);
+    }
+    let outcomes = email_client
+        .send_newsletter_batch(
+            &personalizations,
+            &issue.title,
+            &content.html,
+            &content.text,
+            UNSUBSCRIBE_LINK,
+            &options,
+        )
+        .await;
+
+    for (task, outcome) in deliveries.iter().zip(outcomes) {
+        match outcome {
+            Ok(_) => delivery_queue.delete_task(task.id).await?,
+            Err(e) if task.n_retries < MAX_RETRIES => {
+                tracing::warn!(
+                    subscriber_email = %task.subscriber_email,
+                    "Failed to deliver issue, retrying later: {:?}",
+                    e
+                );
+                let backoff = BASE_BACKOFF * 2i32.pow(task.n_retries);
+                delivery_queue
+                    .reschedule(task.id, chrono::Utc::now() + backoff)
+                    .await?;
+            }
+            Err(e) => {
+                tracing::error!(
+                    subscriber_email = %task.subscriber_email,
+                    "Failed to deliver issue, giving up: {:?}",
+                    e
+                );
+                delivery_queue.delete_task(task.id).await?;
+            }
+        }
+    }
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Delivers queued emails until `shutdown` flips to `true`. A task that is
+/// being sent when the signal arrives is allowed to finish.
+pub async fn run_worker_until_stopped(
+    delivery_queue: Arc<dyn DeliveryQueue>,
+    subscribers: Arc<dyn SubscriberRepository>,
+    email_client: EmailClient,
+    templates: Arc<Templates>,
+    unsubscribe_signer: UnsubscribeSigner,
+    tracking_signer: TrackingSigner,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    while !*shutdown.borrow() {
+        let pause = match try_execute_task(
+            delivery_queue.as_ref(),
+            subscribers.as_ref(),
+            &email_client,
+            &templates,
+            &unsubscribe_signer,
+            &tracking_signer,
+        )
+        .await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => Duration::from_secs(10),
+            Err(_) => Duration::from_secs(1),
+            Ok(ExecutionOutcome::TaskCompleted) => continue,
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(pause) => {}
+            // A dropped sender means nobody can ask us to stop any more
+            changed = shutdown.changed() => if changed.is_err() { break; },
+        }
+    }
+    tracing::info!("Background worker stopped");
+    Ok(())
+}
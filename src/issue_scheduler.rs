@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use tokio::sync::watch;
+
+use crate::domain::{SendTime, SubscriberStatus, SubscriberTimeZone};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::storage::{DeliveryQueue, SubscriberRepository};
+
+/// Queues one delivery of `issue_id` per confirmed member of `list`, or of
+/// the main list without one, due at `send_time` in the subscriber's time
+/// zone or right away without one.
+pub async fn enqueue_delivery(
+    subscribers: &dyn SubscriberRepository,
+    delivery_queue: &dyn DeliveryQueue,
+    issue_id: ObjectId,
+    list: Option<&str>,
+    send_time: Option<SendTime>,
+) -> Result<usize> {
+    let recipients = match list {
+        Some(list) => {
+            subscribers
+                .members(list, SubscriberStatus::Confirmed)
+                .await?
+        }
+        None => subscribers.list(Some(SubscriberStatus::Confirmed)).await?,
+    };
+    let now = Utc::now();
+    let mut batches: BTreeMap<DateTime<Utc>, Vec<(ObjectId, String)>> = BTreeMap::new();
+    for subscriber in recipients {
+        let execute_after = match send_time {
+            Some(send_time) => {
+                let time_zone = subscriber.time_zone.clone().and_then(|time_zone| {
+                    SubscriberTimeZone::parse(time_zone)
+                        .inspect_err(|e| {
+                            tracing::warn!(subscriber_id = %subscriber.id, "Falling back to UTC: {}", e)
+                        })
+                        .ok()
+                });
+                send_time.instant_in(time_zone.as_ref()).max(now)
+            }
+            None => now,
+        };
+        batches
+            .entry(execute_after)
+            .or_default()
+            .push((subscriber.id, subscriber.email));
+    }
+    let mut enqueued = 0;
+    for (execute_after, recipients) in batches {
+        enqueued += delivery_queue
+            .enqueue(issue_id, recipients, execute_after)
+            .await?;
+    }
+    Ok(enqueued)
+}
+
+/// How long queueing the deliveries of an issue may take before a scheduler
+/// takes over.
+pub(crate) const ENQUEUE_LEASE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Publishes the scheduled issue that has been due the longest, if any. If
+/// queueing its deliveries fails, the issue is picked up again once its
+/// claim runs out.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id = tracing::field::Empty), err)]
+pub async fn try_publish_due_issue(
+    subscribers: &dyn SubscriberRepository,
+    delivery_queue: &dyn DeliveryQueue,
+) -> Result<ExecutionOutcome> {
+    let Some(issue) = delivery_queue.claim_due_issue(ENQUEUE_LEASE).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    tracing::Span::current().record("newsletter_issue_id", tracing::field::display(&issue.id));
+    let enqueued = enqueue_delivery(
+        subscribers,
+        delivery_queue,
+        issue.id,
+        issue.list.as_deref(),
+        issue.send_time(),
+    )
+    .await
+    .with_context(|| format!("Failed to enqueue the due issue {}", issue.id))?;
+    delivery_queue.finish_enqueuing(issue.id).await?;
+    tracing::info!(enqueued, "Published a scheduled issue");
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Publishes scheduled issues as they come due until `shutdown` flips to
+/// `true`.
+pub async fn run_scheduler_until_stopped(
+    subscribers: Arc<dyn SubscriberRepository>,
+    delivery_queue: Arc<dyn DeliveryQueue>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    while !*shutdown.borrow() {
+        let pause = match try_publish_due_issue(subscribers.as_ref(), delivery_queue.as_ref()).await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => Duration::from_secs(10),
+            Err(_) => Duration::from_secs(1),
+            Ok(ExecutionOutcome::TaskCompleted) => continue,
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(pause) => {}
+            // A dropped sender means nobody can ask us to stop any more
+            changed = shutdown.changed() => if changed.is_err() { break; },
+        }
+    }
+    tracing::info!("Issue scheduler stopped");
+    Ok(())
+}
@@ -1,6 +1,17 @@
+pub mod authentication;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
 pub mod routes;
+pub mod session_state;
+pub mod session_store;
 pub mod startup;
+pub mod storage;
 pub mod telemetry;
+pub mod templates;
+pub mod tracking;
+pub mod unsubscribe;
+pub mod utils;
@@ -1,14 +1,14 @@
-use std::net::TcpListener;
+use std::io::BufRead;
 
-use actix_web::web;
-use anyhow::Result;
-use mongodb::{bson::doc, options::IndexOptions, IndexModel};
-use secrecy::ExposeSecret;
+use anyhow::{anyhow, bail, Context, Result};
+use secrecy::Secret;
 
 use zero::{
+    authentication::create_user,
     configuration::get_configuration,
-    email_client::EmailClient,
-    startup::run,
+    domain::Password,
+    startup::{get_db_client, Application},
+    storage::{MongoUserRepository, UserRepository},
     telemetry::{get_subscriber, init_subscriber},
 };
 
@@ -19,44 +19,31 @@ async fn main() -> Result<()> {
 
     let configuration = get_configuration().expect("Failed to get configuration");
 
-    let listener = TcpListener::bind(format!(
-        "{}:{}",
-        configuration.application.host, configuration.application.port
-    ))
-    .expect("Failed to bind port");
-
-    let db_client =
-        mongodb::Client::with_uri_str(configuration.database.connection_string().expose_secret())
-            .await
-            .expect("Failed connection to database");
-
-    create_email_index(&db_client).await;
-    let db_client = web::Data::new(db_client);
-
-    let email_sender = configuration
-        .email_client
-        .sender()
-        .expect("Invalid sender email");
-    let email_client = EmailClient::new(
-        configuration.email_client.base_url.clone(),
-        configuration.email_client.client_secret(),
-        email_sender,
-    );
-
-    run(listener, db_client, email_client)?.await?;
-    Ok(())
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        None => {}
+        Some("create-admin") => {
+            let username = args.next().context("Usage: zero create-admin <username>")?;
+            let db_client = get_db_client(&configuration.database).await?;
+            return create_admin(&MongoUserRepository::new(db_client), &username).await;
+        }
+        Some(other) => bail!("Unknown command `{}`. Use `create-admin`.", other),
+    }
+
+    let application = Application::build(configuration).await?;
+    application.run_until_stopped().await
 }
 
-async fn create_email_index(db_client: &mongodb::Client) {
-    let options = IndexOptions::builder().unique(true).build();
-    let model = IndexModel::builder()
-        .keys(doc! { "email": 1 })
-        .options(options)
-        .build();
-    db_client
-        .database("zero")
-        .collection::<zero::routes::FormData>("subscribers")
-        .create_index(model, None)
-        .await
-        .expect("Failed to create index");
+/// Bootstraps an admin account, reading its password from the first line of stdin.
+async fn create_admin(users: &dyn UserRepository, username: &str) -> Result<()> {
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password from stdin")?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    let password = Password::parse(Secret::new(password)).map_err(|e| anyhow!(e))?;
+    let user_id = create_user(users, username, password).await?;
+    tracing::info!("Created admin `{}` with id {}", username, user_id);
+    Ok(())
 }
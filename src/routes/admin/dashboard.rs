@@ -0,0 +1,60 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+
+use crate::authentication::{get_username, UserId};
+use crate::storage::{SubscriberRepository, UserRepository};
+use crate::utils::e500;
+
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "Subscriber counts and admin actions", content_type = "text/html"),
+        (status = 303, description = "Redirects anonymous users to the login form")
+    )
+)]
+pub async fn admin_dashboard(
+    user_id: web::ReqData<UserId>,
+    users: web::Data<dyn UserRepository>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let username = get_username(users.get_ref(), *user_id)
+        .await
+        .map_err(e500)?;
+    let counts = subscribers.count_by_status().await.map_err(e500)?;
+    let rows: String = counts
+        .iter()
+        .map(|(status, count)| format!("<tr><td>{}</td><td>{}</td></tr>", status.as_str(), count))
+        .collect();
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Admin dashboard</title>
+</head>
+<body>
+    <p>Welcome {}!</p>
+    <table>
+        <tr><th>Status</th><th>Subscribers</th></tr>
+        {rows}
+    </table>
+    <p>Available actions:</p>
+    <ol>
+        <li><a href="/admin/password">Change password</a></li>
+        <li>
+            <form name="logoutForm" action="/admin/logout" method="post">
+                <input type="submit" value="Logout">
+            </form>
+        </li>
+    </ol>
+</body>
+</html>"#,
+            htmlescape::encode_minimal(&username)
+        )))
+}
@@ -0,0 +1,19 @@
+use actix_web::HttpResponse;
+
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+#[utoipa::path(
+    post,
+    path = "/admin/logout",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses((status = 303, description = "Ends the session and redirects to the login form"))
+)]
+pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    session.log_out();
+    session
+        .insert_flash("You have successfully logged out.")
+        .map_err(e500)?;
+    Ok(see_other("/login"))
+}
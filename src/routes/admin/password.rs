@@ -0,0 +1,129 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::authentication::{
+    change_password as store_password, get_username, validate_credentials, AuthError, Credentials,
+    UserId,
+};
+use crate::domain::Password;
+use crate::session_state::TypedSession;
+use crate::storage::UserRepository;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct PasswordFormData {
+    #[schema(value_type = String, format = Password)]
+    current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    new_password_check: Secret<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The change password form", content_type = "text/html"),
+        (status = 303, description = "Redirects anonymous users to the login form")
+    )
+)]
+pub async fn change_password_form(session: TypedSession) -> HttpResponse {
+    let message_html = match session.take_flash() {
+        Some(message) => format!("<p><i>{}</i></p>", htmlescape::encode_minimal(&message)),
+        None => String::new(),
+    };
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Change Password</title>
+</head>
+<body>
+    {message_html}
+    <form action="/admin/password" method="post">
+        <label>Current password
+            <input type="password" placeholder="Enter current password" name="current_password">
+        </label>
+        <br>
+        <label>New password
+            <input type="password" placeholder="Enter new password" name="new_password">
+        </label>
+        <br>
+        <label>Confirm new password
+            <input type="password" placeholder="Type the new password again" name="new_password_check">
+        </label>
+        <br>
+        <button type="submit">Change password</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    request_body(content = PasswordFormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirects back to the form with the outcome as a flash message"))
+)]
+#[tracing::instrument(name = "Change password", skip(form, users, session, user_id))]
+pub async fn change_password(
+    form: web::Form<PasswordFormData>,
+    users: web::Data<dyn UserRepository>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form = form.0;
+
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        session
+            .insert_flash("You entered two different new passwords - the field values must match.")
+            .map_err(e500)?;
+        return Ok(see_other("/admin/password"));
+    }
+
+    let username = get_username(users.get_ref(), *user_id)
+        .await
+        .map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form.current_password,
+    };
+    if let Err(e) = validate_credentials(users.get_ref(), credentials).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                session
+                    .insert_flash("The current password is incorrect.")
+                    .map_err(e500)?;
+                Ok(see_other("/admin/password"))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    let new_password = match Password::parse(form.new_password) {
+        Ok(password) => password,
+        Err(e) => {
+            session.insert_flash(&e).map_err(e500)?;
+            return Ok(see_other("/admin/password"));
+        }
+    };
+    store_password(users.get_ref(), *user_id, new_password)
+        .await
+        .map_err(e500)?;
+    session
+        .insert_flash("Your password has been changed.")
+        .map_err(e500)?;
+    Ok(see_other("/admin/password"))
+}
@@ -0,0 +1,238 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+
+use crate::authentication::BasicAuthUser;
+use crate::domain::{IssueStatus, SendTime};
+use crate::routes::ApiError;
+use crate::storage::{DeliveryQueue, NewsletterIssue, TrackingRepository};
+use crate::utils::ProblemDetails;
+
+/// The JSON representation of a newsletter issue, without its content.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct IssueResource {
+    pub id: String,
+    pub title: String,
+    pub status: IssueStatus,
+    /// When a scheduled issue goes out; for a local send time, when it goes
+    /// out in the earliest time zone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<DateTime<Utc>>,
+    /// The wall-clock time a scheduled issue goes out in each subscriber's
+    /// time zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub send_at_local: Option<NaiveDateTime>,
+}
+
+impl From<&NewsletterIssue> for IssueResource {
+    fn from(issue: &NewsletterIssue) -> Self {
+        Self {
+            id: issue.id.to_hex(),
+            title: issue.title.clone(),
+            status: issue.status,
+            send_at: issue.send_at,
+            send_at_local: issue.send_at_local,
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueList {
+    pub data: Vec<IssueResource>,
+}
+
+/// Exactly one of the two fields must be set.
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct IssueSchedule {
+    /// Must lie in the future.
+    #[serde(default)]
+    send_at: Option<DateTime<Utc>>,
+    /// A wall-clock time in each subscriber's time zone. Must not have
+    /// passed in any time zone yet.
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "2026-10-19T09:00:00")]
+    send_at_local: Option<NaiveDateTime>,
+}
+
+/// How recipients engaged with a newsletter issue.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct IssueStats {
+    pub issue_id: String,
+    pub title: String,
+    /// How many subscribers the issue was sent to.
+    pub recipients: u64,
+    /// Subscribers who loaded the tracking pixel at least once.
+    pub unique_opens: u64,
+    pub total_opens: u64,
+    /// Subscribers who followed at least one tracked link.
+    pub unique_clicks: u64,
+    pub total_clicks: u64,
+    /// `unique_opens / recipients`, 0 when there were no recipients.
+    pub open_rate: f64,
+    /// `unique_clicks / recipients`, 0 when there were no recipients.
+    pub click_rate: f64,
+}
+
+fn rate(count: u64, recipients: u64) -> f64 {
+    if recipients == 0 {
+        0.0
+    } else {
+        count as f64 / recipients as f64
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}/stats",
+    tag = "issues",
+    security(("basic_auth" = [])),
+    params(("issue_id" = String, Path, description = "The newsletter issue's id")),
+    responses(
+        (status = 200, description = "Open and click counts and rates", body = IssueStats),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such issue", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Get newsletter issue stats",
+    skip(delivery_queue, tracking, user),
+    fields(username = %user.username)
+)]
+pub async fn get_issue_stats(
+    user: BasicAuthUser,
+    issue_id: web::Path<String>,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+    tracking: web::Data<dyn TrackingRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = find_issue(delivery_queue.get_ref(), &issue_id).await?;
+    let id = issue.id;
+    let engagement = tracking.engagement(id).await?;
+    Ok(HttpResponse::Ok().json(IssueStats {
+        issue_id: issue.id.to_hex(),
+        title: issue.title,
+        recipients: issue.recipients,
+        unique_opens: engagement.opens.unique,
+        total_opens: engagement.opens.total,
+        unique_clicks: engagement.clicks.unique,
+        total_clicks: engagement.clicks.total,
+        open_rate: rate(engagement.opens.unique, issue.recipients),
+        click_rate: rate(engagement.clicks.unique, issue.recipients),
+    }))
+}
+
+async fn find_issue(
+    delivery_queue: &dyn DeliveryQueue,
+    issue_id: &str,
+) -> Result<NewsletterIssue, ApiError> {
+    let not_found =
+        || ApiError::IssueNotFound(format!("No newsletter issue with id {}.", issue_id));
+    let id = ObjectId::parse_str(issue_id).map_err(|_| not_found())?;
+    delivery_queue.get_issue(id).await?.ok_or_else(not_found)
+}
+
+fn not_scheduled(issue: &NewsletterIssue) -> ApiError {
+    ApiError::IssueNotScheduled(format!(
+        "Issue {} is {}, not scheduled.",
+        issue.id,
+        issue.status.as_str()
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/scheduled",
+    tag = "issues",
+    security(("basic_auth" = [])),
+    responses(
+        (status = 200, description = "Issues waiting to be sent, soonest first", body = IssueList),
+        (status = 401, description = "Missing or invalid credentials")
+    )
+)]
+#[tracing::instrument(
+    name = "List scheduled newsletter issues",
+    skip(delivery_queue, user),
+    fields(username = %user.username)
+)]
+pub async fn list_scheduled_issues(
+    user: BasicAuthUser,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let issues = delivery_queue.scheduled_issues().await?;
+    Ok(HttpResponse::Ok().json(IssueList {
+        data: issues.iter().map(IssueResource::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/issues/{issue_id}",
+    tag = "issues",
+    security(("basic_auth" = [])),
+    params(("issue_id" = String, Path, description = "The newsletter issue's id")),
+    request_body = IssueSchedule,
+    responses(
+        (status = 200, description = "The rescheduled issue", body = IssueResource),
+        (status = 400, description = "The new time is missing or not in the future", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such issue", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The issue was already published or cancelled", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Reschedule a newsletter issue",
+    skip(body, delivery_queue, user),
+    fields(username = %user.username)
+)]
+pub async fn reschedule_issue(
+    user: BasicAuthUser,
+    issue_id: web::Path<String>,
+    body: web::Json<IssueSchedule>,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = find_issue(delivery_queue.get_ref(), &issue_id).await?;
+    let send_time = SendTime::parse(body.send_at, body.send_at_local)
+        .map_err(ApiError::InvalidIssue)?
+        .ok_or_else(|| ApiError::InvalidIssue("Set send_at or send_at_local.".into()))?;
+    if send_time.earliest() <= Utc::now() {
+        return Err(ApiError::InvalidIssue(
+            "An issue can only be rescheduled to a time in the future in every time zone.".into(),
+        ));
+    }
+    let rescheduled = delivery_queue
+        .reschedule_issue(issue.id, send_time)
+        .await?
+        .ok_or_else(|| not_scheduled(&issue))?;
+    Ok(HttpResponse::Ok().json(IssueResource::from(&rescheduled)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues/{issue_id}/cancel",
+    tag = "issues",
+    security(("basic_auth" = [])),
+    params(("issue_id" = String, Path, description = "The newsletter issue's id")),
+    responses(
+        (status = 200, description = "The cancelled issue", body = IssueResource),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such issue", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The issue was already published or cancelled", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Cancel a newsletter issue",
+    skip(delivery_queue, user),
+    fields(username = %user.username)
+)]
+pub async fn cancel_issue(
+    user: BasicAuthUser,
+    issue_id: web::Path<String>,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = find_issue(delivery_queue.get_ref(), &issue_id).await?;
+    let cancelled = delivery_queue
+        .cancel_issue(issue.id)
+        .await?
+        .ok_or_else(|| not_scheduled(&issue))?;
+    Ok(HttpResponse::Ok().json(IssueResource::from(&cancelled)))
+}
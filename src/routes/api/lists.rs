@@ -0,0 +1,138 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+
+use crate::authentication::BasicAuthUser;
+use crate::domain::{ListSlug, SubscriberEmail};
+use crate::routes::ApiError;
+use crate::storage::{ListRepository, MailingList, NewMailingList};
+use crate::utils::ProblemDetails;
+
+/// The JSON representation of a mailing list.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct MailingListResource {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+impl From<&MailingList> for MailingListResource {
+    fn from(list: &MailingList) -> Self {
+        Self {
+            slug: list.slug.clone(),
+            name: list.name.clone(),
+            description: list.description.clone(),
+            sender_name: list.sender_name.clone(),
+            reply_to: list.reply_to.clone(),
+            created: list.created,
+        }
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct MailingLists {
+    pub data: Vec<MailingListResource>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ListData {
+    /// Identifies the list in subscriptions and issues: lowercase letters,
+    /// digits and dashes.
+    slug: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    /// Replaces the configured sender name on the list's emails.
+    #[serde(default)]
+    sender_name: Option<String>,
+    /// Replaces the configured reply-to address on the list's emails.
+    #[serde(default)]
+    reply_to: Option<String>,
+}
+
+impl TryFrom<ListData> for NewMailingList {
+    type Error = String;
+    fn try_from(data: ListData) -> Result<Self, Self::Error> {
+        let slug = ListSlug::parse(data.slug)?;
+        if data.name.trim().is_empty() {
+            return Err("A list needs a name.".into());
+        }
+        // The sender name ends up in the From header
+        if data
+            .sender_name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty() || name.contains(['\r', '\n']))
+        {
+            return Err("The sender name must be a single line of text.".into());
+        }
+        let reply_to = data.reply_to.map(SubscriberEmail::parse).transpose()?;
+        Ok(NewMailingList {
+            slug,
+            name: data.name,
+            description: data.description,
+            sender_name: data.sender_name,
+            reply_to,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists",
+    tag = "lists",
+    security(("basic_auth" = [])),
+    responses(
+        (status = 200, description = "Every mailing list, ordered by slug", body = MailingLists),
+        (status = 401, description = "Missing or invalid credentials")
+    )
+)]
+#[tracing::instrument(name = "List mailing lists", skip(lists, user), fields(username = %user.username))]
+pub async fn list_mailing_lists(
+    user: BasicAuthUser,
+    lists: web::Data<dyn ListRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let lists = lists.all().await?;
+    Ok(HttpResponse::Ok().json(MailingLists {
+        data: lists.iter().map(MailingListResource::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists",
+    tag = "lists",
+    security(("basic_auth" = [])),
+    request_body = ListData,
+    responses(
+        (status = 201, description = "The new list", body = MailingListResource),
+        (status = 400, description = "Invalid list data", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 409, description = "A list with this slug already exists", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Create a mailing list",
+    skip(body, lists, user),
+    fields(username = %user.username, slug = %body.slug)
+)]
+pub async fn create_mailing_list(
+    user: BasicAuthUser,
+    body: web::Json<ListData>,
+    lists: web::Data<dyn ListRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let new_list: NewMailingList = body
+        .into_inner()
+        .try_into()
+        .map_err(ApiError::InvalidList)?;
+    let list = lists.insert(&new_list).await?.ok_or_else(|| {
+        ApiError::ListAlreadyExists(format!(
+            "A list called {} already exists.",
+            new_list.slug.as_ref()
+        ))
+    })?;
+    Ok(HttpResponse::Created().json(MailingListResource::from(&list)))
+}
@@ -0,0 +1,98 @@
+mod issues;
+mod lists;
+mod subscribers;
+
+pub use issues::*;
+pub use lists::*;
+pub use subscribers::*;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+
+use crate::utils::{error_chain_fmt, ProblemDetails};
+
+pub enum ApiError {
+    SubscriberNotFound(String),
+    IssueNotFound(String),
+    /// The issue has already been published or cancelled.
+    IssueNotScheduled(String),
+    InvalidIssue(String),
+    /// Another list already has the slug.
+    ListAlreadyExists(String),
+    InvalidList(String),
+    InvalidSubscriber(String),
+    /// A query parameter, such as a page cursor, that no resource owns.
+    InvalidRequest(String),
+    UnexpectedError(anyhow::Error),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::SubscriberNotFound(_) => "subscriber.not_found",
+            ApiError::IssueNotFound(_) => "issue.not_found",
+            ApiError::IssueNotScheduled(_) => "issue.not_scheduled",
+            ApiError::InvalidIssue(_) => "issue.invalid_data",
+            ApiError::ListAlreadyExists(_) => "list.already_exists",
+            ApiError::InvalidList(_) => "list.invalid_data",
+            ApiError::InvalidSubscriber(_) => "subscriber.invalid_data",
+            ApiError::InvalidRequest(_) => "request.invalid_data",
+            ApiError::UnexpectedError(_) => "internal_error",
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::SubscriberNotFound(e)
+            | ApiError::IssueNotFound(e)
+            | ApiError::IssueNotScheduled(e)
+            | ApiError::InvalidIssue(e)
+            | ApiError::ListAlreadyExists(e)
+            | ApiError::InvalidList(e)
+            | ApiError::InvalidSubscriber(e)
+            | ApiError::InvalidRequest(e) => write!(f, "{}", e),
+            ApiError::UnexpectedError(_) => write!(f, "Something went wrong. Please retry later."),
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::UnexpectedError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::UnexpectedError(e)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::SubscriberNotFound(_) | ApiError::IssueNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::IssueNotScheduled(_) | ApiError::ListAlreadyExists(_) => StatusCode::CONFLICT,
+            ApiError::InvalidIssue(_)
+            | ApiError::InvalidList(_)
+            | ApiError::InvalidSubscriber(_)
+            | ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ProblemDetails::new(self.status_code(), self.code(), self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,252 @@
+use actix_web::{web, HttpResponse};
+use mongodb::bson::oid::ObjectId;
+
+use crate::authentication::BasicAuthUser;
+use crate::domain::{SubscriberEmail, SubscriberName, SubscriberStatus, SubscriberTimeZone};
+use crate::routes::{ApiError, SubscriberResource};
+use crate::storage::{DeliveryQueue, SubscriberFilter, SubscriberRepository};
+use crate::utils::ProblemDetails;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParameters {
+    /// Only list subscribers with this status.
+    #[param(value_type = Option<SubscriberStatus>)]
+    status: Option<String>,
+    /// Only list the subscriber with this email address.
+    email: Option<String>,
+    /// The `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// Between 1 and 100, defaults to 50.
+    limit: Option<usize>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberPage {
+    pub data: Vec<SubscriberResource>,
+    /// Pass as `cursor` to fetch the next page; absent on the last one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// The fields to change; absent fields are left alone.
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SubscriberPatch {
+    #[serde(default)]
+    name: Option<String>,
+    /// An IANA time zone name; `null` clears it.
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    time_zone: Option<Option<String>>,
+}
+
+/// Tells a field set to `null` apart from an absent one.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+fn parse_subscriber_id(id: &str) -> Result<ObjectId, ApiError> {
+    ObjectId::parse_str(id)
+        .map_err(|_| ApiError::SubscriberNotFound(format!("No subscriber with id {}.", id)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    security(("basic_auth" = [])),
+    params(ListParameters),
+    responses(
+        (status = 200, description = "A page of subscribers, ordered by id", body = SubscriberPage),
+        (status = 400, description = "Invalid filter, cursor or limit", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials")
+    )
+)]
+#[tracing::instrument(
+    name = "List subscribers",
+    skip(parameters, subscribers, user),
+    fields(username = %user.username)
+)]
+pub async fn list_subscribers(
+    user: BasicAuthUser,
+    parameters: web::Query<ListParameters>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let parameters = parameters.into_inner();
+    let filter = SubscriberFilter {
+        status: parameters
+            .status
+            .map(SubscriberStatus::try_from)
+            .transpose()
+            .map_err(ApiError::InvalidSubscriber)?,
+        email: parameters
+            .email
+            .map(|email| SubscriberEmail::parse(email).map(|e| e.as_ref().to_owned()))
+            .transpose()
+            .map_err(ApiError::InvalidSubscriber)?,
+    };
+    let after = parameters
+        .cursor
+        .map(|cursor| ObjectId::parse_str(&cursor))
+        .transpose()
+        .map_err(|_| ApiError::InvalidRequest("The cursor is not valid.".into()))?;
+    let limit = match parameters.limit.unwrap_or(DEFAULT_PAGE_SIZE) {
+        limit @ 1..=MAX_PAGE_SIZE => limit,
+        _ => {
+            return Err(ApiError::InvalidRequest(format!(
+                "The limit must be between 1 and {}.",
+                MAX_PAGE_SIZE
+            )))
+        }
+    };
+
+    // Fetch one extra subscriber to learn whether there is a next page
+    let mut page = subscribers.page(&filter, after, limit + 1).await?;
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|s| s.id.to_hex())
+    } else {
+        None
+    };
+    Ok(HttpResponse::Ok().json(SubscriberPage {
+        data: page.iter().map(SubscriberResource::from).collect(),
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("basic_auth" = [])),
+    params(("subscriber_id" = String, Path, description = "The subscriber's id")),
+    responses(
+        (status = 200, description = "The subscriber", body = SubscriberResource),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such subscriber", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Get a subscriber",
+    skip(subscribers, user),
+    fields(username = %user.username)
+)]
+pub async fn get_subscriber(
+    user: BasicAuthUser,
+    subscriber_id: web::Path<String>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_subscriber_id(&subscriber_id)?;
+    let subscriber = subscribers
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| ApiError::SubscriberNotFound(format!("No subscriber with id {}.", id)))?;
+    Ok(HttpResponse::Ok().json(SubscriberResource::from(&subscriber)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("basic_auth" = [])),
+    params(("subscriber_id" = String, Path, description = "The subscriber's id")),
+    request_body = SubscriberPatch,
+    responses(
+        (status = 200, description = "The updated subscriber", body = SubscriberResource),
+        (status = 400, description = "Invalid name or time zone, or nothing to update", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such subscriber", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Update a subscriber",
+    skip(body, subscribers, user),
+    fields(username = %user.username)
+)]
+pub async fn update_subscriber(
+    user: BasicAuthUser,
+    subscriber_id: web::Path<String>,
+    body: web::Json<SubscriberPatch>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_subscriber_id(&subscriber_id)?;
+    let patch = body.into_inner();
+    if patch.name.is_none() && patch.time_zone.is_none() {
+        return Err(ApiError::InvalidSubscriber(
+            "There is nothing to update.".into(),
+        ));
+    }
+    // Validate everything before changing anything
+    let name = patch
+        .name
+        .map(SubscriberName::parse)
+        .transpose()
+        .map_err(ApiError::InvalidSubscriber)?;
+    let time_zone = patch
+        .time_zone
+        .map(|time_zone| time_zone.map(SubscriberTimeZone::parse).transpose())
+        .transpose()
+        .map_err(ApiError::InvalidSubscriber)?;
+    let not_found = || ApiError::SubscriberNotFound(format!("No subscriber with id {}.", id));
+    let mut subscriber = None;
+    if let Some(name) = name {
+        subscriber = Some(
+            subscribers
+                .update_name(id, &name)
+                .await?
+                .ok_or_else(not_found)?,
+        );
+    }
+    if let Some(time_zone) = time_zone {
+        subscriber = Some(
+            subscribers
+                .update_time_zone(id, time_zone.as_ref())
+                .await?
+                .ok_or_else(not_found)?,
+        );
+    }
+    let subscriber = subscriber.ok_or_else(not_found)?;
+    Ok(HttpResponse::Ok().json(SubscriberResource::from(&subscriber)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("basic_auth" = [])),
+    params(("subscriber_id" = String, Path, description = "The subscriber's id")),
+    responses(
+        (status = 204, description = "The subscriber and their pending deliveries were deleted"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 404, description = "No such subscriber", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(
+    name = "Delete a subscriber",
+    skip(subscribers, delivery_queue, user),
+    fields(username = %user.username)
+)]
+pub async fn delete_subscriber(
+    user: BasicAuthUser,
+    subscriber_id: web::Path<String>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let id = parse_subscriber_id(&subscriber_id)?;
+    // Queued issues hold the address and must not go out after it is deleted
+    delivery_queue.delete_subscriber_tasks(id).await?;
+    if !subscribers.delete(id).await? {
+        return Err(ApiError::SubscriberNotFound(format!(
+            "No subscriber with id {}.",
+            id
+        )));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
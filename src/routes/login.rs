@@ -0,0 +1,93 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use secrecy::Secret;
+
+use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::session_state::TypedSession;
+use crate::storage::UserRepository;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct LoginFormData {
+    username: String,
+    #[schema(value_type = String, format = Password)]
+    password: Secret<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/login",
+    tag = "admin",
+    responses((status = 200, description = "The login form", content_type = "text/html"))
+)]
+pub async fn login_form(session: TypedSession) -> HttpResponse {
+    let error_html = match session.take_flash() {
+        Some(message) => format!("<p><i>{}</i></p>", htmlescape::encode_minimal(&message)),
+        None => String::new(),
+    };
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Login</title>
+</head>
+<body>
+    {error_html}
+    <form action="/login" method="post">
+        <label>Username
+            <input type="text" placeholder="Enter Username" name="username">
+        </label>
+        <label>Password
+            <input type="password" placeholder="Enter Password" name="password">
+        </label>
+        <button type="submit">Login</button>
+    </form>
+</body>
+</html>"#,
+        ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "admin",
+    request_body(content = LoginFormData, content_type = "application/x-www-form-urlencoded"),
+    responses((
+        status = 303,
+        description = "Redirects to the dashboard, or back to the login form if the credentials are wrong"
+    ))
+)]
+#[tracing::instrument(
+    name = "Log in",
+    skip(form, users, session),
+    fields(username = %form.username, user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    form: web::Form<LoginFormData>,
+    users: web::Data<dyn UserRepository>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let credentials = Credentials {
+        username: form.0.username,
+        password: form.0.password,
+    };
+    match validate_credentials(users.get_ref(), credentials).await {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            session.renew();
+            session.insert_user_id(user_id).map_err(e500)?;
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(AuthError::InvalidCredentials(e)) => {
+            tracing::warn!("Failed login attempt: {:?}", e);
+            session
+                .insert_flash("Authentication failed")
+                .map_err(e500)?;
+            Ok(see_other("/login"))
+        }
+        Err(e @ AuthError::UnexpectedError(_)) => Err(e500(e)),
+    }
+}
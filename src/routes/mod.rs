@@ -1,5 +1,23 @@
+mod admin;
+mod api;
 mod health_check;
+mod login;
+mod newsletters;
+mod openapi;
 mod subscriptions;
+mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod tracking;
+mod webhooks;
 
+pub use admin::*;
+pub use api::*;
 pub use health_check::*;
+pub use login::*;
+pub use newsletters::*;
+pub use openapi::*;
 pub use subscriptions::*;
+pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
+pub use tracking::*;
+pub use webhooks::*;
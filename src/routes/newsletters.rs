@@ -0,0 +1,219 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+
+use crate::authentication::BasicAuthUser;
+use crate::domain::{IssueStatus, SendTime};
+use crate::idempotency::{
+    delete_pending, save_response, try_processing, IdempotencyKey, NextAction,
+};
+use crate::issue_scheduler::{enqueue_delivery, ENQUEUE_LEASE};
+use crate::storage::{
+    DeliveryQueue, IdempotencyRepository, ListRepository, MailingList, SubscriberRepository,
+};
+use crate::utils::{e400, e500};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct BodyData {
+    title: String,
+    content: Content,
+    /// The slug of the list to send the issue to; the main list if absent.
+    #[serde(default)]
+    list: Option<String>,
+    /// Holds the issue back until this time; a time in the past sends it
+    /// right away.
+    #[serde(default)]
+    send_at: Option<DateTime<Utc>>,
+    /// Sends the issue at this wall-clock time in each subscriber's time
+    /// zone, or in UTC for subscribers without one. Excludes `send_at`.
+    #[serde(default)]
+    #[schema(value_type = Option<String>, example = "2026-10-19T09:00:00")]
+    send_at_local: Option<NaiveDateTime>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = NewsletterContent)]
+pub struct Content {
+    html: String,
+    text: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct PublishReceipt {
+    pub issue_id: String,
+    pub status: IssueStatus,
+    /// Deliveries queued so far; 0 while the issue is scheduled.
+    pub enqueued: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub send_at_local: Option<NaiveDateTime>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/newsletters",
+    tag = "newsletters",
+    security(("basic_auth" = [])),
+    params((
+        "Idempotency-Key" = Option<String>,
+        Header,
+        description = "Retries with the same key replay the first response instead of publishing again"
+    )),
+    request_body = BodyData,
+    responses(
+        (status = 202, description = "The issue was queued or scheduled for delivery", body = PublishReceipt),
+        (status = 400, description = "Invalid issue, unknown list or invalid idempotency key"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 409, description = "A request with the same idempotency key is still in flight")
+    )
+)]
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(body, request, subscribers, delivery_queue, idempotency, lists, user),
+    fields(title = %body.title, username = %user.username, user_id = %user.user_id)
+)]
+pub async fn publish_newsletter(
+    user: BasicAuthUser,
+    body: web::Json<BodyData>,
+    request: HttpRequest,
+    subscribers: web::Data<dyn SubscriberRepository>,
+    delivery_queue: web::Data<dyn DeliveryQueue>,
+    idempotency: web::Data<dyn IdempotencyRepository>,
+    lists: web::Data<dyn ListRepository>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscribers = subscribers.get_ref();
+    let delivery_queue = delivery_queue.get_ref();
+    let idempotency = idempotency.get_ref();
+    let send_time = SendTime::parse(body.send_at, body.send_at_local).map_err(e400)?;
+    let list = match &body.list {
+        Some(slug) => Some(
+            lists
+                .find(slug)
+                .await
+                .map_err(e500)?
+                .ok_or_else(|| e400(format!("No mailing list called {}.", slug)))?,
+        ),
+        None => None,
+    };
+    let Some(idempotency_key) = get_idempotency_key(&request).map_err(e400)? else {
+        let issue_id = insert_issue(delivery_queue, &body, list.as_ref(), send_time)
+            .await
+            .map_err(e500)?;
+        let receipt = enqueue_issue(subscribers, delivery_queue, issue_id)
+            .await
+            .map_err(e500)?;
+        return Ok(HttpResponse::Accepted().json(receipt));
+    };
+
+    match try_processing(idempotency, &idempotency_key, user.user_id)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing => {}
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+    }
+    let publish = async {
+        // A retry after a failure resumes the issue the first attempt stored
+        let issue_id = match idempotency
+            .get_issue(user.user_id, &idempotency_key)
+            .await?
+        {
+            Some(issue_id) => issue_id,
+            None => {
+                let issue_id =
+                    insert_issue(delivery_queue, &body, list.as_ref(), send_time).await?;
+                idempotency
+                    .save_issue(user.user_id, &idempotency_key, issue_id)
+                    .await?;
+                issue_id
+            }
+        };
+        enqueue_issue(subscribers, delivery_queue, issue_id).await
+    };
+    let receipt = match publish.await {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            if let Err(e) = delete_pending(idempotency, &idempotency_key, user.user_id).await {
+                tracing::error!("Failed to release the idempotency key: {:?}", e);
+            }
+            return Err(e500(e));
+        }
+    };
+    let response = HttpResponse::Accepted().json(receipt);
+    save_response(idempotency, &idempotency_key, user.user_id, response)
+        .await
+        .map_err(e500)
+}
+
+fn get_idempotency_key(request: &HttpRequest) -> Result<Option<IdempotencyKey>, String> {
+    request
+        .headers()
+        .get("Idempotency-Key")
+        .map(|value| {
+            let value = value
+                .to_str()
+                .map_err(|_| "The idempotency key must be a valid string.".to_string())?;
+            IdempotencyKey::try_from(value.to_string())
+        })
+        .transpose()
+}
+
+async fn insert_issue(
+    delivery_queue: &dyn DeliveryQueue,
+    body: &BodyData,
+    list: Option<&MailingList>,
+    send_time: Option<SendTime>,
+) -> Result<ObjectId> {
+    // Local send times are always handed to the scheduler, which spreads the
+    // deliveries over the time zones once the first one comes due
+    let send_time = send_time.filter(|send_time| match send_time {
+        SendTime::At(at) => *at > Utc::now(),
+        SendTime::Local(_) => true,
+    });
+    delivery_queue
+        .insert_issue(
+            list,
+            &body.title,
+            &body.content.text,
+            &body.content.html,
+            send_time,
+            ENQUEUE_LEASE,
+        )
+        .await
+}
+
+/// Queues the deliveries of a published issue that are not queued yet. If
+/// this fails, the scheduler finishes the job once the issue's claim runs
+/// out.
+async fn enqueue_issue(
+    subscribers: &dyn SubscriberRepository,
+    delivery_queue: &dyn DeliveryQueue,
+    issue_id: ObjectId,
+) -> Result<PublishReceipt> {
+    let issue = delivery_queue
+        .get_issue(issue_id)
+        .await?
+        .context("The newsletter issue was deleted")?;
+    let mut enqueued = usize::try_from(issue.recipients)?;
+    if issue.status == IssueStatus::Published && issue.enqueuing_until.is_some() {
+        enqueued += enqueue_delivery(
+            subscribers,
+            delivery_queue,
+            issue.id,
+            issue.list.as_deref(),
+            None,
+        )
+        .await?;
+        delivery_queue.finish_enqueuing(issue.id).await?;
+    }
+    Ok(PublishReceipt {
+        issue_id: issue.id.to_hex(),
+        status: issue.status,
+        enqueued,
+        send_at: issue.send_at,
+        send_at_local: issue.send_at_local,
+    })
+}
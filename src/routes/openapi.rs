@@ -0,0 +1,333 @@
+use actix_web::http::header::{ContentType, CONTENT_SECURITY_POLICY};
+use actix_web::HttpResponse;
+use serde_json::Value;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::domain::{IssueStatus, SubscriberStatus};
+use crate::routes::{
+    BodyData, BounceType, Content, EmailEvent, FormData, IssueList, IssueResource, IssueSchedule,
+    IssueStats, ListData, LoginFormData, MailingListResource, MailingLists, MembershipResource,
+    PasswordFormData, PublishReceipt, SubscriberPage, SubscriberPatch, SubscriberResource,
+    SubscriptionAcknowledgement, SubscriptionResponse,
+};
+use crate::utils::ProblemDetails;
+
+/// The OpenAPI description of every route, generated from the handlers'
+/// `#[utoipa::path]` annotations.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "zero", description = "A newsletter delivery service."),
+    paths(
+        crate::routes::health_check,
+        crate::routes::login_form,
+        crate::routes::login,
+        crate::routes::admin_dashboard,
+        crate::routes::change_password_form,
+        crate::routes::change_password,
+        crate::routes::log_out,
+        crate::routes::subscribe,
+        crate::routes::confirm,
+        crate::routes::unsubscribe_form,
+        crate::routes::unsubscribe,
+        crate::routes::publish_newsletter,
+        crate::routes::list_subscribers,
+        crate::routes::get_subscriber,
+        crate::routes::update_subscriber,
+        crate::routes::delete_subscriber,
+        crate::routes::list_scheduled_issues,
+        crate::routes::reschedule_issue,
+        crate::routes::cancel_issue,
+        crate::routes::get_issue_stats,
+        crate::routes::list_mailing_lists,
+        crate::routes::create_mailing_list,
+        crate::routes::email_events,
+        crate::routes::track_open,
+        crate::routes::track_click,
+    ),
+    components(schemas(
+        BodyData,
+        BounceType,
+        Content,
+        EmailEvent,
+        FormData,
+        IssueList,
+        IssueResource,
+        IssueSchedule,
+        IssueStats,
+        IssueStatus,
+        ListData,
+        LoginFormData,
+        MailingListResource,
+        MailingLists,
+        MembershipResource,
+        PasswordFormData,
+        ProblemDetails,
+        PublishReceipt,
+        SubscriberPage,
+        SubscriberPatch,
+        SubscriberResource,
+        SubscriberStatus,
+        SubscriptionAcknowledgement,
+        SubscriptionResponse,
+    )),
+    modifiers(&SecuritySchemes, &Unlicensed),
+    tags(
+        (name = "health"),
+        (name = "subscriptions", description = "Public subscription flow"),
+        (name = "newsletters", description = "Publishing issues"),
+        (name = "subscribers", description = "Subscriber management API"),
+        (name = "issues", description = "Newsletter issue scheduling and reporting API"),
+        (name = "lists", description = "Mailing list management API"),
+        (name = "admin", description = "Admin panel"),
+        (name = "webhooks", description = "Callbacks from the email provider"),
+        (name = "tracking", description = "Open and click tracking embedded in issues"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+    }
+}
+
+/// utoipa copies the license from Cargo.toml, which doesn't declare one, and
+/// an empty license name is not valid OpenAPI.
+struct Unlicensed;
+
+impl Modify for Unlicensed {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.license = None;
+    }
+}
+
+pub async fn openapi_spec() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// The Swagger-style viewer behind `/docs`. It is rendered from the spec on
+/// the server, so the page runs no scripts and loads nothing from elsewhere.
+pub async fn api_docs() -> HttpResponse {
+    let spec = serde_json::to_value(ApiDoc::openapi()).expect("The OpenAPI spec is valid JSON");
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header((
+            CONTENT_SECURITY_POLICY,
+            "default-src 'none'; style-src 'unsafe-inline'",
+        ))
+        .body(render_docs(&spec))
+}
+
+fn escape(text: &str) -> String {
+    htmlescape::encode_minimal(text)
+}
+
+/// The HTML-escaped string at `value`, or nothing if it is not a string.
+fn text(value: &Value) -> String {
+    escape(value.as_str().unwrap_or_default())
+}
+
+fn render_docs(spec: &Value) -> String {
+    let mut body = format!(
+        r#"<h1>{}</h1><p>{}</p><p>The raw specification lives at <a href="/openapi.json">/openapi.json</a>.</p>"#,
+        text(&spec["info"]["title"]),
+        text(&spec["info"]["description"]),
+    );
+    for tag in spec["tags"].as_array().into_iter().flatten() {
+        let name = tag["name"].as_str().unwrap_or_default();
+        body.push_str(&format!(
+            "<h2>{}</h2><p>{}</p>",
+            escape(name),
+            text(&tag["description"])
+        ));
+        for (path, item) in spec["paths"].as_object().into_iter().flatten() {
+            for (method, operation) in item.as_object().into_iter().flatten() {
+                let tagged = operation["tags"]
+                    .as_array()
+                    .is_some_and(|tags| tags.iter().any(|tag| tag == name));
+                if tagged {
+                    body.push_str(&render_operation(path, method, operation));
+                }
+            }
+        }
+    }
+    body.push_str("<h2>Schemas</h2>");
+    for (name, schema) in spec["components"]["schemas"]
+        .as_object()
+        .into_iter()
+        .flatten()
+    {
+        body.push_str(&format!(
+            r#"<details id="schema-{name}"><summary><code>{name}</code></summary>{}</details>"#,
+            render_schema(schema),
+            name = escape(name),
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API documentation</title>
+    <style>
+        body {{ font-family: sans-serif; max-width: 60em; margin: 0 auto; padding: 1em; }}
+        details {{ border: 1px solid #ccc; border-radius: 4px; margin: 0.5em 0; padding: 0.5em; }}
+        summary {{ cursor: pointer; }}
+        table {{ border-collapse: collapse; margin: 0.5em 0; }}
+        th, td {{ border-bottom: 1px solid #eee; padding: 0.25em 0.75em; text-align: left; }}
+        .method {{ display: inline-block; min-width: 4em; font-weight: bold; color: #fff; background: #555; border-radius: 3px; text-align: center; }}
+        .get {{ background: #1f6feb; }} .post {{ background: #2da44e; }}
+        .patch {{ background: #bf8700; }} .delete {{ background: #cf222e; }}
+    </style>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+    )
+}
+
+fn render_operation(path: &str, method: &str, operation: &Value) -> String {
+    let mut html = format!(
+        r#"<details><summary><span class="method {}">{}</span> <code>{}</code> {}</summary><p>{}</p>"#,
+        escape(method),
+        escape(&method.to_uppercase()),
+        escape(path),
+        text(&operation["summary"]),
+        text(&operation["description"]),
+    );
+    if let Some(parameters) = operation["parameters"].as_array() {
+        html.push_str("<h4>Parameters</h4><table><tr><th>Name</th><th>In</th><th>Type</th><th>Description</th></tr>");
+        for parameter in parameters {
+            html.push_str(&format!(
+                "<tr><td><code>{}</code>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                text(&parameter["name"]),
+                required_marker(parameter["required"].as_bool().unwrap_or_default()),
+                text(&parameter["in"]),
+                schema_type(&parameter["schema"]),
+                text(&parameter["description"]),
+            ));
+        }
+        html.push_str("</table>");
+    }
+    if let Some(content) = operation["requestBody"]["content"].as_object() {
+        html.push_str("<h4>Request body</h4><ul>");
+        for (content_type, media) in content {
+            html.push_str(&format!(
+                "<li><code>{}</code>: {}</li>",
+                escape(content_type),
+                schema_type(&media["schema"])
+            ));
+        }
+        html.push_str("</ul>");
+    }
+    html.push_str(
+        "<h4>Responses</h4><table><tr><th>Status</th><th>Description</th><th>Body</th></tr>",
+    );
+    for (status, response) in operation["responses"].as_object().into_iter().flatten() {
+        let body: Vec<_> = response["content"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(content_type, media)| {
+                format!(
+                    "<code>{}</code>: {}",
+                    escape(content_type),
+                    schema_type(&media["schema"])
+                )
+            })
+            .collect();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(status),
+            text(&response["description"]),
+            body.join("<br>")
+        ));
+    }
+    html.push_str("</table></details>");
+    html
+}
+
+fn required_marker(required: bool) -> &'static str {
+    if required {
+        r#" <abbr title="required">*</abbr>"#
+    } else {
+        ""
+    }
+}
+
+/// Describes the type of `schema`, linking to the named schemas it uses.
+fn schema_type(schema: &Value) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = escape(reference.rsplit('/').next().unwrap_or_default());
+        return format!(r##"<a href="#schema-{name}">{name}</a>"##);
+    }
+    for combinator in ["oneOf", "anyOf", "allOf"] {
+        if let Some(variants) = schema[combinator].as_array() {
+            let variants: Vec<_> = variants.iter().map(schema_type).collect();
+            return variants.join(" | ");
+        }
+    }
+    let types: Vec<String> = match &schema["type"] {
+        Value::String(single) => vec![single.clone()],
+        Value::Array(several) => several
+            .iter()
+            .filter_map(|t| t.as_str().map(str::to_owned))
+            .collect(),
+        _ => return "any".into(),
+    };
+    types
+        .iter()
+        .map(|t| match t.as_str() {
+            "array" => format!("array of {}", schema_type(&schema["items"])),
+            other => escape(other),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Lists the fields of an object schema, the values of an enum or the
+/// variants of a `oneOf`.
+fn render_schema(schema: &Value) -> String {
+    let mut html = format!("<p>{}</p>", text(&schema["description"]));
+    if let Some(values) = schema["enum"].as_array() {
+        let values: Vec<_> = values
+            .iter()
+            .map(|value| format!("<code>{}</code>", escape(&value.to_string())))
+            .collect();
+        html.push_str(&format!("<p>One of {}.</p>", values.join(", ")));
+    }
+    if let Some(variants) = schema["oneOf"].as_array() {
+        for variant in variants {
+            html.push_str(&render_schema(variant));
+        }
+    }
+    if let Some(properties) = schema["properties"].as_object() {
+        let required = schema["required"].as_array();
+        html.push_str("<table><tr><th>Field</th><th>Type</th><th>Description</th></tr>");
+        for (name, property) in properties {
+            let is_required =
+                required.is_some_and(|required| required.iter().any(|field| field == name));
+            html.push_str(&format!(
+                "<tr><td><code>{}</code>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                required_marker(is_required),
+                schema_type(property),
+                text(&property["description"]),
+            ));
+        }
+        html.push_str("</table>");
+    }
+    html
+}
@@ -1,76 +1,407 @@
-use actix_web::{web, HttpResponse};
-use anyhow::Result;
-use mongodb::bson::doc;
-use mongodb::options::UpdateOptions;
+use actix_web::http::StatusCode;
+use actix_web::{web, Either, HttpResponse, ResponseError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
 
-use crate::domain::{NewSubscriber, SubscriberName, SubscriberEmail};
+use crate::domain::{
+    NewSubscriber, SubscriberEmail, SubscriberName, SubscriberStatus, SubscriberTimeZone,
+};
+use crate::email_client::{EmailClient, MessageOptions};
+use crate::startup::ApplicationBaseUrl;
+use crate::storage::{ListRepository, MailingList, Subscriber, SubscriberRepository};
+use crate::templates::{EmailTemplate, Templates};
+use crate::utils::{error_chain_fmt, ProblemDetails};
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(serde::Deserialize, serde::Serialize, utoipa::ToSchema)]
 pub struct FormData {
     pub email: String,
     pub name: String,
+    /// An IANA time zone name, such as `Europe/Berlin`.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// The slug of the list to join; the main list if absent.
+    #[serde(default)]
+    pub list: Option<String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
     type Error = String;
-    fn try_from(form: FormData) -> std::result::Result<Self, Self::Error> {
+    fn try_from(form: FormData) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(form.name)?;
         let email = SubscriberEmail::parse(form.email)?;
-        Ok(NewSubscriber { name, email})
+        // Forms submit an empty field when no time zone was picked
+        let time_zone = form
+            .time_zone
+            .filter(|time_zone| !time_zone.is_empty())
+            .map(SubscriberTimeZone::parse)
+            .transpose()?;
+        Ok(NewSubscriber {
+            name,
+            email,
+            time_zone,
+        })
     }
 }
 
+/// The JSON representation of a subscriber.
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct SubscriberResource {
+    pub id: String,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriberStatus,
+    pub created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// The lists, besides the main list, they joined.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<MembershipResource>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct MembershipResource {
+    pub list: String,
+    pub status: SubscriberStatus,
+}
+
+impl From<&Subscriber> for SubscriberResource {
+    fn from(subscriber: &Subscriber) -> Self {
+        Self {
+            id: subscriber.id.to_hex(),
+            email: subscriber.email.clone(),
+            name: subscriber.name.clone(),
+            status: subscriber.status,
+            created: subscriber.created,
+            time_zone: subscriber.time_zone.clone(),
+            lists: subscriber
+                .lists
+                .iter()
+                .map(|membership| MembershipResource {
+                    list: membership.list.clone(),
+                    status: membership.status,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What a JSON client gets back for an address that was already known.
+/// Anyone can submit any address, so it tells nothing about the subscriber.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriptionAcknowledgement {
+    pub email: String,
+    pub message: String,
+}
+
+/// The JSON response to a subscription: the subscriber if the request created
+/// them, an acknowledgement otherwise.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum SubscriptionResponse {
+    Created(SubscriberResource),
+    Acknowledged(SubscriptionAcknowledgement),
+}
+
+pub enum SubscribeError {
+    ValidationError(String),
+    StorageError(anyhow::Error),
+    EmailError(anyhow::Error),
+}
+
+impl SubscribeError {
+    /// A stable identifier for the failure, included in the response body.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubscribeError::ValidationError(_) => "subscription.invalid_data",
+            SubscribeError::StorageError(_) => "subscription.storage_failure",
+            SubscribeError::EmailError(_) => "subscription.email_failure",
+        }
+    }
+}
+
+impl std::fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscribeError::ValidationError(e) => write!(f, "{}", e),
+            SubscribeError::StorageError(_) => {
+                write!(f, "Failed to store the subscription. Please retry later.")
+            }
+            SubscribeError::EmailError(_) => write!(
+                f,
+                "Failed to send a confirmation email. Please retry later."
+            ),
+        }
+    }
+}
+
+impl std::fmt::Debug for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl std::error::Error for SubscribeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SubscribeError::ValidationError(_) => None,
+            SubscribeError::StorageError(e) | SubscribeError::EmailError(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl ResponseError for SubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::StorageError(_) | SubscribeError::EmailError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ProblemDetails::new(self.status_code(), self.code(), self.to_string()).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(content(
+        (FormData = "application/x-www-form-urlencoded"),
+        (FormData = "application/json")
+    )),
+    responses(
+        (
+            status = 200,
+            description = "The subscriber is stored and, while pending, sent a confirmation email. \
+                JSON requests get a new subscriber back, and only an acknowledgement for a known address.",
+            body = SubscriptionResponse
+        ),
+        (status = 400, description = "Invalid subscriber data or unknown list", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "The subscriber could not be stored or emailed", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, db_client),
+    skip(body, subscribers, lists, email_client, templates, base_url),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name,
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty,
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
-    db_client: web::Data<mongodb::Client>,
-) -> HttpResponse {
-    let new_subscriber = match form.0.try_into() {
-        Ok(subscriber) => subscriber,
-        Err(_) => return HttpResponse::BadRequest().finish(),
+    body: Either<web::Json<FormData>, web::Form<FormData>>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+    lists: web::Data<dyn ListRepository>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, SubscribeError> {
+    // JSON clients get a new subscriber back, HTML forms only need the status code
+    let (mut form, respond_with_json) = match body {
+        Either::Left(json) => (json.into_inner(), true),
+        Either::Right(form) => (form.into_inner(), false),
+    };
+    tracing::Span::current()
+        .record("subscriber_email", tracing::field::display(&form.email))
+        .record("subscriber_name", tracing::field::display(&form.name));
+    // Forms submit an empty field for the main list
+    let list = match form.list.take().filter(|list| !list.is_empty()) {
+        Some(slug) => Some(
+            lists
+                .find(&slug)
+                .await
+                .map_err(SubscribeError::StorageError)?
+                .ok_or_else(|| {
+                    SubscribeError::ValidationError(format!("No mailing list called {}.", slug))
+                })?,
+        ),
+        None => None,
+    };
+    let new_subscriber: NewSubscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let (mut subscriber, created) = insert_subscriber(subscribers.get_ref(), &new_subscriber)
+        .await
+        .map_err(SubscribeError::StorageError)?;
+    if let Some(list) = &list {
+        subscriber = subscribers
+            .join_list(subscriber.id, &list.slug)
+            .await
+            .and_then(|subscriber| subscriber.context("The subscriber was deleted"))
+            .map_err(SubscribeError::StorageError)?;
+    }
+    subscriber = resubscribe(subscribers.get_ref(), subscriber, list.as_ref())
+        .await
+        .map_err(SubscribeError::StorageError)?;
+    let awaits_confirmation = match &list {
+        Some(list) => {
+            // Bounced and complained addresses get no email from any list
+            !subscriber.status.is_suppressed()
+                && subscriber.membership(&list.slug).is_some_and(|membership| {
+                    membership.status == SubscriberStatus::PendingConfirmation
+                })
+        }
+        None => subscriber.status == SubscriberStatus::PendingConfirmation,
+    };
+    let response = match (respond_with_json, created) {
+        (false, _) => HttpResponse::Ok().finish(),
+        (true, true) => HttpResponse::Ok().json(SubscriptionResponse::Created(
+            SubscriberResource::from(&subscriber),
+        )),
+        (true, false) => HttpResponse::Ok().json(SubscriptionResponse::Acknowledged(
+            SubscriptionAcknowledgement {
+                email: subscriber.email.clone(),
+                message: "The subscription request was received.".into(),
+            },
+        )),
     };
-    match insert_subscriber(&db_client, &new_subscriber).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    if !awaits_confirmation {
+        return Ok(response);
     }
+    let subscription_token = generate_subscription_token();
+    store_token(
+        subscribers.get_ref(),
+        subscriber.id,
+        list.as_ref(),
+        &subscription_token,
+    )
+    .await
+    .map_err(SubscribeError::StorageError)?;
+    send_confirmation_email(
+        &email_client,
+        &templates,
+        new_subscriber,
+        list.as_ref(),
+        &base_url.0,
+        &subscription_token,
+    )
+    .await
+    .map_err(SubscribeError::EmailError)?;
+    Ok(response)
 }
 
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
-    skip(new_subscriber, db_client)
+    skip(new_subscriber, subscribers)
 )]
-pub async fn insert_subscriber(
-    db_client: &mongodb::Client,
+async fn insert_subscriber(
+    subscribers: &dyn SubscriberRepository,
     new_subscriber: &NewSubscriber,
-) -> Result<()> {
-    let db_options = UpdateOptions::builder().upsert(true).build();
-    db_client
-        .database("zero")
-        .collection::<FormData>("subscribers")
-        .update_one(
-            doc! {
-                "email": new_subscriber.email.as_ref(),
-            },
-            doc! {
-                "$setOnInsert": {
-                    "email": new_subscriber.email.as_ref(),
-                    "name": new_subscriber.name.as_ref(),
-                    "created": chrono::Utc::now(),
-                }
-            },
-            Some(db_options),
+) -> anyhow::Result<(Subscriber, bool)> {
+    subscribers.insert(new_subscriber).await
+}
+
+/// Moves a subscriber who unsubscribed from `list` back to pending
+/// confirmation, so signing up again sends them a fresh confirmation email.
+#[tracing::instrument(name = "Resubscribing an unsubscribed subscriber", skip_all)]
+async fn resubscribe(
+    subscribers: &dyn SubscriberRepository,
+    subscriber: Subscriber,
+    list: Option<&MailingList>,
+) -> anyhow::Result<Subscriber> {
+    let pending = SubscriberStatus::PendingConfirmation;
+    let moved = match list {
+        Some(list) => {
+            let unsubscribed = subscriber
+                .membership(&list.slug)
+                .is_some_and(|membership| membership.status.can_become(pending));
+            unsubscribed
+                && subscribers
+                    .update_membership_status(subscriber.id, &list.slug, pending)
+                    .await?
+        }
+        None => {
+            subscriber.status.can_become(pending)
+                && subscribers.update_status(subscriber.id, pending).await?
+        }
+    };
+    if !moved {
+        return Ok(subscriber);
+    }
+    subscribers
+        .find_by_id(subscriber.id)
+        .await?
+        .context("The subscriber was deleted")
+}
+
+#[tracing::instrument(
+    name = "Store subscription token in the database",
+    skip(subscription_token, subscribers)
+)]
+async fn store_token(
+    subscribers: &dyn SubscriberRepository,
+    subscriber_id: ObjectId,
+    list: Option<&MailingList>,
+    subscription_token: &str,
+) -> anyhow::Result<()> {
+    let list = list.map(|list| list.slug.as_str());
+    subscribers
+        .store_token(subscriber_id, list, subscription_token)
+        .await
+}
+
+#[tracing::instrument(
+    name = "Send a confirmation email to a new subscriber",
+    skip(
+        email_client,
+        templates,
+        new_subscriber,
+        list,
+        base_url,
+        subscription_token
+    )
+)]
+async fn send_confirmation_email(
+    email_client: &EmailClient,
+    templates: &Templates,
+    new_subscriber: NewSubscriber,
+    list: Option<&MailingList>,
+    base_url: &str,
+    subscription_token: &str,
+) -> anyhow::Result<()> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    );
+    let content = templates.render(&EmailTemplate::Confirmation {
+        name: new_subscriber.name.as_ref(),
+        confirmation_link: &confirmation_link,
+    })?;
+    email_client
+        .send_email_with_options(
+            new_subscriber.email,
+            "Welcome!",
+            &content.html,
+            &content.text,
+            &sender_options(list),
         )
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to execute query: {:?}", e);
-            e
-        })?;
-    Ok(())
+        .context("Failed to send a confirmation email")
+}
+
+/// Sends emails about `list` as the list's sender.
+pub(crate) fn sender_options(list: Option<&MailingList>) -> MessageOptions {
+    let Some(list) = list else {
+        return MessageOptions::default();
+    };
+    MessageOptions {
+        sender_name: list.sender_name.clone(),
+        reply_to: list
+            .reply_to
+            .clone()
+            .and_then(|reply_to| SubscriberEmail::parse(reply_to).ok()),
+        ..MessageOptions::default()
+    }
+}
+
+fn generate_subscription_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
 }
@@ -0,0 +1,124 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+
+use crate::domain::{SubscriberEmail, SubscriberStatus};
+use crate::email_client::EmailClient;
+use crate::routes::sender_options;
+use crate::storage::{ListRepository, Subscriber, SubscriberRepository};
+use crate::templates::{EmailTemplate, Templates};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    subscription_token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "A pending subscriber is confirmed on the main list or the list they joined and sent a welcome email"),
+        (status = 400, description = "The token is missing"),
+        (status = 401, description = "The token is unknown")
+    )
+)]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, subscribers, lists, email_client, templates)
+)]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+    lists: web::Data<dyn ListRepository>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+) -> HttpResponse {
+    let token_owner = match subscribers
+        .find_by_token(&parameters.subscription_token)
+        .await
+    {
+        Ok(token_owner) => token_owner,
+        Err(e) => {
+            tracing::error!("Failed to execute query: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    match token_owner {
+        None => HttpResponse::Unauthorized().finish(),
+        Some((subscriber_id, list)) => {
+            // Old links must not confirm an unsubscribed or suppressed address
+            let confirmed = match &list {
+                Some(list) => {
+                    subscribers
+                        .update_membership_status(subscriber_id, list, SubscriberStatus::Confirmed)
+                        .await
+                }
+                None => {
+                    subscribers
+                        .update_status(subscriber_id, SubscriberStatus::Confirmed)
+                        .await
+                }
+            };
+            let subscriber = match confirmed {
+                // Following the link again must not send another welcome
+                Ok(false) => None,
+                Ok(true) => match subscribers.find_by_id(subscriber_id).await {
+                    Ok(subscriber) => subscriber,
+                    Err(e) => {
+                        tracing::error!("Failed to execute query: {:?}", e);
+                        return HttpResponse::InternalServerError().finish();
+                    }
+                },
+                Err(e) => {
+                    tracing::error!("Failed to execute query: {:?}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            };
+            if let Some(subscriber) = subscriber {
+                // The subscription stands even if the welcome email is lost
+                if let Err(e) = send_welcome_email(
+                    &email_client,
+                    &templates,
+                    lists.get_ref(),
+                    &subscriber,
+                    list.as_deref(),
+                )
+                .await
+                {
+                    tracing::warn!("Failed to send a welcome email: {:?}", e);
+                }
+            }
+            HttpResponse::Ok().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Send a welcome email to a confirmed subscriber", skip_all)]
+async fn send_welcome_email(
+    email_client: &EmailClient,
+    templates: &Templates,
+    lists: &dyn ListRepository,
+    subscriber: &Subscriber,
+    list: Option<&str>,
+) -> anyhow::Result<()> {
+    let email = SubscriberEmail::parse(subscriber.email.clone()).map_err(anyhow::Error::msg)?;
+    let list = match list {
+        Some(slug) => lists.find(slug).await?,
+        None => None,
+    };
+    let content = templates.render(&EmailTemplate::Welcome {
+        name: &subscriber.name,
+    })?;
+    email_client
+        .send_email_with_options(
+            email,
+            "You're subscribed!",
+            &content.html,
+            &content.text,
+            &sender_options(list.as_ref()),
+        )
+        .await
+        .context("Failed to send a welcome email")
+}
@@ -0,0 +1,160 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+
+use crate::domain::{SubscriberEmail, SubscriberStatus};
+use crate::email_client::EmailClient;
+use crate::routes::sender_options;
+use crate::storage::{ListRepository, Subscriber, SubscriberRepository};
+use crate::templates::{EmailTemplate, Templates};
+use crate::unsubscribe::UnsubscribeSigner;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+/// Asks for confirmation rather than unsubscribing straight away: link
+/// scanners and mail previews follow `GET` links on their own.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "A form confirming the unsubscription", content_type = "text/html"),
+        (status = 401, description = "The token is not valid")
+    )
+)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    signer: web::Data<UnsubscribeSigner>,
+) -> HttpResponse {
+    if signer.verify(&parameters.token).is_err() {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let token = htmlescape::encode_attribute(&parameters.token);
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <form action="/subscriptions/unsubscribe?token={token}" method="post">
+        <p>Do you want to stop receiving our newsletter?</p>
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+        ))
+}
+
+/// Handles both the form above and RFC 8058 one-click requests sent by mail
+/// clients with a `List-Unsubscribe=One-Click` body.
+#[utoipa::path(
+    post,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "The subscriber is unsubscribed from the main list or the list the token names and, the first time, sent a confirmation email", content_type = "text/html"),
+        (status = 401, description = "The token is not valid")
+    )
+)]
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, subscribers, lists, signer, email_client, templates)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+    lists: web::Data<dyn ListRepository>,
+    signer: web::Data<UnsubscribeSigner>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Templates>,
+) -> HttpResponse {
+    let (subscriber_id, list) = match signer.verify(&parameters.token) {
+        Ok(token_owner) => token_owner,
+        Err(e) => {
+            tracing::warn!("Rejected unsubscribe request: {}", e);
+            return HttpResponse::Unauthorized().finish();
+        }
+    };
+    // Suppressed addresses stay suppressed and get no email
+    let unsubscribed = match &list {
+        Some(list) => {
+            subscribers
+                .update_membership_status(subscriber_id, list, SubscriberStatus::Unsubscribed)
+                .await
+        }
+        None => {
+            subscribers
+                .update_status(subscriber_id, SubscriberStatus::Unsubscribed)
+                .await
+        }
+    };
+    let subscriber = match unsubscribed {
+        // Repeated one-click requests must not send another email
+        Ok(false) => None,
+        Ok(true) => match subscribers.find_by_id(subscriber_id).await {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                tracing::error!("Failed to execute query: {:?}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
+        Err(e) => {
+            tracing::error!("Failed to execute query: {:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if let Some(subscriber) = subscriber {
+        if let Err(e) = send_unsubscribe_confirmation_email(
+            &email_client,
+            &templates,
+            lists.get_ref(),
+            &subscriber,
+            list.as_deref(),
+        )
+        .await
+        {
+            tracing::warn!("Failed to send an unsubscribe confirmation email: {:?}", e);
+        }
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>You have been unsubscribed.</p>")
+}
+
+#[tracing::instrument(name = "Send an unsubscribe confirmation email", skip_all)]
+async fn send_unsubscribe_confirmation_email(
+    email_client: &EmailClient,
+    templates: &Templates,
+    lists: &dyn ListRepository,
+    subscriber: &Subscriber,
+    list: Option<&str>,
+) -> anyhow::Result<()> {
+    let email = SubscriberEmail::parse(subscriber.email.clone()).map_err(anyhow::Error::msg)?;
+    let list = match list {
+        Some(slug) => lists.find(slug).await?,
+        None => None,
+    };
+    let content = templates.render(&EmailTemplate::UnsubscribeConfirmation {
+        name: &subscriber.name,
+    })?;
+    email_client
+        .send_email_with_options(
+            email,
+            "You have been unsubscribed",
+            &content.html,
+            &content.text,
+            &sender_options(list.as_ref()),
+        )
+        .await
+        .context("Failed to send an unsubscribe confirmation email")
+}
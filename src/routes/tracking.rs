@@ -0,0 +1,94 @@
+use actix_web::http::header::{self, CacheControl, CacheDirective};
+use actix_web::{web, HttpResponse};
+
+use crate::storage::{TrackingEvent, TrackingRepository};
+use crate::tracking::TrackingSigner;
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// Records that the recipient opened the issue. The pixel is served even
+/// for invalid tokens so mail clients never show a broken image.
+#[utoipa::path(
+    get,
+    path = "/t/o/{token}.gif",
+    tag = "tracking",
+    params(("token" = String, Path, description = "The token embedded in the issue")),
+    responses((status = 200, description = "A transparent 1x1 pixel", content_type = "image/gif"))
+)]
+#[tracing::instrument(name = "Track an open", skip_all)]
+pub async fn track_open(
+    token: web::Path<String>,
+    signer: web::Data<TrackingSigner>,
+    tracking: web::Data<dyn TrackingRepository>,
+) -> HttpResponse {
+    match signer.verify_open(&token) {
+        Ok(recipient) => {
+            if let Err(e) = tracking
+                .record(
+                    recipient.issue_id,
+                    recipient.subscriber_id,
+                    TrackingEvent::Open,
+                    None,
+                )
+                .await
+            {
+                tracing::warn!(error = ?e, "Failed to record an open");
+            }
+        }
+        Err(e) => tracing::info!("Ignoring an open with an invalid token: {}", e),
+    }
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![
+            CacheDirective::NoCache,
+            CacheDirective::NoStore,
+            CacheDirective::MustRevalidate,
+        ]))
+        .body(PIXEL)
+}
+
+/// Records that the recipient followed a link of the issue and sends them
+/// on to it.
+#[utoipa::path(
+    get,
+    path = "/t/c/{token}",
+    tag = "tracking",
+    params(("token" = String, Path, description = "The token a link of the issue was rewritten to")),
+    responses(
+        (status = 302, description = "Redirects to the original link"),
+        (status = 404, description = "The token is not valid")
+    )
+)]
+#[tracing::instrument(name = "Track a click", skip_all)]
+pub async fn track_click(
+    token: web::Path<String>,
+    signer: web::Data<TrackingSigner>,
+    tracking: web::Data<dyn TrackingRepository>,
+) -> HttpResponse {
+    let (recipient, url) = match signer.verify_click(&token) {
+        Ok(click) => click,
+        Err(e) => {
+            tracing::info!("Rejecting a click with an invalid token: {}", e);
+            return HttpResponse::NotFound().finish();
+        }
+    };
+    if let Err(e) = tracking
+        .record(
+            recipient.issue_id,
+            recipient.subscriber_id,
+            TrackingEvent::Click,
+            Some(&url),
+        )
+        .await
+    {
+        tracing::warn!(error = ?e, "Failed to record a click");
+    }
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .finish()
+}
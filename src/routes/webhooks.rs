@@ -0,0 +1,302 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+use crate::domain::SubscriberStatus;
+use crate::storage::SubscriberRepository;
+use crate::utils::{error_chain_fmt, ProblemDetails};
+
+/// How old a signed webhook request may be before it is treated as a replay.
+const MAX_SIGNATURE_AGE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Checks that email event webhooks come from the provider.
+///
+/// The provider signs `<timestamp>.<body>` with HMAC-SHA256 and sends the
+/// timestamp (in Unix seconds) in `X-Webhook-Timestamp` and the hex encoded
+/// signature as `sha256=<signature>` in `X-Webhook-Signature`.
+pub struct WebhookVerifier {
+    secret: Option<Secret<String>>,
+}
+
+impl WebhookVerifier {
+    /// Without a secret every request is rejected.
+    pub fn new(secret: Option<Secret<String>>) -> Self {
+        Self { secret }
+    }
+
+    fn mac(secret: &Secret<String>, timestamp: &str, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac
+    }
+
+    /// The `X-Webhook-Signature` value for `body` sent at `timestamp`.
+    pub fn signature(&self, timestamp: &str, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let signature = Self::mac(secret, timestamp, body).finalize().into_bytes();
+        Some(format!("sha256={}", encode_hex(&signature)))
+    }
+
+    pub fn verify(&self, timestamp: &str, signature: &str, body: &[u8]) -> Result<(), String> {
+        let secret = self
+            .secret
+            .as_ref()
+            .ok_or_else(|| "No webhook secret is configured.".to_string())?;
+        let sent_at = timestamp
+            .parse()
+            .ok()
+            .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+            .ok_or_else(|| "Malformed webhook timestamp.".to_string())?;
+        if (Utc::now() - sent_at).abs() > MAX_SIGNATURE_AGE {
+            return Err("The webhook timestamp is too old.".into());
+        }
+        let signature = signature
+            .strip_prefix("sha256=")
+            .and_then(decode_hex)
+            .ok_or_else(|| "Malformed webhook signature.".to_string())?;
+        Self::mac(secret, timestamp, body)
+            .verify_slice(&signature)
+            .map_err(|_| "Invalid webhook signature.".to_string())
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// An event the email provider reports about a message it handled.
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmailEvent {
+    Bounce {
+        email: String,
+        #[serde(default)]
+        bounce_type: BounceType,
+    },
+    Complaint {
+        email: String,
+    },
+    Delivered {
+        email: String,
+    },
+    /// Event types we do not act on.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BounceType {
+    /// The address does not exist or permanently refuses mail.
+    #[default]
+    Hard,
+    /// A temporary failure, such as a full mailbox.
+    Soft,
+}
+
+pub enum WebhookError {
+    Unauthorized(String),
+    ValidationError(String),
+    UnexpectedError(anyhow::Error),
+}
+
+impl WebhookError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            WebhookError::Unauthorized(_) => "webhook.invalid_signature",
+            WebhookError::ValidationError(_) => "webhook.invalid_payload",
+            WebhookError::UnexpectedError(_) => "internal_error",
+        }
+    }
+}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::Unauthorized(e) | WebhookError::ValidationError(e) => {
+                write!(f, "{}", e)
+            }
+            WebhookError::UnexpectedError(_) => {
+                write!(f, "Something went wrong. Please retry later.")
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl std::error::Error for WebhookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WebhookError::UnexpectedError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            WebhookError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ProblemDetails::new(self.status_code(), self.code(), self.to_string()).into_response()
+    }
+}
+
+/// Takes bounced and complaining addresses off the mailing list. A hard
+/// bounce marks the subscriber `bounced`, a complaint `complained`; neither
+/// receives any further issues.
+#[utoipa::path(
+    post,
+    path = "/webhooks/email-events",
+    tag = "webhooks",
+    params(
+        ("X-Webhook-Timestamp" = String, Header, description = "When the request was signed, in Unix seconds"),
+        ("X-Webhook-Signature" = String, Header, description = "`sha256=` followed by the hex encoded HMAC-SHA256 of `<timestamp>.<body>`")
+    ),
+    request_body = Vec<EmailEvent>,
+    responses(
+        (status = 200, description = "The events were processed"),
+        (status = 400, description = "The body is not a list of events", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing, stale or invalid signature", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "The events could not be processed; the provider should retry", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(name = "Process email events", skip_all)]
+pub async fn email_events(
+    request: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<WebhookVerifier>,
+    subscribers: web::Data<dyn SubscriberRepository>,
+) -> Result<HttpResponse, WebhookError> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| WebhookError::Unauthorized(format!("Missing the {} header.", name)))
+    };
+    verifier
+        .verify(
+            header("X-Webhook-Timestamp")?,
+            header("X-Webhook-Signature")?,
+            &body,
+        )
+        .map_err(WebhookError::Unauthorized)?;
+    let events: Vec<EmailEvent> = serde_json::from_slice(&body)
+        .map_err(|e| WebhookError::ValidationError(format!("Invalid events: {}", e)))?;
+
+    for event in events {
+        let (email, status) = match event {
+            EmailEvent::Bounce {
+                email,
+                bounce_type: BounceType::Hard,
+            } => (email, SubscriberStatus::Bounced),
+            EmailEvent::Complaint { email } => (email, SubscriberStatus::Complained),
+            EmailEvent::Bounce { email, .. } | EmailEvent::Delivered { email } => {
+                tracing::debug!(%email, "Ignoring a delivery or soft bounce event");
+                continue;
+            }
+            EmailEvent::Other => continue,
+        };
+        let Some(subscriber) = subscribers
+            .find_by_email(&email)
+            .await
+            .map_err(WebhookError::UnexpectedError)?
+        else {
+            tracing::info!(%email, "Ignoring an event for an unknown address");
+            continue;
+        };
+        tracing::info!(%email, status = status.as_str(), "Taking an address off the list");
+        subscribers
+            .update_status(subscriber.id, status)
+            .await
+            .map_err(WebhookError::UnexpectedError)?;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    use crate::routes::WebhookVerifier;
+
+    fn verifier(secret: &str) -> WebhookVerifier {
+        WebhookVerifier::new(Some(Secret::new(secret.into())))
+    }
+
+    #[test]
+    fn a_signed_body_is_verified() {
+        let verifier = verifier("secret");
+        let timestamp = Utc::now().timestamp().to_string();
+        let signature = verifier.signature(&timestamp, b"[]").unwrap();
+
+        assert_ok!(verifier.verify(&timestamp, &signature, b"[]"));
+    }
+
+    #[test]
+    fn a_tampered_body_or_foreign_signature_is_rejected() {
+        let timestamp = Utc::now().timestamp().to_string();
+        let signature = verifier("secret").signature(&timestamp, b"[]").unwrap();
+
+        assert_err!(verifier("secret").verify(&timestamp, &signature, b"[{}]"));
+        assert_err!(verifier("other secret").verify(&timestamp, &signature, b"[]"));
+    }
+
+    #[test]
+    fn stale_signatures_are_rejected() {
+        let verifier = verifier("secret");
+        let timestamp = (Utc::now() - chrono::Duration::hours(1))
+            .timestamp()
+            .to_string();
+        let signature = verifier.signature(&timestamp, b"[]").unwrap();
+
+        assert_err!(verifier.verify(&timestamp, &signature, b"[]"));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let verifier = verifier("secret");
+        let timestamp = Utc::now().timestamp().to_string();
+        for signature in ["", "sha256=", "sha256=abc", "sha256=zz", "md5=00"] {
+            assert_err!(verifier.verify(&timestamp, signature, b"[]"));
+        }
+        assert_err!(verifier.verify("yesterday", "sha256=00", b"[]"));
+    }
+
+    #[test]
+    fn everything_is_rejected_without_a_secret() {
+        let verifier = WebhookVerifier::new(None);
+        let timestamp = Utc::now().timestamp().to_string();
+        assert_eq!(verifier.signature(&timestamp, b"[]"), None);
+        assert_err!(verifier.verify(&timestamp, "sha256=00", b"[]"));
+    }
+}
@@ -0,0 +1,57 @@
+use std::future::{ready, Ready};
+
+use actix_session::{Session, SessionExt, SessionInsertError};
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use anyhow::Context;
+use mongodb::bson::oid::ObjectId;
+
+pub struct TypedSession(Session);
+
+impl TypedSession {
+    const USER_ID_KEY: &'static str = "user_id";
+    const FLASH_KEY: &'static str = "flash";
+
+    pub fn renew(&self) {
+        self.0.renew();
+    }
+
+    pub fn insert_user_id(&self, user_id: ObjectId) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::USER_ID_KEY, user_id.to_hex())
+    }
+
+    pub fn get_user_id(&self) -> anyhow::Result<Option<ObjectId>> {
+        self.0
+            .get::<String>(Self::USER_ID_KEY)?
+            .map(|id| ObjectId::parse_str(id).context("Invalid user id in session"))
+            .transpose()
+    }
+
+    /// Drops the logged-in user while keeping a fresh session around, so that
+    /// a flash message can still be attached to the next response.
+    pub fn log_out(&self) {
+        self.0.clear();
+        self.0.renew();
+    }
+
+    /// Stores a message to be displayed on the next page that asks for it.
+    pub fn insert_flash(&self, message: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::FLASH_KEY, message)
+    }
+
+    /// Removes and returns the pending flash message, if any.
+    pub fn take_flash(&self) -> Option<String> {
+        self.0
+            .remove_as::<String>(Self::FLASH_KEY)
+            .and_then(Result::ok)
+    }
+}
+
+impl FromRequest for TypedSession {
+    type Error = <Session as FromRequest>::Error;
+    type Future = Ready<Result<TypedSession, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(TypedSession(req.get_session())))
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use anyhow::anyhow;
+use mongodb::bson::{self, doc, Document};
+use mongodb::options::UpdateOptions;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+type SessionState = HashMap<String, String>;
+
+fn generate_session_key() -> SessionKey {
+    let key: String = std::iter::repeat_with(|| thread_rng().sample(Alphanumeric))
+        .map(char::from)
+        .take(64)
+        .collect();
+    key.try_into()
+        .expect("A 64 characters key is a valid session key")
+}
+
+/// Keeps sessions in process memory. State is lost on restart and not shared
+/// between instances, which makes it a good fit for tests and local runs.
+#[derive(Clone, Default)]
+pub struct InMemorySessionStore {
+    sessions: Arc<Mutex<HashMap<String, (SessionState, Instant)>>>,
+}
+
+impl InMemorySessionStore {
+    fn expiry(ttl: &Duration) -> Instant {
+        Instant::now() + std::time::Duration::from_secs(ttl.whole_seconds().max(0) as u64)
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(session_key.as_ref()) {
+            Some((_, expires_at)) if *expires_at <= Instant::now() => {
+                sessions.remove(session_key.as_ref());
+                Ok(None)
+            }
+            Some((state, _)) => Ok(Some(state.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_key = generate_session_key();
+        self.sessions.lock().unwrap().insert(
+            session_key.as_ref().to_owned(),
+            (session_state, Self::expiry(ttl)),
+        );
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        // The session may have expired since it was loaded: start a new one
+        let session_key = if sessions.contains_key(session_key.as_ref()) {
+            session_key
+        } else {
+            generate_session_key()
+        };
+        sessions.insert(
+            session_key.as_ref().to_owned(),
+            (session_state, Self::expiry(ttl)),
+        );
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        if let Some((_, expires_at)) = self.sessions.lock().unwrap().get_mut(session_key.as_ref()) {
+            *expires_at = Self::expiry(ttl);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        self.sessions.lock().unwrap().remove(session_key.as_ref());
+        Ok(())
+    }
+}
+
+/// Persists sessions in the `sessions` collection.
+///
+/// Expired documents are filtered out on load and reaped by the TTL index on
+/// `expires_at` created at startup.
+#[derive(Clone)]
+pub struct MongoSessionStore {
+    db_client: mongodb::Client,
+}
+
+impl MongoSessionStore {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn collection(&self) -> mongodb::Collection<Document> {
+        self.db_client.database("zero").collection("sessions")
+    }
+
+    fn expiry(ttl: &Duration) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+    }
+}
+
+impl SessionStore for MongoSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let session = self
+            .collection()
+            .find_one(
+                doc! {
+                    "_id": session_key.as_ref(),
+                    "expires_at": { "$gt": chrono::Utc::now() },
+                },
+                None,
+            )
+            .await
+            .map_err(|e| LoadError::Other(e.into()))?;
+        let Some(session) = session else {
+            return Ok(None);
+        };
+        let state = session
+            .get_document("state")
+            .map_err(|e| LoadError::Deserialization(e.into()))?;
+        bson::from_document(state.clone())
+            .map(Some)
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let state =
+            bson::to_document(&session_state).map_err(|e| SaveError::Serialization(e.into()))?;
+        let session_key = generate_session_key();
+        self.collection()
+            .insert_one(
+                doc! {
+                    "_id": session_key.as_ref(),
+                    "state": state,
+                    "expires_at": Self::expiry(ttl),
+                },
+                None,
+            )
+            .await
+            .map_err(|e| SaveError::Other(e.into()))?;
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state =
+            bson::to_document(&session_state).map_err(|e| UpdateError::Serialization(e.into()))?;
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.collection()
+            .update_one(
+                doc! { "_id": session_key.as_ref() },
+                doc! { "$set": { "state": state, "expires_at": Self::expiry(ttl) } },
+                Some(options),
+            )
+            .await
+            .map_err(|e| UpdateError::Other(e.into()))?;
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        self.collection()
+            .update_one(
+                doc! { "_id": session_key.as_ref() },
+                doc! { "$set": { "expires_at": Self::expiry(ttl) } },
+                None,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        self.collection()
+            .delete_one(doc! { "_id": session_key.as_ref() }, None)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InMemorySessionStore;
+    use actix_session::storage::SessionStore;
+    use actix_web::cookie::time::Duration;
+    use claim::{assert_none, assert_some};
+    use std::collections::HashMap;
+
+    fn state() -> HashMap<String, String> {
+        HashMap::from([("user_id".to_string(), "\"42\"".to_string())])
+    }
+
+    #[tokio::test]
+    async fn a_saved_session_can_be_loaded() {
+        let store = InMemorySessionStore::default();
+        let key = store.save(state(), &Duration::minutes(5)).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(state()));
+    }
+
+    #[tokio::test]
+    async fn an_expired_session_is_not_loaded() {
+        let store = InMemorySessionStore::default();
+        let key = store.save(state(), &Duration::ZERO).await.unwrap();
+        assert_none!(store.load(&key).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn a_deleted_session_is_not_loaded() {
+        let store = InMemorySessionStore::default();
+        let key = store.save(state(), &Duration::minutes(5)).await.unwrap();
+        assert_some!(store.load(&key).await.unwrap());
+        store.delete(&key).await.unwrap();
+        assert_none!(store.load(&key).await.unwrap());
+    }
+}
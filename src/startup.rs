@@ -1,30 +1,386 @@
+use std::future::Future;
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
 
+use actix_session::storage::SessionStore;
+use actix_session::SessionMiddleware;
 use actix_web::{
-    dev::Server,
+    cookie::Key,
+    dev::{Server, ServerHandle},
+    middleware::from_fn,
     web::{self, Data},
     App, HttpServer,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+use mongodb::{
+    bson::{doc, Document},
+    options::IndexOptions,
+    IndexModel,
+};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tracing_actix_web::TracingLogger;
 
-use crate::{email_client::EmailClient, routes::*};
+use crate::{
+    authentication::reject_anonymous_users,
+    configuration::{DatabaseSettings, Settings},
+    email_client::EmailClient,
+    issue_delivery_worker::run_worker_until_stopped,
+    issue_scheduler::run_scheduler_until_stopped,
+    routes::*,
+    session_store::MongoSessionStore,
+    storage::Storage,
+    templates::Templates,
+    tracking::TrackingSigner,
+    unsubscribe::UnsubscribeSigner,
+};
+
+pub struct ApplicationBaseUrl(pub String);
+
+/// The API server and the background delivery worker and issue scheduler,
+/// wired from [`Settings`].
+pub struct Application {
+    port: u16,
+    server: Server,
+    worker: Option<JoinHandle<Result<()>>>,
+    stop_worker: watch::Sender<bool>,
+    shutdown_timeout: Duration,
+}
+
+impl Application {
+    /// Connects to MongoDB and binds the listener described by `configuration`.
+    pub async fn build(configuration: Settings) -> Result<Self> {
+        let db_client = get_db_client(&configuration.database).await?;
+        let session_store = MongoSessionStore::new(db_client.clone());
+        Self::build_with_storage(configuration, Storage::mongo(db_client), session_store)
+    }
+
+    /// Like [`Application::build`], but on top of the given storage backends.
+    pub fn build_with_storage<S>(
+        configuration: Settings,
+        storage: Storage,
+        session_store: S,
+    ) -> Result<Self>
+    where
+        S: SessionStore + Clone + Send + 'static,
+    {
+        let listener = TcpListener::bind(format!(
+            "{}:{}",
+            configuration.application.host, configuration.application.port
+        ))
+        .context("Failed to bind port")?;
+        let port = listener.local_addr()?.port();
+        let shutdown_timeout =
+            Duration::from_secs(configuration.application.shutdown_timeout_seconds);
+
+        let email_client = configuration.email_client.client()?;
+        let templates = Arc::new(Templates::load(
+            &configuration.application.templates_directory,
+        )?);
+        let (stop_worker, worker_shutdown) = watch::channel(false);
+        let worker = configuration.application.run_delivery_worker.then(|| {
+            let scheduler = run_scheduler_until_stopped(
+                storage.subscribers.clone(),
+                storage.delivery_queue.clone(),
+                worker_shutdown.clone(),
+            );
+            let worker = run_worker_until_stopped(
+                storage.delivery_queue.clone(),
+                storage.subscribers.clone(),
+                email_client.clone(),
+                templates.clone(),
+                UnsubscribeSigner::new(
+                    configuration.application.base_url.clone(),
+                    configuration.application.hmac_secret.clone(),
+                ),
+                TrackingSigner::new(
+                    configuration.application.base_url.clone(),
+                    configuration.application.hmac_secret.clone(),
+                ),
+                worker_shutdown,
+            );
+            tokio::spawn(async move { tokio::try_join!(worker, scheduler).map(|_| ()) })
+        });
+        let server = run(
+            listener,
+            storage,
+            email_client,
+            templates,
+            WebhookVerifier::new(configuration.email_client.webhook_secret),
+            configuration.application.base_url,
+            configuration.application.hmac_secret,
+            session_store,
+            shutdown_timeout,
+        )?;
+
+        Ok(Self {
+            port,
+            server,
+            worker,
+            stop_worker,
+            shutdown_timeout,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Serves requests until SIGTERM or SIGINT is received.
+    pub async fn run_until_stopped(self) -> Result<()> {
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Serves requests until `shutdown` completes, then stops accepting
+    /// connections, gives in-flight requests up to the configured drain
+    /// timeout and waits for the background worker to wind down.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let server_handle = self.server.handle();
+        let mut server = tokio::spawn(self.server);
+        let Some(mut worker) = self.worker else {
+            tokio::select! {
+                _ = shutdown => drain(server_handle, server).await,
+                outcome = &mut server => report_exit("API", outcome),
+            }
+            return Ok(());
+        };
+        tokio::select! {
+            _ = shutdown => drain(server_handle, server).await,
+            outcome = &mut server => report_exit("API", outcome),
+            outcome = &mut worker => {
+                report_exit("Background worker", outcome);
+                server_handle.stop(true).await;
+                return Ok(());
+            }
+        }
+        let _ = self.stop_worker.send(true);
+        match tokio::time::timeout(self.shutdown_timeout, &mut worker).await {
+            Ok(outcome) => report_exit("Background worker", outcome),
+            Err(_) => {
+                tracing::warn!("Background worker did not stop in time, aborting it");
+                worker.abort();
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn drain(server_handle: ServerHandle, server: JoinHandle<std::io::Result<()>>) {
+    tracing::info!("Shutdown signal received, draining in-flight requests");
+    server_handle.stop(true).await;
+    report_exit("API", server.await);
+}
+
+async fn shutdown_signal() {
+    let interrupt = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+fn report_exit<E, T>(task_name: &str, outcome: Result<Result<T, E>, tokio::task::JoinError>)
+where
+    E: std::fmt::Debug,
+{
+    match outcome {
+        Ok(Ok(_)) => tracing::info!("{} has exited", task_name),
+        Ok(Err(e)) => tracing::error!(error = ?e, "{} failed", task_name),
+        Err(e) => tracing::error!(error = ?e, "{} task failed to complete", task_name),
+    }
+}
+
+/// Connects to MongoDB and makes sure the indexes the application relies on exist.
+pub async fn get_db_client(configuration: &DatabaseSettings) -> Result<mongodb::Client> {
+    let db_client =
+        mongodb::Client::with_uri_str(configuration.connection_string().expose_secret())
+            .await
+            .context("Failed connection to database")?;
+
+    create_unique_index(&db_client, "subscribers", doc! { "email": 1 }).await?;
+    create_unique_index(
+        &db_client,
+        "subscription_tokens",
+        doc! { "subscription_token": 1 },
+    )
+    .await?;
+    create_unique_index(&db_client, "users", doc! { "username": 1 }).await?;
+    // Lets a failed enqueue be retried without delivering an issue twice
+    create_unique_index(
+        &db_client,
+        "issue_delivery_queue",
+        doc! { "newsletter_issue_id": 1, "subscriber_id": 1 },
+    )
+    .await?;
+    create_expiry_index(&db_client, "sessions", "expires_at").await?;
+    create_expiry_index(&db_client, "idempotency", "expires_at").await?;
+    create_index(&db_client, "tracking_events", "newsletter_issue_id").await?;
+    create_index(&db_client, "issue_delivery_queue", "subscriber_id").await?;
+    create_index(&db_client, "subscribers", "lists.list").await?;
+    Ok(db_client)
+}
+
+async fn create_unique_index(
+    db_client: &mongodb::Client,
+    collection: &str,
+    keys: Document,
+) -> Result<()> {
+    let options = IndexOptions::builder().unique(true).build();
+    let model = IndexModel::builder().keys(keys).options(options).build();
+    db_client
+        .database("zero")
+        .collection::<Document>(collection)
+        .create_index(model, None)
+        .await
+        .context("Failed to create index")?;
+    Ok(())
+}
+
+async fn create_index(db_client: &mongodb::Client, collection: &str, field: &str) -> Result<()> {
+    let model = IndexModel::builder().keys(doc! { field: 1 }).build();
+    db_client
+        .database("zero")
+        .collection::<Document>(collection)
+        .create_index(model, None)
+        .await
+        .context("Failed to create index")?;
+    Ok(())
+}
+
+/// Lets MongoDB delete documents once the date stored in `field` has passed.
+async fn create_expiry_index(
+    db_client: &mongodb::Client,
+    collection: &str,
+    field: &str,
+) -> Result<()> {
+    let options = IndexOptions::builder().expire_after(Duration::ZERO).build();
+    let model = IndexModel::builder()
+        .keys(doc! { field: 1 })
+        .options(options)
+        .build();
+    db_client
+        .database("zero")
+        .collection::<Document>(collection)
+        .create_index(model, None)
+        .await
+        .context("Failed to create index")?;
+    Ok(())
+}
 
-pub fn run(
+#[allow(clippy::too_many_arguments)]
+pub fn run<S>(
     listener: TcpListener,
-    db_client: web::Data<mongodb::Client>,
+    storage: Storage,
     email_client: EmailClient,
-) -> Result<Server> {
+    templates: Arc<Templates>,
+    webhook_verifier: WebhookVerifier,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    session_store: S,
+    shutdown_timeout: Duration,
+) -> Result<Server>
+where
+    S: SessionStore + Clone + Send + 'static,
+{
+    let subscribers = Data::from(storage.subscribers);
+    let users = Data::from(storage.users);
+    let idempotency = Data::from(storage.idempotency);
+    let delivery_queue = Data::from(storage.delivery_queue);
+    let tracking = Data::from(storage.tracking);
+    let lists = Data::from(storage.lists);
     let email_client = Data::new(email_client);
+    let templates = Data::from(templates);
+    let webhook_verifier = Data::new(webhook_verifier);
+    let secure_cookies = base_url.starts_with("https://");
+    let secret_key = Key::try_from(hmac_secret.expose_secret().as_bytes())
+        .context("The HMAC secret must be at least 64 bytes long")?;
+    let unsubscribe_signer = Data::new(UnsubscribeSigner::new(
+        base_url.clone(),
+        hmac_secret.clone(),
+    ));
+    let tracking_signer = Data::new(TrackingSigner::new(base_url.clone(), hmac_secret));
+    let base_url = Data::new(ApplicationBaseUrl(base_url));
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(
+                SessionMiddleware::builder(session_store.clone(), secret_key.clone())
+                    .cookie_secure(secure_cookies)
+                    .build(),
+            )
             .wrap(TracingLogger::default())
             .route("/health_check", web::get().to(health_check))
+            .route("/openapi.json", web::get().to(openapi_spec))
+            .route("/docs", web::get().to(api_docs))
+            .route("/login", web::get().to(login_form))
+            .route("/login", web::post().to(login))
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(reject_anonymous_users))
+                    .route("/dashboard", web::get().to(admin_dashboard))
+                    .route("/password", web::get().to(change_password_form))
+                    .route("/password", web::post().to(change_password))
+                    .route("/logout", web::post().to(log_out)),
+            )
             .route("/subscriptions", web::post().to(subscribe))
-            .app_data(db_client.clone())
+            .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/newsletters", web::post().to(publish_newsletter))
+            .route("/webhooks/email-events", web::post().to(email_events))
+            .route("/t/o/{token}.gif", web::get().to(track_open))
+            .route("/t/c/{token}", web::get().to(track_click))
+            .service(
+                web::scope("/api/v1")
+                    .route("/subscribers", web::get().to(list_subscribers))
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(get_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::patch().to(update_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::delete().to(delete_subscriber),
+                    )
+                    .route("/issues/scheduled", web::get().to(list_scheduled_issues))
+                    .route("/issues/{issue_id}", web::patch().to(reschedule_issue))
+                    .route("/issues/{issue_id}/cancel", web::post().to(cancel_issue))
+                    .route("/issues/{issue_id}/stats", web::get().to(get_issue_stats))
+                    .route("/lists", web::get().to(list_mailing_lists))
+                    .route("/lists", web::post().to(create_mailing_list)),
+            )
+            .app_data(subscribers.clone())
+            .app_data(users.clone())
+            .app_data(idempotency.clone())
+            .app_data(delivery_queue.clone())
+            .app_data(tracking.clone())
+            .app_data(lists.clone())
             .app_data(email_client.clone())
+            .app_data(templates.clone())
+            .app_data(webhook_verifier.clone())
+            .app_data(base_url.clone())
+            .app_data(unsubscribe_signer.clone())
+            .app_data(tracking_signer.clone())
     })
     .listen(listener)?
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout.as_secs())
     .run();
 
     Ok(server)
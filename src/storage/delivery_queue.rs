@@ -0,0 +1,1014 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, InsertManyOptions, ReturnDocument};
+
+use super::{duplicate_key_count, MailingList};
+use crate::domain::{IssueStatus, SendTime};
+
+#[derive(Debug, Clone)]
+pub struct NewsletterIssue {
+    pub id: ObjectId,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub published_at: DateTime<Utc>,
+    /// How many deliveries were enqueued for the issue.
+    pub recipients: u64,
+    pub status: IssueStatus,
+    /// When a scheduled issue is due to go out; for a local send time, the
+    /// instant it is due in the earliest time zone.
+    pub send_at: Option<DateTime<Utc>>,
+    /// The wall-clock time the issue goes out in each subscriber's time zone.
+    pub send_at_local: Option<NaiveDateTime>,
+    /// The slug of the list the issue goes to; the main list if unset.
+    pub list: Option<String>,
+    /// The list's sender name and reply-to address when the issue was
+    /// published.
+    pub sender_name: Option<String>,
+    pub reply_to: Option<String>,
+    /// Set while a scheduler is queueing the deliveries of a claimed issue,
+    /// until when its claim holds. The deliveries are held back meanwhile.
+    pub enqueuing_until: Option<DateTime<Utc>>,
+}
+
+impl NewsletterIssue {
+    pub fn send_time(&self) -> Option<SendTime> {
+        match (self.send_at_local, self.send_at) {
+            (Some(local), _) => Some(SendTime::Local(local)),
+            (None, Some(at)) => Some(SendTime::At(at)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// One pending delivery of a newsletter issue to a single subscriber.
+#[derive(Debug, Clone)]
+pub struct DeliveryTask {
+    pub id: ObjectId,
+    pub newsletter_issue_id: ObjectId,
+    pub subscriber_id: ObjectId,
+    pub subscriber_email: String,
+    pub n_retries: u32,
+    pub execute_after: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait DeliveryQueue: Send + Sync {
+    /// Stores a new issue for `list`, or for the main list without one. With a
+    /// `send_time` it stays `scheduled` until
+    /// [`DeliveryQueue::claim_due_issue`] hands it out, otherwise it is
+    /// `published` straight away and claimed for `lease`, as if handed out.
+    async fn insert_issue(
+        &self,
+        list: Option<&MailingList>,
+        title: &str,
+        text_content: &str,
+        html_content: &str,
+        send_time: Option<SendTime>,
+        lease: chrono::Duration,
+    ) -> Result<ObjectId>;
+
+    async fn get_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>>;
+
+    /// Every issue still waiting for its send time, soonest first.
+    async fn scheduled_issues(&self) -> Result<Vec<NewsletterIssue>>;
+
+    /// Moves a scheduled issue to `send_time`. Returns `None` unless
+    /// `issue_id` is scheduled.
+    async fn reschedule_issue(
+        &self,
+        issue_id: ObjectId,
+        send_time: SendTime,
+    ) -> Result<Option<NewsletterIssue>>;
+
+    /// Returns `None` unless `issue_id` is scheduled.
+    async fn cancel_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>>;
+
+    /// Atomically marks the scheduled issue that has been due the longest as
+    /// published and returns it, so only one scheduler delivers it at a time.
+    /// The claim holds for `lease`; an issue whose deliveries are still not
+    /// fully queued by then is handed out again.
+    async fn claim_due_issue(&self, lease: chrono::Duration) -> Result<Option<NewsletterIssue>>;
+
+    /// Releases the deliveries of an issue claimed with
+    /// [`DeliveryQueue::claim_due_issue`] once all of them are queued.
+    async fn finish_enqueuing(&self, issue_id: ObjectId) -> Result<()>;
+
+    /// Adds one delivery task per recipient of `issue_id`, given as
+    /// `(subscriber_id, email)` pairs and due at `execute_after`, and counts
+    /// them towards the issue's recipients. Recipients who already have a
+    /// task for the issue are skipped, so a failed enqueue can be retried.
+    async fn enqueue(
+        &self,
+        issue_id: ObjectId,
+        recipients: Vec<(ObjectId, String)>,
+        execute_after: DateTime<Utc>,
+    ) -> Result<usize>;
+
+    /// Atomically claims the oldest task that is due and not leased by another
+    /// worker, along with up to `max - 1` more such tasks of the same issue,
+    /// hiding them from other workers for `lease`.
+    async fn dequeue(&self, lease: chrono::Duration, max: usize) -> Result<Vec<DeliveryTask>>;
+
+    /// Bumps the retry counter of `task_id` and makes it due again at `execute_after`.
+    async fn reschedule(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()>;
+
+    /// Makes `task_id` due again at `execute_after` without counting a retry.
+    async fn postpone(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()>;
+
+    async fn delete_task(&self, task_id: ObjectId) -> Result<()>;
+
+    /// Drops every delivery still waiting to go out to `subscriber_id`.
+    async fn delete_subscriber_tasks(&self, subscriber_id: ObjectId) -> Result<()>;
+}
+
+#[derive(serde::Deserialize)]
+struct IssueDocument {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: bson::DateTime,
+    #[serde(default)]
+    recipients: i64,
+    /// Issues stored before scheduling existed were all published.
+    #[serde(default = "published")]
+    status: IssueStatus,
+    #[serde(default)]
+    send_at: Option<bson::DateTime>,
+    #[serde(default)]
+    send_at_local: Option<NaiveDateTime>,
+    #[serde(default)]
+    list: Option<String>,
+    #[serde(default)]
+    sender_name: Option<String>,
+    #[serde(default)]
+    reply_to: Option<String>,
+    #[serde(default)]
+    enqueuing_until: Option<bson::DateTime>,
+}
+
+fn published() -> IssueStatus {
+    IssueStatus::Published
+}
+
+impl TryFrom<IssueDocument> for NewsletterIssue {
+    type Error = anyhow::Error;
+    fn try_from(document: IssueDocument) -> Result<Self> {
+        Ok(Self {
+            id: document.id,
+            title: document.title,
+            text_content: document.text_content,
+            html_content: document.html_content,
+            published_at: document.published_at.to_chrono(),
+            recipients: document.recipients.try_into()?,
+            status: document.status,
+            send_at: document.send_at.map(bson::DateTime::to_chrono),
+            send_at_local: document.send_at_local,
+            list: document.list,
+            sender_name: document.sender_name,
+            reply_to: document.reply_to,
+            enqueuing_until: document.enqueuing_until.map(bson::DateTime::to_chrono),
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct TaskDocument {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    newsletter_issue_id: ObjectId,
+    subscriber_id: ObjectId,
+    subscriber_email: String,
+    n_retries: i32,
+    execute_after: bson::DateTime,
+}
+
+impl TryFrom<TaskDocument> for DeliveryTask {
+    type Error = anyhow::Error;
+    fn try_from(document: TaskDocument) -> Result<Self> {
+        Ok(Self {
+            id: document.id,
+            newsletter_issue_id: document.newsletter_issue_id,
+            subscriber_id: document.subscriber_id,
+            subscriber_email: document.subscriber_email,
+            n_retries: document.n_retries.try_into()?,
+            execute_after: document.execute_after.to_chrono(),
+        })
+    }
+}
+
+/// Stores issues in `newsletter_issues` and their delivery tasks in
+/// `issue_delivery_queue`.
+pub struct MongoDeliveryQueue {
+    db_client: mongodb::Client,
+}
+
+impl MongoDeliveryQueue {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn issues(&self) -> mongodb::Collection<Document> {
+        self.db_client
+            .database("zero")
+            .collection("newsletter_issues")
+    }
+
+    /// Applies `update` to `issue_id` if it is still scheduled.
+    async fn update_scheduled_issue(
+        &self,
+        issue_id: ObjectId,
+        update: Document,
+    ) -> Result<Option<NewsletterIssue>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        self.issues()
+            .clone_with_type::<IssueDocument>()
+            .find_one_and_update(
+                doc! { "_id": issue_id, "status": IssueStatus::Scheduled.as_str() },
+                update,
+                Some(options),
+            )
+            .await
+            .context("Failed to update a scheduled newsletter issue")?
+            .map(NewsletterIssue::try_from)
+            .transpose()
+    }
+
+    fn queue(&self) -> mongodb::Collection<Document> {
+        self.db_client
+            .database("zero")
+            .collection("issue_delivery_queue")
+    }
+}
+
+#[async_trait]
+impl DeliveryQueue for MongoDeliveryQueue {
+    async fn insert_issue(
+        &self,
+        list: Option<&MailingList>,
+        title: &str,
+        text_content: &str,
+        html_content: &str,
+        send_time: Option<SendTime>,
+        lease: chrono::Duration,
+    ) -> Result<ObjectId> {
+        let now = Utc::now();
+        let (status, enqueuing_until) = match send_time {
+            Some(_) => (IssueStatus::Scheduled, None),
+            None => (IssueStatus::Published, Some(now + lease)),
+        };
+        let result = self
+            .issues()
+            .insert_one(
+                doc! {
+                    "title": title,
+                    "text_content": text_content,
+                    "html_content": html_content,
+                    "published_at": now,
+                    "recipients": 0_i64,
+                    "status": status.as_str(),
+                    "send_at": send_time.map(|send_time| send_time.earliest()),
+                    "send_at_local": bson::to_bson(&send_time.and_then(|t| t.local()))?,
+                    "list": list.map(|list| list.slug.as_str()),
+                    "sender_name": list.and_then(|list| list.sender_name.as_deref()),
+                    "reply_to": list.and_then(|list| list.reply_to.as_deref()),
+                    "enqueuing_until": enqueuing_until,
+                },
+                None,
+            )
+            .await
+            .context("Failed to store a newsletter issue")?;
+        result
+            .inserted_id
+            .as_object_id()
+            .ok_or_else(|| anyhow!("The newsletter issue was not assigned an ObjectId"))
+    }
+
+    async fn get_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>> {
+        let issue = self
+            .issues()
+            .clone_with_type::<IssueDocument>()
+            .find_one(doc! { "_id": issue_id }, None)
+            .await
+            .context("Failed to retrieve a newsletter issue")?;
+        issue.map(NewsletterIssue::try_from).transpose()
+    }
+
+    async fn scheduled_issues(&self) -> Result<Vec<NewsletterIssue>> {
+        let options = FindOptions::builder().sort(doc! { "send_at": 1 }).build();
+        let mut cursor = self
+            .issues()
+            .clone_with_type::<IssueDocument>()
+            .find(
+                doc! { "status": IssueStatus::Scheduled.as_str() },
+                Some(options),
+            )
+            .await
+            .context("Failed to list scheduled newsletter issues")?;
+        let mut issues = Vec::new();
+        while cursor.advance().await? {
+            issues.push(cursor.deserialize_current()?.try_into()?);
+        }
+        Ok(issues)
+    }
+
+    async fn reschedule_issue(
+        &self,
+        issue_id: ObjectId,
+        send_time: SendTime,
+    ) -> Result<Option<NewsletterIssue>> {
+        self.update_scheduled_issue(
+            issue_id,
+            doc! { "$set": {
+                "send_at": send_time.earliest(),
+                "send_at_local": bson::to_bson(&send_time.local())?,
+            } },
+        )
+        .await
+    }
+
+    async fn cancel_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>> {
+        self.update_scheduled_issue(
+            issue_id,
+            doc! { "$set": { "status": IssueStatus::Cancelled.as_str() } },
+        )
+        .await
+    }
+
+    async fn claim_due_issue(&self, lease: chrono::Duration) -> Result<Option<NewsletterIssue>> {
+        let now = Utc::now();
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! { "send_at": 1 })
+            .return_document(ReturnDocument::After)
+            .build();
+        self.issues()
+            .clone_with_type::<IssueDocument>()
+            .find_one_and_update(
+                doc! { "$or": [
+                    { "status": IssueStatus::Scheduled.as_str(), "send_at": { "$lte": now } },
+                    { "status": IssueStatus::Published.as_str(), "enqueuing_until": { "$lte": now } },
+                ] },
+                vec![doc! { "$set": {
+                    "status": IssueStatus::Published.as_str(),
+                    // A reclaimed issue keeps the time it was first published
+                    "published_at": { "$cond": [
+                        { "$eq": ["$status", IssueStatus::Scheduled.as_str()] },
+                        now,
+                        "$published_at",
+                    ] },
+                    "enqueuing_until": now + lease,
+                } }],
+                Some(options),
+            )
+            .await
+            .context("Failed to claim a due newsletter issue")?
+            .map(NewsletterIssue::try_from)
+            .transpose()
+    }
+
+    async fn finish_enqueuing(&self, issue_id: ObjectId) -> Result<()> {
+        self.issues()
+            .update_one(
+                doc! { "_id": issue_id },
+                doc! { "$unset": { "enqueuing_until": "" } },
+                None,
+            )
+            .await
+            .context("Failed to release the deliveries of a newsletter issue")?;
+        Ok(())
+    }
+
+    async fn enqueue(
+        &self,
+        issue_id: ObjectId,
+        recipients: Vec<(ObjectId, String)>,
+        execute_after: DateTime<Utc>,
+    ) -> Result<usize> {
+        if recipients.is_empty() {
+            return Ok(0);
+        }
+        let now = Utc::now();
+        let attempted = recipients.len();
+        let tasks = recipients.into_iter().map(|(subscriber_id, email)| {
+            doc! {
+                "newsletter_issue_id": issue_id,
+                "subscriber_id": subscriber_id,
+                "subscriber_email": email,
+                "n_retries": 0,
+                "execute_after": execute_after,
+                "lease_expires_at": now,
+            }
+        });
+        let options = InsertManyOptions::builder().ordered(false).build();
+        let enqueued = match self.queue().insert_many(tasks, Some(options)).await {
+            Ok(result) => result.inserted_ids.len(),
+            Err(e) => match duplicate_key_count(&e) {
+                Some(skipped) => attempted - skipped,
+                None => return Err(e).context("Failed to enqueue delivery tasks"),
+            },
+        };
+        self.issues()
+            .update_one(
+                doc! { "_id": issue_id },
+                doc! { "$inc": { "recipients": i64::try_from(enqueued)? } },
+                None,
+            )
+            .await
+            .context("Failed to count the recipients of a newsletter issue")?;
+        Ok(enqueued)
+    }
+
+    async fn dequeue(&self, lease: chrono::Duration, max: usize) -> Result<Vec<DeliveryTask>> {
+        let now = Utc::now();
+        let available = doc! {
+            "execute_after": { "$lte": now },
+            "lease_expires_at": { "$lte": now },
+        };
+        let lease_id = ObjectId::new();
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! { "execute_after": 1 })
+            .return_document(ReturnDocument::After)
+            .build();
+        let Some(first) = self
+            .queue()
+            .clone_with_type::<TaskDocument>()
+            .find_one_and_update(
+                available.clone(),
+                doc! { "$set": { "lease_expires_at": now + lease, "lease_id": lease_id } },
+                Some(options),
+            )
+            .await
+            .context("Failed to dequeue a delivery task")?
+        else {
+            return Ok(vec![]);
+        };
+        let mut tasks = vec![DeliveryTask::try_from(first)?];
+        if max <= 1 {
+            return Ok(tasks);
+        }
+
+        // Other workers may lease some of these in the meantime, so only the
+        // tasks that end up carrying this lease are handed out
+        let mut same_issue = available;
+        same_issue.insert("newsletter_issue_id", tasks[0].newsletter_issue_id);
+        let options = FindOptions::builder()
+            .sort(doc! { "execute_after": 1 })
+            .limit(i64::try_from(max - 1)?)
+            .projection(doc! { "_id": 1 })
+            .build();
+        let mut cursor = self
+            .queue()
+            .find(same_issue.clone(), Some(options))
+            .await
+            .context("Failed to dequeue delivery tasks")?;
+        let mut ids = Vec::new();
+        while cursor.advance().await? {
+            ids.push(cursor.current().get_object_id("_id")?);
+        }
+        same_issue.insert("_id", doc! { "$in": ids });
+        self.queue()
+            .update_many(
+                same_issue,
+                doc! { "$set": { "lease_expires_at": now + lease, "lease_id": lease_id } },
+                None,
+            )
+            .await
+            .context("Failed to dequeue delivery tasks")?;
+        let mut cursor = self
+            .queue()
+            .clone_with_type::<TaskDocument>()
+            .find(
+                doc! { "lease_id": lease_id, "_id": { "$ne": tasks[0].id } },
+                None,
+            )
+            .await
+            .context("Failed to dequeue delivery tasks")?;
+        while cursor.advance().await? {
+            tasks.push(cursor.deserialize_current()?.try_into()?);
+        }
+        Ok(tasks)
+    }
+
+    async fn reschedule(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()> {
+        self.queue()
+            .update_one(
+                doc! { "_id": task_id },
+                doc! {
+                    "$inc": { "n_retries": 1 },
+                    "$set": {
+                        "execute_after": execute_after,
+                        "lease_expires_at": Utc::now(),
+                    }
+                },
+                None,
+            )
+            .await
+            .context("Failed to reschedule a delivery task")?;
+        Ok(())
+    }
+
+    async fn postpone(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()> {
+        self.queue()
+            .update_one(
+                doc! { "_id": task_id },
+                doc! { "$set": {
+                    "execute_after": execute_after,
+                    "lease_expires_at": Utc::now(),
+                } },
+                None,
+            )
+            .await
+            .context("Failed to postpone a delivery task")?;
+        Ok(())
+    }
+
+    async fn delete_task(&self, task_id: ObjectId) -> Result<()> {
+        self.queue()
+            .delete_one(doc! { "_id": task_id }, None)
+            .await
+            .context("Failed to delete a completed delivery task")?;
+        Ok(())
+    }
+
+    async fn delete_subscriber_tasks(&self, subscriber_id: ObjectId) -> Result<()> {
+        self.queue()
+            .delete_many(doc! { "subscriber_id": subscriber_id }, None)
+            .await
+            .context("Failed to delete the delivery tasks of a subscriber")?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    issues: HashMap<ObjectId, NewsletterIssue>,
+    /// Tasks paired with the instant their current lease runs out.
+    tasks: Vec<(DeliveryTask, DateTime<Utc>)>,
+}
+
+#[derive(Default)]
+pub struct InMemoryDeliveryQueue {
+    state: Mutex<QueueState>,
+}
+
+impl InMemoryDeliveryQueue {
+    fn update_scheduled_issue(
+        &self,
+        issue_id: ObjectId,
+        update: impl FnOnce(&mut NewsletterIssue),
+    ) -> Option<NewsletterIssue> {
+        let mut state = self.state.lock().unwrap();
+        let issue = state
+            .issues
+            .get_mut(&issue_id)
+            .filter(|issue| issue.status == IssueStatus::Scheduled)?;
+        update(issue);
+        Some(issue.clone())
+    }
+
+    /// Every task still waiting to be delivered, leased or not.
+    pub fn pending_tasks(&self) -> Vec<DeliveryTask> {
+        let state = self.state.lock().unwrap();
+        state.tasks.iter().map(|(task, _)| task.clone()).collect()
+    }
+}
+
+#[async_trait]
+impl DeliveryQueue for InMemoryDeliveryQueue {
+    async fn insert_issue(
+        &self,
+        list: Option<&MailingList>,
+        title: &str,
+        text_content: &str,
+        html_content: &str,
+        send_time: Option<SendTime>,
+        lease: chrono::Duration,
+    ) -> Result<ObjectId> {
+        let now = Utc::now();
+        let issue = NewsletterIssue {
+            id: ObjectId::new(),
+            title: title.to_owned(),
+            text_content: text_content.to_owned(),
+            html_content: html_content.to_owned(),
+            published_at: now,
+            recipients: 0,
+            status: match send_time {
+                Some(_) => IssueStatus::Scheduled,
+                None => IssueStatus::Published,
+            },
+            send_at: send_time.map(|send_time| send_time.earliest()),
+            send_at_local: send_time.and_then(|send_time| send_time.local()),
+            list: list.map(|list| list.slug.clone()),
+            sender_name: list.and_then(|list| list.sender_name.clone()),
+            reply_to: list.and_then(|list| list.reply_to.clone()),
+            enqueuing_until: send_time.is_none().then(|| now + lease),
+        };
+        let issue_id = issue.id;
+        self.state.lock().unwrap().issues.insert(issue_id, issue);
+        Ok(issue_id)
+    }
+
+    async fn get_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>> {
+        Ok(self.state.lock().unwrap().issues.get(&issue_id).cloned())
+    }
+
+    async fn scheduled_issues(&self) -> Result<Vec<NewsletterIssue>> {
+        let state = self.state.lock().unwrap();
+        let mut issues: Vec<_> = state
+            .issues
+            .values()
+            .filter(|issue| issue.status == IssueStatus::Scheduled)
+            .cloned()
+            .collect();
+        issues.sort_by_key(|issue| issue.send_at);
+        Ok(issues)
+    }
+
+    async fn reschedule_issue(
+        &self,
+        issue_id: ObjectId,
+        send_time: SendTime,
+    ) -> Result<Option<NewsletterIssue>> {
+        Ok(self.update_scheduled_issue(issue_id, |issue| {
+            issue.send_at = Some(send_time.earliest());
+            issue.send_at_local = send_time.local();
+        }))
+    }
+
+    async fn cancel_issue(&self, issue_id: ObjectId) -> Result<Option<NewsletterIssue>> {
+        Ok(self.update_scheduled_issue(issue_id, |issue| issue.status = IssueStatus::Cancelled))
+    }
+
+    async fn claim_due_issue(&self, lease: chrono::Duration) -> Result<Option<NewsletterIssue>> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+        let due = state
+            .issues
+            .values_mut()
+            .filter(|issue| match issue.status {
+                IssueStatus::Scheduled => issue.send_at.is_some_and(|send_at| send_at <= now),
+                IssueStatus::Published => issue.enqueuing_until.is_some_and(|until| until <= now),
+                IssueStatus::Cancelled => false,
+            })
+            .min_by_key(|issue| issue.send_at);
+        Ok(due.map(|issue| {
+            if issue.status == IssueStatus::Scheduled {
+                issue.status = IssueStatus::Published;
+                issue.published_at = now;
+            }
+            issue.enqueuing_until = Some(now + lease);
+            issue.clone()
+        }))
+    }
+
+    async fn finish_enqueuing(&self, issue_id: ObjectId) -> Result<()> {
+        if let Some(issue) = self.state.lock().unwrap().issues.get_mut(&issue_id) {
+            issue.enqueuing_until = None;
+        }
+        Ok(())
+    }
+
+    async fn enqueue(
+        &self,
+        issue_id: ObjectId,
+        recipients: Vec<(ObjectId, String)>,
+        execute_after: DateTime<Utc>,
+    ) -> Result<usize> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+        let mut enqueued = 0;
+        for (subscriber_id, email) in recipients {
+            let queued = state.tasks.iter().any(|(task, _)| {
+                task.newsletter_issue_id == issue_id && task.subscriber_id == subscriber_id
+            });
+            if queued {
+                continue;
+            }
+            let task = DeliveryTask {
+                id: ObjectId::new(),
+                newsletter_issue_id: issue_id,
+                subscriber_id,
+                subscriber_email: email,
+                n_retries: 0,
+                execute_after,
+            };
+            state.tasks.push((task, now));
+            enqueued += 1;
+        }
+        if let Some(issue) = state.issues.get_mut(&issue_id) {
+            issue.recipients += enqueued as u64;
+        }
+        Ok(enqueued)
+    }
+
+    async fn dequeue(&self, lease: chrono::Duration, max: usize) -> Result<Vec<DeliveryTask>> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+        let mut available: Vec<_> = state
+            .tasks
+            .iter_mut()
+            .filter(|(task, lease_expires_at)| {
+                task.execute_after <= now && *lease_expires_at <= now
+            })
+            .collect();
+        available.sort_by_key(|(task, _)| task.execute_after);
+        let Some(issue_id) = available.first().map(|(task, _)| task.newsletter_issue_id) else {
+            return Ok(vec![]);
+        };
+        Ok(available
+            .into_iter()
+            .filter(|(task, _)| task.newsletter_issue_id == issue_id)
+            .take(max.max(1))
+            .map(|(task, lease_expires_at)| {
+                *lease_expires_at = now + lease;
+                task.clone()
+            })
+            .collect())
+    }
+
+    async fn reschedule(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((task, lease_expires_at)) =
+            state.tasks.iter_mut().find(|(task, _)| task.id == task_id)
+        {
+            task.n_retries += 1;
+            task.execute_after = execute_after;
+            *lease_expires_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn postpone(&self, task_id: ObjectId, execute_after: DateTime<Utc>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((task, lease_expires_at)) =
+            state.tasks.iter_mut().find(|(task, _)| task.id == task_id)
+        {
+            task.execute_after = execute_after;
+            *lease_expires_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn delete_task(&self, task_id: ObjectId) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.tasks.retain(|(task, _)| task.id != task_id);
+        Ok(())
+    }
+
+    async fn delete_subscriber_tasks(&self, subscriber_id: ObjectId) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .tasks
+            .retain(|(task, _)| task.subscriber_id != subscriber_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeliveryQueue, InMemoryDeliveryQueue};
+    use crate::domain::{IssueStatus, SendTime};
+    use claim::assert_none;
+    use mongodb::bson::oid::ObjectId;
+
+    const LEASE: chrono::Duration = chrono::Duration::minutes(5);
+
+    #[tokio::test]
+    async fn leased_tasks_are_not_handed_out_twice() {
+        let queue = InMemoryDeliveryQueue::default();
+        let issue_id = queue
+            .insert_issue(None, "title", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        queue
+            .enqueue(
+                issue_id,
+                vec![(ObjectId::new(), "a@example.com".into())],
+                chrono::Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(queue.dequeue(LEASE, 1).await.unwrap().len(), 1);
+        assert!(queue.dequeue(LEASE, 1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn tasks_are_dequeued_in_batches_of_a_single_issue() {
+        let queue = InMemoryDeliveryQueue::default();
+        let now = chrono::Utc::now();
+        let recipients = |n| {
+            (0..n)
+                .map(|i| (ObjectId::new(), format!("{}@example.com", i)))
+                .collect()
+        };
+        let older = queue
+            .insert_issue(None, "older", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        let newer = queue
+            .insert_issue(None, "newer", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        queue
+            .enqueue(older, recipients(3), now - chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        queue.enqueue(newer, recipients(2), now).await.unwrap();
+
+        let first = queue.dequeue(LEASE, 2).await.unwrap();
+        let second = queue.dequeue(LEASE, 2).await.unwrap();
+        let third = queue.dequeue(LEASE, 5).await.unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert!(first.iter().all(|task| task.newsletter_issue_id == older));
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].newsletter_issue_id, older);
+        assert_eq!(third.len(), 2);
+        assert!(third.iter().all(|task| task.newsletter_issue_id == newer));
+        assert!(queue.dequeue(LEASE, 5).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn scheduled_issues_are_claimed_once_they_are_due() {
+        let queue = InMemoryDeliveryQueue::default();
+        let now = chrono::Utc::now();
+        let later = queue
+            .insert_issue(
+                None,
+                "later",
+                "text",
+                "html",
+                Some(SendTime::At(now + chrono::Duration::hours(1))),
+                LEASE,
+            )
+            .await
+            .unwrap();
+        let due = queue
+            .insert_issue(None, "due", "text", "html", Some(SendTime::At(now)), LEASE)
+            .await
+            .unwrap();
+
+        let claimed = queue.claim_due_issue(LEASE).await.unwrap().unwrap();
+
+        assert_eq!(claimed.id, due);
+        assert_eq!(claimed.status, IssueStatus::Published);
+        assert_none!(queue.claim_due_issue(LEASE).await.unwrap());
+        let scheduled = queue.scheduled_issues().await.unwrap();
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].id, later);
+    }
+
+    #[tokio::test]
+    async fn half_enqueued_issues_are_claimed_again_once_their_lease_runs_out() {
+        let queue = InMemoryDeliveryQueue::default();
+        let issue_id = queue
+            .insert_issue(
+                None,
+                "title",
+                "text",
+                "html",
+                Some(SendTime::At(chrono::Utc::now())),
+                LEASE,
+            )
+            .await
+            .unwrap();
+
+        let claimed = queue
+            .claim_due_issue(chrono::Duration::zero())
+            .await
+            .unwrap()
+            .unwrap();
+        let reclaimed = queue.claim_due_issue(LEASE).await.unwrap().unwrap();
+
+        assert_eq!(reclaimed.id, issue_id);
+        assert_eq!(reclaimed.published_at, claimed.published_at);
+        assert_none!(queue.claim_due_issue(LEASE).await.unwrap());
+        queue.finish_enqueuing(issue_id).await.unwrap();
+        let issue = queue.get_issue(issue_id).await.unwrap().unwrap();
+        assert_none!(issue.enqueuing_until);
+    }
+
+    #[tokio::test]
+    async fn enqueueing_a_recipient_twice_queues_one_delivery() {
+        let queue = InMemoryDeliveryQueue::default();
+        let issue_id = queue
+            .insert_issue(None, "title", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        let first = (ObjectId::new(), "a@example.com".to_string());
+        let second = (ObjectId::new(), "b@example.com".to_string());
+        let now = chrono::Utc::now();
+
+        queue
+            .enqueue(issue_id, vec![first.clone()], now)
+            .await
+            .unwrap();
+        let enqueued = queue
+            .enqueue(issue_id, vec![first, second], now)
+            .await
+            .unwrap();
+
+        assert_eq!(enqueued, 1);
+        assert_eq!(queue.pending_tasks().len(), 2);
+        let issue = queue.get_issue(issue_id).await.unwrap().unwrap();
+        assert_eq!(issue.recipients, 2);
+    }
+
+    #[tokio::test]
+    async fn only_scheduled_issues_can_be_rescheduled_or_cancelled() {
+        let queue = InMemoryDeliveryQueue::default();
+        let published = queue
+            .insert_issue(None, "title", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        let scheduled = queue
+            .insert_issue(
+                None,
+                "title",
+                "text",
+                "html",
+                Some(SendTime::At(chrono::Utc::now())),
+                LEASE,
+            )
+            .await
+            .unwrap();
+        let tomorrow = chrono::Utc::now() + chrono::Duration::days(1);
+
+        assert_none!(queue
+            .reschedule_issue(published, SendTime::At(tomorrow))
+            .await
+            .unwrap());
+        assert_none!(queue.cancel_issue(published).await.unwrap());
+        let rescheduled = queue
+            .reschedule_issue(scheduled, SendTime::At(tomorrow))
+            .await
+            .unwrap();
+        assert_eq!(rescheduled.unwrap().send_at, Some(tomorrow));
+        let cancelled = queue.cancel_issue(scheduled).await.unwrap();
+        assert_eq!(cancelled.unwrap().status, IssueStatus::Cancelled);
+        assert_none!(queue.claim_due_issue(LEASE).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn rescheduled_tasks_wait_until_they_are_due() {
+        let queue = InMemoryDeliveryQueue::default();
+        let issue_id = queue
+            .insert_issue(None, "title", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        queue
+            .enqueue(
+                issue_id,
+                vec![(ObjectId::new(), "a@example.com".into())],
+                chrono::Utc::now(),
+            )
+            .await
+            .unwrap();
+        let task = queue.dequeue(LEASE, 1).await.unwrap().remove(0);
+
+        queue
+            .reschedule(task.id, chrono::Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+
+        assert!(queue.dequeue(LEASE, 1).await.unwrap().is_empty());
+        assert_eq!(queue.pending_tasks()[0].n_retries, 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_subscriber_s_tasks_spares_other_subscribers() {
+        let queue = InMemoryDeliveryQueue::default();
+        let issue_id = queue
+            .insert_issue(None, "title", "text", "html", None, LEASE)
+            .await
+            .unwrap();
+        let (deleted, kept) = (ObjectId::new(), ObjectId::new());
+        queue
+            .enqueue(
+                issue_id,
+                vec![
+                    (deleted, "a@example.com".into()),
+                    (kept, "b@example.com".into()),
+                ],
+                chrono::Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        queue.delete_subscriber_tasks(deleted).await.unwrap();
+
+        let pending = queue.pending_tasks();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].subscriber_id, kept);
+    }
+}
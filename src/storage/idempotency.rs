@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::spec::BinarySubtype;
+use mongodb::bson::{doc, oid::ObjectId, Binary, Document};
+
+use super::is_duplicate_key_error;
+use crate::idempotency::IdempotencyKey;
+
+/// How long a stored response can be replayed for.
+const RETENTION: chrono::Duration = chrono::Duration::hours(24);
+
+/// An HTTP response as it was first returned for an idempotency key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Claims `idempotency_key` for `user_id`, or takes over a released claim
+    /// that recorded an issue. Returns `false` if another request holds it.
+    async fn try_claim(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<bool>;
+
+    async fn get_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>>;
+
+    async fn save_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        response: &SavedResponse,
+    ) -> Result<()>;
+
+    /// Records the newsletter issue published under a claimed key, so that a
+    /// retry after a failure resumes it instead of publishing another one.
+    async fn save_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        issue_id: ObjectId,
+    ) -> Result<()>;
+
+    async fn get_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<ObjectId>>;
+
+    /// Drops a claim that never had a response saved against it. A claim that
+    /// recorded an issue is kept for the retry to find, but can be claimed
+    /// again.
+    async fn release(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<()>;
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HeaderPair {
+    name: String,
+    value: Binary,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ResponseDocument {
+    status_code: i32,
+    headers: Vec<HeaderPair>,
+    body: Binary,
+}
+
+fn binary(bytes: Vec<u8>) -> Binary {
+    Binary {
+        subtype: BinarySubtype::Generic,
+        bytes,
+    }
+}
+
+impl From<&SavedResponse> for ResponseDocument {
+    fn from(response: &SavedResponse) -> Self {
+        Self {
+            status_code: response.status_code.into(),
+            headers: response
+                .headers
+                .iter()
+                .map(|(name, value)| HeaderPair {
+                    name: name.clone(),
+                    value: binary(value.clone()),
+                })
+                .collect(),
+            body: binary(response.body.clone()),
+        }
+    }
+}
+
+impl TryFrom<ResponseDocument> for SavedResponse {
+    type Error = anyhow::Error;
+    fn try_from(document: ResponseDocument) -> Result<Self> {
+        Ok(Self {
+            status_code: document.status_code.try_into()?,
+            headers: document
+                .headers
+                .into_iter()
+                .map(|HeaderPair { name, value }| (name, value.bytes))
+                .collect(),
+            body: document.body.bytes,
+        })
+    }
+}
+
+/// Stores claimed keys and their responses in the `idempotency` collection.
+/// Records are removed by a TTL index on `expires_at`.
+pub struct MongoIdempotencyRepository {
+    db_client: mongodb::Client,
+}
+
+impl MongoIdempotencyRepository {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn collection(&self) -> mongodb::Collection<Document> {
+        self.db_client.database("zero").collection("idempotency")
+    }
+}
+
+fn record_id(user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Document {
+    doc! {
+        "user_id": user_id,
+        "idempotency_key": idempotency_key.as_ref(),
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for MongoIdempotencyRepository {
+    async fn try_claim(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<bool> {
+        let inserted = self
+            .collection()
+            .insert_one(
+                doc! {
+                    "_id": record_id(user_id, idempotency_key),
+                    "created_at": Utc::now(),
+                    "expires_at": Utc::now() + RETENTION,
+                },
+                None,
+            )
+            .await;
+        match inserted {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key_error(&e) => {
+                let reclaimed = self
+                    .collection()
+                    .update_one(
+                        doc! { "_id": record_id(user_id, idempotency_key), "released": true },
+                        doc! { "$unset": { "released": "" } },
+                        None,
+                    )
+                    .await?;
+                Ok(reclaimed.modified_count > 0)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>> {
+        let record = self
+            .collection()
+            .find_one(doc! { "_id": record_id(user_id, idempotency_key) }, None)
+            .await?;
+        let Some(saved) = record.and_then(|r| r.get_document("response").ok().cloned()) else {
+            return Ok(None);
+        };
+        let saved: ResponseDocument = mongodb::bson::from_document(saved)?;
+        Ok(Some(saved.try_into()?))
+    }
+
+    async fn save_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        response: &SavedResponse,
+    ) -> Result<()> {
+        let response = mongodb::bson::to_document(&ResponseDocument::from(response))?;
+        self.collection()
+            .update_one(
+                doc! { "_id": record_id(user_id, idempotency_key) },
+                doc! { "$set": { "response": response } },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn save_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        issue_id: ObjectId,
+    ) -> Result<()> {
+        self.collection()
+            .update_one(
+                doc! { "_id": record_id(user_id, idempotency_key) },
+                doc! { "$set": { "issue_id": issue_id } },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<ObjectId>> {
+        let record = self
+            .collection()
+            .find_one(doc! { "_id": record_id(user_id, idempotency_key) }, None)
+            .await?;
+        Ok(record.and_then(|r| r.get_object_id("issue_id").ok()))
+    }
+
+    async fn release(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<()> {
+        self.collection()
+            .delete_one(
+                doc! {
+                    "_id": record_id(user_id, idempotency_key),
+                    "response": { "$exists": false },
+                    "issue_id": { "$exists": false },
+                },
+                None,
+            )
+            .await?;
+        self.collection()
+            .update_one(
+                doc! {
+                    "_id": record_id(user_id, idempotency_key),
+                    "response": { "$exists": false },
+                },
+                doc! { "$set": { "released": true } },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+struct Record {
+    expires_at: DateTime<Utc>,
+    response: Option<SavedResponse>,
+    issue_id: Option<ObjectId>,
+    released: bool,
+}
+
+#[derive(Default)]
+pub struct InMemoryIdempotencyRepository {
+    records: Mutex<HashMap<(ObjectId, String), Record>>,
+}
+
+fn record_key(user_id: ObjectId, idempotency_key: &IdempotencyKey) -> (ObjectId, String) {
+    (user_id, idempotency_key.as_ref().to_owned())
+}
+
+#[async_trait]
+impl IdempotencyRepository for InMemoryIdempotencyRepository {
+    async fn try_claim(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<bool> {
+        let now = Utc::now();
+        let mut records = self.records.lock().unwrap();
+        records.retain(|_, record| record.expires_at > now);
+        let key = record_key(user_id, idempotency_key);
+        if let Some(record) = records.get_mut(&key) {
+            let reclaimed = record.released;
+            record.released = false;
+            return Ok(reclaimed);
+        }
+        records.insert(
+            key,
+            Record {
+                expires_at: now + RETENTION,
+                response: None,
+                issue_id: None,
+                released: false,
+            },
+        );
+        Ok(true)
+    }
+
+    async fn get_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<SavedResponse>> {
+        let records = self.records.lock().unwrap();
+        Ok(records
+            .get(&record_key(user_id, idempotency_key))
+            .and_then(|record| record.response.clone()))
+    }
+
+    async fn save_response(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        response: &SavedResponse,
+    ) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(record) = records.get_mut(&record_key(user_id, idempotency_key)) {
+            record.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn save_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+        issue_id: ObjectId,
+    ) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(record) = records.get_mut(&record_key(user_id, idempotency_key)) {
+            record.issue_id = Some(issue_id);
+        }
+        Ok(())
+    }
+
+    async fn get_issue(
+        &self,
+        user_id: ObjectId,
+        idempotency_key: &IdempotencyKey,
+    ) -> Result<Option<ObjectId>> {
+        let records = self.records.lock().unwrap();
+        Ok(records
+            .get(&record_key(user_id, idempotency_key))
+            .and_then(|record| record.issue_id))
+    }
+
+    async fn release(&self, user_id: ObjectId, idempotency_key: &IdempotencyKey) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        let key = record_key(user_id, idempotency_key);
+        match records.get_mut(&key) {
+            Some(record) if record.response.is_some() => {}
+            Some(record) if record.issue_id.is_some() => record.released = true,
+            Some(_) => {
+                records.remove(&key);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdempotencyRepository, InMemoryIdempotencyRepository, SavedResponse};
+    use crate::idempotency::IdempotencyKey;
+    use claim::assert_none;
+    use mongodb::bson::oid::ObjectId;
+
+    fn key() -> IdempotencyKey {
+        IdempotencyKey::try_from("key".to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_key_can_only_be_claimed_once() {
+        let repository = InMemoryIdempotencyRepository::default();
+        let user_id = ObjectId::new();
+
+        assert!(repository.try_claim(user_id, &key()).await.unwrap());
+        assert!(!repository.try_claim(user_id, &key()).await.unwrap());
+        assert!(repository.try_claim(ObjectId::new(), &key()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn released_keys_can_be_claimed_again() {
+        let repository = InMemoryIdempotencyRepository::default();
+        let user_id = ObjectId::new();
+        repository.try_claim(user_id, &key()).await.unwrap();
+
+        repository.release(user_id, &key()).await.unwrap();
+
+        assert!(repository.try_claim(user_id, &key()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn released_keys_keep_the_issue_they_recorded() {
+        let repository = InMemoryIdempotencyRepository::default();
+        let user_id = ObjectId::new();
+        let issue_id = ObjectId::new();
+        repository.try_claim(user_id, &key()).await.unwrap();
+        repository
+            .save_issue(user_id, &key(), issue_id)
+            .await
+            .unwrap();
+
+        repository.release(user_id, &key()).await.unwrap();
+
+        assert!(repository.try_claim(user_id, &key()).await.unwrap());
+        assert!(!repository.try_claim(user_id, &key()).await.unwrap());
+        assert_eq!(
+            repository.get_issue(user_id, &key()).await.unwrap(),
+            Some(issue_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn keys_with_a_saved_response_are_not_released() {
+        let repository = InMemoryIdempotencyRepository::default();
+        let user_id = ObjectId::new();
+        let response = SavedResponse {
+            status_code: 202,
+            headers: vec![("content-type".into(), b"application/json".to_vec())],
+            body: b"{}".to_vec(),
+        };
+        repository.try_claim(user_id, &key()).await.unwrap();
+        assert_none!(repository.get_response(user_id, &key()).await.unwrap());
+        repository
+            .save_response(user_id, &key(), &response)
+            .await
+            .unwrap();
+
+        repository.release(user_id, &key()).await.unwrap();
+
+        assert_eq!(
+            repository.get_response(user_id, &key()).await.unwrap(),
+            Some(response)
+        );
+    }
+}
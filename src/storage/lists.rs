@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{self, doc};
+
+use super::is_duplicate_key_error;
+use crate::domain::{ListSlug, SubscriberEmail};
+
+/// A mailing list subscribers can join and issues can be sent to.
+#[derive(Debug, Clone)]
+pub struct MailingList {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    /// Replaces the configured sender name on the list's emails.
+    pub sender_name: Option<String>,
+    /// Replaces the configured reply-to address on the list's emails.
+    pub reply_to: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+pub struct NewMailingList {
+    pub slug: ListSlug,
+    pub name: String,
+    pub description: String,
+    pub sender_name: Option<String>,
+    pub reply_to: Option<SubscriberEmail>,
+}
+
+#[async_trait]
+pub trait ListRepository: Send + Sync {
+    /// Stores `new_list`, returning `None` if its slug is taken.
+    async fn insert(&self, new_list: &NewMailingList) -> Result<Option<MailingList>>;
+
+    async fn find(&self, slug: &str) -> Result<Option<MailingList>>;
+
+    /// Every list, ordered by slug.
+    async fn all(&self) -> Result<Vec<MailingList>>;
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ListDocument {
+    #[serde(rename = "_id")]
+    slug: String,
+    name: String,
+    description: String,
+    sender_name: Option<String>,
+    reply_to: Option<String>,
+    created: bson::DateTime,
+}
+
+impl From<ListDocument> for MailingList {
+    fn from(document: ListDocument) -> Self {
+        Self {
+            slug: document.slug,
+            name: document.name,
+            description: document.description,
+            sender_name: document.sender_name,
+            reply_to: document.reply_to,
+            created: document.created.to_chrono(),
+        }
+    }
+}
+
+impl From<&NewMailingList> for MailingList {
+    fn from(new_list: &NewMailingList) -> Self {
+        Self {
+            slug: new_list.slug.as_ref().to_owned(),
+            name: new_list.name.clone(),
+            description: new_list.description.clone(),
+            sender_name: new_list.sender_name.clone(),
+            reply_to: new_list
+                .reply_to
+                .as_ref()
+                .map(|reply_to| reply_to.as_ref().to_owned()),
+            created: Utc::now(),
+        }
+    }
+}
+
+/// Stores lists in the `lists` collection, keyed by their slug.
+pub struct MongoListRepository {
+    db_client: mongodb::Client,
+}
+
+impl MongoListRepository {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn lists(&self) -> mongodb::Collection<ListDocument> {
+        self.db_client.database("zero").collection("lists")
+    }
+}
+
+#[async_trait]
+impl ListRepository for MongoListRepository {
+    async fn insert(&self, new_list: &NewMailingList) -> Result<Option<MailingList>> {
+        let list = MailingList::from(new_list);
+        let document = ListDocument {
+            slug: list.slug.clone(),
+            name: list.name.clone(),
+            description: list.description.clone(),
+            sender_name: list.sender_name.clone(),
+            reply_to: list.reply_to.clone(),
+            created: list.created.into(),
+        };
+        match self.lists().insert_one(document, None).await {
+            Ok(_) => Ok(Some(list)),
+            Err(e) if is_duplicate_key_error(&e) => Ok(None),
+            Err(e) => Err(e).context("Failed to store a mailing list"),
+        }
+    }
+
+    async fn find(&self, slug: &str) -> Result<Option<MailingList>> {
+        let list = self
+            .lists()
+            .find_one(doc! { "_id": slug }, None)
+            .await
+            .context("Failed to retrieve a mailing list")?;
+        Ok(list.map(MailingList::from))
+    }
+
+    async fn all(&self) -> Result<Vec<MailingList>> {
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .build();
+        let mut cursor = self
+            .lists()
+            .find(None, Some(options))
+            .await
+            .context("Failed to list mailing lists")?;
+        let mut lists = Vec::new();
+        while cursor.advance().await? {
+            lists.push(cursor.deserialize_current()?.into());
+        }
+        Ok(lists)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryListRepository {
+    lists: Mutex<BTreeMap<String, MailingList>>,
+}
+
+#[async_trait]
+impl ListRepository for InMemoryListRepository {
+    async fn insert(&self, new_list: &NewMailingList) -> Result<Option<MailingList>> {
+        let mut lists = self.lists.lock().unwrap();
+        if lists.contains_key(new_list.slug.as_ref()) {
+            return Ok(None);
+        }
+        let list = MailingList::from(new_list);
+        lists.insert(list.slug.clone(), list.clone());
+        Ok(Some(list))
+    }
+
+    async fn find(&self, slug: &str) -> Result<Option<MailingList>> {
+        Ok(self.lists.lock().unwrap().get(slug).cloned())
+    }
+
+    async fn all(&self) -> Result<Vec<MailingList>> {
+        Ok(self.lists.lock().unwrap().values().cloned().collect())
+    }
+}
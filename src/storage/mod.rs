@@ -0,0 +1,87 @@
+mod delivery_queue;
+mod idempotency;
+mod lists;
+mod subscribers;
+mod tracking;
+mod users;
+
+use std::sync::Arc;
+
+use mongodb::error::{BulkWriteFailure, ErrorKind, WriteFailure};
+
+pub use delivery_queue::{
+    DeliveryQueue, DeliveryTask, InMemoryDeliveryQueue, MongoDeliveryQueue, NewsletterIssue,
+};
+pub use idempotency::{
+    IdempotencyRepository, InMemoryIdempotencyRepository, MongoIdempotencyRepository, SavedResponse,
+};
+pub use lists::{
+    InMemoryListRepository, ListRepository, MailingList, MongoListRepository, NewMailingList,
+};
+pub use subscribers::{
+    InMemorySubscriberRepository, ListMembership, MongoSubscriberRepository, Subscriber,
+    SubscriberFilter, SubscriberRepository,
+};
+pub use tracking::{
+    EventCount, InMemoryTrackingRepository, IssueEngagement, MongoTrackingRepository,
+    TrackingEvent, TrackingRepository,
+};
+pub use users::{InMemoryUserRepository, MongoUserRepository, User, UserRepository};
+
+/// The repositories the application is wired with.
+#[derive(Clone)]
+pub struct Storage {
+    pub subscribers: Arc<dyn SubscriberRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub idempotency: Arc<dyn IdempotencyRepository>,
+    pub delivery_queue: Arc<dyn DeliveryQueue>,
+    pub tracking: Arc<dyn TrackingRepository>,
+    pub lists: Arc<dyn ListRepository>,
+}
+
+impl Storage {
+    pub fn mongo(db_client: mongodb::Client) -> Self {
+        Self {
+            subscribers: Arc::new(MongoSubscriberRepository::new(db_client.clone())),
+            users: Arc::new(MongoUserRepository::new(db_client.clone())),
+            idempotency: Arc::new(MongoIdempotencyRepository::new(db_client.clone())),
+            delivery_queue: Arc::new(MongoDeliveryQueue::new(db_client.clone())),
+            tracking: Arc::new(MongoTrackingRepository::new(db_client.clone())),
+            lists: Arc::new(MongoListRepository::new(db_client)),
+        }
+    }
+
+    /// Keeps everything in process memory. Nothing survives a restart.
+    pub fn in_memory() -> Self {
+        Self {
+            subscribers: Arc::new(InMemorySubscriberRepository::default()),
+            users: Arc::new(InMemoryUserRepository::default()),
+            idempotency: Arc::new(InMemoryIdempotencyRepository::default()),
+            delivery_queue: Arc::new(InMemoryDeliveryQueue::default()),
+            tracking: Arc::new(InMemoryTrackingRepository::default()),
+            lists: Arc::new(InMemoryListRepository::default()),
+        }
+    }
+}
+
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key_error(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) if write_error.code == DUPLICATE_KEY
+    )
+}
+
+/// How many documents an unordered `insert_many` skipped because they were
+/// already stored, if that is all that went wrong.
+fn duplicate_key_count(e: &mongodb::error::Error) -> Option<usize> {
+    match e.kind.as_ref() {
+        ErrorKind::BulkWrite(BulkWriteFailure {
+            write_errors: Some(write_errors),
+            write_concern_error: None,
+            ..
+        }) if write_errors.iter().all(|e| e.code == DUPLICATE_KEY) => Some(write_errors.len()),
+        _ => None,
+    }
+}
@@ -0,0 +1,938 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::domain::{NewSubscriber, SubscriberName, SubscriberStatus, SubscriberTimeZone};
+
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    pub id: ObjectId,
+    pub email: String,
+    pub name: String,
+    /// Their status on the main list. Bounced and complained addresses get
+    /// no issues from any list.
+    pub status: SubscriberStatus,
+    pub created: DateTime<Utc>,
+    /// An IANA time zone name, validated when it was stored.
+    pub time_zone: Option<String>,
+    /// The mailing lists, besides the main list, they joined.
+    pub lists: Vec<ListMembership>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ListMembership {
+    /// The slug of the list.
+    pub list: String,
+    pub status: SubscriberStatus,
+}
+
+impl Subscriber {
+    pub fn membership(&self, list: &str) -> Option<&ListMembership> {
+        self.lists.iter().find(|membership| membership.list == list)
+    }
+
+    /// Whether they still get the issues sent to `list`, or to the main list
+    /// without one.
+    pub fn receives(&self, list: Option<&str>) -> bool {
+        let status = match list {
+            Some(list) => self.membership(list).map(|membership| membership.status),
+            None => Some(self.status),
+        };
+        !self.status.is_suppressed() && status == Some(SubscriberStatus::Confirmed)
+    }
+}
+
+/// Criteria for [`SubscriberRepository::page`]. Unset fields match everything.
+#[derive(Debug, Default, Clone)]
+pub struct SubscriberFilter {
+    pub status: Option<SubscriberStatus>,
+    pub email: Option<String>,
+}
+
+#[async_trait]
+pub trait SubscriberRepository: Send + Sync {
+    /// Stores `new_subscriber` as pending confirmation. If the email address is
+    /// already known, the existing subscriber is returned untouched. The flag
+    /// tells whether the subscriber was created.
+    async fn insert(&self, new_subscriber: &NewSubscriber) -> Result<(Subscriber, bool)>;
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Subscriber>>;
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<Subscriber>>;
+
+    /// Moves a subscriber to `status` if their current status allows it, see
+    /// [`SubscriberStatus::can_become`]. Returns whether the status changed.
+    async fn update_status(&self, id: ObjectId, status: SubscriberStatus) -> Result<bool>;
+
+    /// Lists subscribers, optionally restricted to a single status.
+    async fn list(&self, status: Option<SubscriberStatus>) -> Result<Vec<Subscriber>>;
+
+    /// Adds `id` to `list` as pending confirmation, unless they already joined
+    /// it. Returns `None` if there is no such subscriber.
+    async fn join_list(&self, id: ObjectId, list: &str) -> Result<Option<Subscriber>>;
+
+    /// Moves a membership of `id` to `status` like [`Self::update_status`].
+    /// Memberships of suppressed addresses never change.
+    async fn update_membership_status(
+        &self,
+        id: ObjectId,
+        list: &str,
+        status: SubscriberStatus,
+    ) -> Result<bool>;
+
+    /// Lists the members of `list` with `status`, leaving out bounced and
+    /// complained addresses.
+    async fn members(&self, list: &str, status: SubscriberStatus) -> Result<Vec<Subscriber>>;
+
+    /// Returns up to `limit` subscribers matching `filter`, ordered by id and
+    /// starting right after the subscriber with id `after`.
+    async fn page(
+        &self,
+        filter: &SubscriberFilter,
+        after: Option<ObjectId>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>>;
+
+    /// Renames a subscriber, returning `None` if there is no such subscriber.
+    async fn update_name(&self, id: ObjectId, name: &SubscriberName) -> Result<Option<Subscriber>>;
+
+    /// Sets or, with `None`, clears a subscriber's time zone. Returns `None`
+    /// if there is no such subscriber.
+    async fn update_time_zone(
+        &self,
+        id: ObjectId,
+        time_zone: Option<&SubscriberTimeZone>,
+    ) -> Result<Option<Subscriber>>;
+
+    /// Removes a subscriber along with their confirmation tokens. Returns
+    /// `false` if there was no such subscriber.
+    async fn delete(&self, id: ObjectId) -> Result<bool>;
+
+    async fn count_by_status(&self) -> Result<Vec<(SubscriberStatus, u64)>>;
+
+    /// Stores a token confirming `subscriber_id` on `list`, or on the main
+    /// list without one.
+    async fn store_token(
+        &self,
+        subscriber_id: ObjectId,
+        list: Option<&str>,
+        subscription_token: &str,
+    ) -> Result<()>;
+
+    /// Returns the id of the subscriber `subscription_token` was issued to and
+    /// the list it confirms them on, if not the main list.
+    async fn find_by_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<(ObjectId, Option<String>)>>;
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SubscriberDocument {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    email: String,
+    name: String,
+    status: SubscriberStatus,
+    created: bson::DateTime,
+    #[serde(default)]
+    time_zone: Option<String>,
+    #[serde(default)]
+    lists: Vec<ListMembership>,
+}
+
+impl From<SubscriberDocument> for Subscriber {
+    fn from(document: SubscriberDocument) -> Self {
+        Self {
+            id: document.id,
+            email: document.email,
+            name: document.name,
+            status: document.status,
+            created: document.created.to_chrono(),
+            time_zone: document.time_zone,
+            lists: document.lists,
+        }
+    }
+}
+
+/// Stores subscribers in the `subscribers` collection and their confirmation
+/// tokens in `subscription_tokens`.
+pub struct MongoSubscriberRepository {
+    db_client: mongodb::Client,
+}
+
+impl MongoSubscriberRepository {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn subscribers(&self) -> mongodb::Collection<SubscriberDocument> {
+        self.db_client.database("zero").collection("subscribers")
+    }
+
+    fn tokens(&self) -> mongodb::Collection<Document> {
+        self.db_client
+            .database("zero")
+            .collection("subscription_tokens")
+    }
+
+    async fn find_one(&self, filter: Document) -> Result<Option<Subscriber>> {
+        let subscriber = self
+            .subscribers()
+            .find_one(filter, None)
+            .await
+            .context("Failed to retrieve a subscriber")?;
+        Ok(subscriber.map(Subscriber::from))
+    }
+}
+
+#[async_trait]
+impl SubscriberRepository for MongoSubscriberRepository {
+    async fn insert(&self, new_subscriber: &NewSubscriber) -> Result<(Subscriber, bool)> {
+        // The document from before the upsert is missing if it inserted ours
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .build();
+        let subscriber = SubscriberDocument {
+            id: ObjectId::new(),
+            email: new_subscriber.email.as_ref().to_owned(),
+            name: new_subscriber.name.as_ref().to_owned(),
+            status: SubscriberStatus::PendingConfirmation,
+            created: bson::DateTime::now(),
+            time_zone: new_subscriber
+                .time_zone
+                .as_ref()
+                .map(|time_zone| time_zone.as_ref().to_owned()),
+            lists: Vec::new(),
+        };
+        let existing = self
+            .subscribers()
+            .find_one_and_update(
+                doc! {
+                    "email": &subscriber.email,
+                },
+                doc! {
+                    "$setOnInsert": bson::to_document(&subscriber)?,
+                },
+                Some(options),
+            )
+            .await
+            .context("Failed to save a new subscriber")?;
+        Ok(match existing {
+            Some(existing) => (existing.into(), false),
+            None => (subscriber.into(), true),
+        })
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Subscriber>> {
+        self.find_one(doc! { "_id": id }).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<Subscriber>> {
+        self.find_one(doc! { "email": email }).await
+    }
+
+    async fn update_status(&self, id: ObjectId, status: SubscriberStatus) -> Result<bool> {
+        let result = self
+            .subscribers()
+            .update_one(
+                doc! { "_id": id, "status": { "$in": status_strings(status.allowed_from()) } },
+                doc! { "$set": { "status": status.as_str(), "updated": Utc::now() } },
+                None,
+            )
+            .await
+            .context("Failed to update the subscriber status")?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn list(&self, status: Option<SubscriberStatus>) -> Result<Vec<Subscriber>> {
+        let filter = match status {
+            Some(status) => doc! { "status": status.as_str() },
+            None => doc! {},
+        };
+        let mut cursor = self
+            .subscribers()
+            .find(filter, None)
+            .await
+            .context("Failed to list subscribers")?;
+        let mut subscribers = Vec::new();
+        while cursor.advance().await? {
+            subscribers.push(cursor.deserialize_current()?.into());
+        }
+        Ok(subscribers)
+    }
+
+    async fn join_list(&self, id: ObjectId, list: &str) -> Result<Option<Subscriber>> {
+        let membership = ListMembership {
+            list: list.to_owned(),
+            status: SubscriberStatus::PendingConfirmation,
+        };
+        self.subscribers()
+            .update_one(
+                doc! { "_id": id, "lists.list": { "$ne": list } },
+                doc! { "$push": { "lists": bson::to_bson(&membership)? } },
+                None,
+            )
+            .await
+            .context("Failed to add a subscriber to a list")?;
+        self.find_by_id(id).await
+    }
+
+    async fn update_membership_status(
+        &self,
+        id: ObjectId,
+        list: &str,
+        status: SubscriberStatus,
+    ) -> Result<bool> {
+        let result = self
+            .subscribers()
+            .update_one(
+                doc! {
+                    "_id": id,
+                    "status": { "$nin": status_strings(SUPPRESSED) },
+                    "lists": { "$elemMatch": {
+                        "list": list,
+                        "status": { "$in": status_strings(status.allowed_from()) },
+                    } },
+                },
+                doc! { "$set": { "lists.$.status": status.as_str(), "updated": Utc::now() } },
+                None,
+            )
+            .await
+            .context("Failed to update the status of a list membership")?;
+        Ok(result.matched_count > 0)
+    }
+
+    async fn members(&self, list: &str, status: SubscriberStatus) -> Result<Vec<Subscriber>> {
+        let filter = doc! {
+            "lists": { "$elemMatch": { "list": list, "status": status.as_str() } },
+            "status": { "$nin": status_strings(SUPPRESSED) },
+        };
+        let mut cursor = self
+            .subscribers()
+            .find(filter, None)
+            .await
+            .context("Failed to list the members of a list")?;
+        let mut subscribers = Vec::new();
+        while cursor.advance().await? {
+            subscribers.push(cursor.deserialize_current()?.into());
+        }
+        Ok(subscribers)
+    }
+
+    async fn page(
+        &self,
+        filter: &SubscriberFilter,
+        after: Option<ObjectId>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>> {
+        let mut query = doc! {};
+        if let Some(status) = filter.status {
+            query.insert("status", status.as_str());
+        }
+        if let Some(email) = &filter.email {
+            query.insert("email", email);
+        }
+        if let Some(after) = after {
+            query.insert("_id", doc! { "$gt": after });
+        }
+        let options = FindOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .limit(i64::try_from(limit)?)
+            .build();
+        let mut cursor = self
+            .subscribers()
+            .find(query, Some(options))
+            .await
+            .context("Failed to list subscribers")?;
+        let mut subscribers = Vec::new();
+        while cursor.advance().await? {
+            subscribers.push(cursor.deserialize_current()?.into());
+        }
+        Ok(subscribers)
+    }
+
+    async fn update_name(&self, id: ObjectId, name: &SubscriberName) -> Result<Option<Subscriber>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let subscriber = self
+            .subscribers()
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$set": { "name": name.as_ref(), "updated": Utc::now() } },
+                Some(options),
+            )
+            .await
+            .context("Failed to rename a subscriber")?;
+        Ok(subscriber.map(Subscriber::from))
+    }
+
+    async fn update_time_zone(
+        &self,
+        id: ObjectId,
+        time_zone: Option<&SubscriberTimeZone>,
+    ) -> Result<Option<Subscriber>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let subscriber = self
+            .subscribers()
+            .find_one_and_update(
+                doc! { "_id": id },
+                doc! { "$set": {
+                    "time_zone": time_zone.map(AsRef::<str>::as_ref),
+                    "updated": Utc::now(),
+                } },
+                Some(options),
+            )
+            .await
+            .context("Failed to update the time zone of a subscriber")?;
+        Ok(subscriber.map(Subscriber::from))
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let result = self
+            .subscribers()
+            .delete_one(doc! { "_id": id }, None)
+            .await
+            .context("Failed to delete a subscriber")?;
+        self.tokens()
+            .delete_many(doc! { "subscriber_id": id }, None)
+            .await
+            .context("Failed to delete the subscriber's tokens")?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn count_by_status(&self) -> Result<Vec<(SubscriberStatus, u64)>> {
+        let pipeline = vec![
+            doc! { "$group": { "_id": "$status", "count": { "$sum": 1 } } },
+            doc! { "$sort": { "_id": 1 } },
+        ];
+        let mut cursor = self
+            .subscribers()
+            .aggregate(pipeline, None)
+            .await
+            .context("Failed to count subscribers")?;
+        let mut counts = Vec::new();
+        while cursor.advance().await? {
+            let group = cursor.deserialize_current()?;
+            let status = SubscriberStatus::try_from(group.get_str("_id")?.to_string())
+                .map_err(anyhow::Error::msg)?;
+            let count = group
+                .get_i32("count")
+                .map(i64::from)
+                .or_else(|_| group.get_i64("count"))?;
+            counts.push((status, count.try_into()?));
+        }
+        Ok(counts)
+    }
+
+    async fn store_token(
+        &self,
+        subscriber_id: ObjectId,
+        list: Option<&str>,
+        subscription_token: &str,
+    ) -> Result<()> {
+        self.tokens()
+            .insert_one(
+                doc! {
+                    "subscription_token": subscription_token,
+                    "subscriber_id": subscriber_id,
+                    "list": list,
+                },
+                None,
+            )
+            .await
+            .context("Failed to store a subscription token")?;
+        Ok(())
+    }
+
+    async fn find_by_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<(ObjectId, Option<String>)>> {
+        let token = self
+            .tokens()
+            .find_one(doc! { "subscription_token": subscription_token }, None)
+            .await
+            .context("Failed to retrieve a subscription token")?;
+        Ok(token.and_then(|t| {
+            let subscriber_id = t.get_object_id("subscriber_id").ok()?;
+            // Tokens stored before lists existed have no list field
+            Some((subscriber_id, t.get_str("list").ok().map(str::to_owned)))
+        }))
+    }
+}
+
+const SUPPRESSED: &[SubscriberStatus] = &[SubscriberStatus::Bounced, SubscriberStatus::Complained];
+
+fn status_strings(statuses: &[SubscriberStatus]) -> Vec<&'static str> {
+    statuses.iter().map(SubscriberStatus::as_str).collect()
+}
+
+#[derive(Default)]
+pub struct InMemorySubscriberRepository {
+    subscribers: Mutex<HashMap<ObjectId, Subscriber>>,
+    tokens: Mutex<HashMap<String, (ObjectId, Option<String>)>>,
+}
+
+#[async_trait]
+impl SubscriberRepository for InMemorySubscriberRepository {
+    async fn insert(&self, new_subscriber: &NewSubscriber) -> Result<(Subscriber, bool)> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(existing) = subscribers
+            .values()
+            .find(|s| s.email == new_subscriber.email.as_ref())
+        {
+            return Ok((existing.clone(), false));
+        }
+        let subscriber = Subscriber {
+            id: ObjectId::new(),
+            email: new_subscriber.email.as_ref().to_owned(),
+            name: new_subscriber.name.as_ref().to_owned(),
+            status: SubscriberStatus::PendingConfirmation,
+            created: Utc::now(),
+            time_zone: new_subscriber
+                .time_zone
+                .as_ref()
+                .map(|time_zone| time_zone.as_ref().to_owned()),
+            lists: Vec::new(),
+        };
+        subscribers.insert(subscriber.id, subscriber.clone());
+        Ok((subscriber, true))
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Subscriber>> {
+        Ok(self.subscribers.lock().unwrap().get(&id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<Subscriber>> {
+        let subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers.values().find(|s| s.email == email).cloned())
+    }
+
+    async fn update_status(&self, id: ObjectId, status: SubscriberStatus) -> Result<bool> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        match subscribers.get_mut(&id) {
+            Some(subscriber) if subscriber.status.can_become(status) => {
+                subscriber.status = status;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list(&self, status: Option<SubscriberStatus>) -> Result<Vec<Subscriber>> {
+        let subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers
+            .values()
+            .filter(|s| status.is_none_or(|status| s.status == status))
+            .cloned()
+            .collect())
+    }
+
+    async fn join_list(&self, id: ObjectId, list: &str) -> Result<Option<Subscriber>> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers.get_mut(&id).map(|subscriber| {
+            if subscriber.membership(list).is_none() {
+                subscriber.lists.push(ListMembership {
+                    list: list.to_owned(),
+                    status: SubscriberStatus::PendingConfirmation,
+                });
+            }
+            subscriber.clone()
+        }))
+    }
+
+    async fn update_membership_status(
+        &self,
+        id: ObjectId,
+        list: &str,
+        status: SubscriberStatus,
+    ) -> Result<bool> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let membership = subscribers
+            .get_mut(&id)
+            .filter(|subscriber| !subscriber.status.is_suppressed())
+            .and_then(|subscriber| subscriber.lists.iter_mut().find(|m| m.list == list))
+            .filter(|membership| membership.status.can_become(status));
+        match membership {
+            Some(membership) => {
+                membership.status = status;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn members(&self, list: &str, status: SubscriberStatus) -> Result<Vec<Subscriber>> {
+        let subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers
+            .values()
+            .filter(|s| {
+                s.membership(list)
+                    .is_some_and(|membership| membership.status == status)
+            })
+            .filter(|s| !s.status.is_suppressed())
+            .cloned()
+            .collect())
+    }
+
+    async fn page(
+        &self,
+        filter: &SubscriberFilter,
+        after: Option<ObjectId>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>> {
+        let subscribers = self.subscribers.lock().unwrap();
+        let mut page: Vec<_> = subscribers
+            .values()
+            .filter(|s| filter.status.is_none_or(|status| s.status == status))
+            .filter(|s| filter.email.as_ref().is_none_or(|email| &s.email == email))
+            .filter(|s| after.is_none_or(|after| s.id > after))
+            .cloned()
+            .collect();
+        page.sort_by_key(|s| s.id);
+        page.truncate(limit);
+        Ok(page)
+    }
+
+    async fn update_name(&self, id: ObjectId, name: &SubscriberName) -> Result<Option<Subscriber>> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers.get_mut(&id).map(|subscriber| {
+            subscriber.name = name.as_ref().to_owned();
+            subscriber.clone()
+        }))
+    }
+
+    async fn update_time_zone(
+        &self,
+        id: ObjectId,
+        time_zone: Option<&SubscriberTimeZone>,
+    ) -> Result<Option<Subscriber>> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        Ok(subscribers.get_mut(&id).map(|subscriber| {
+            subscriber.time_zone = time_zone.map(|time_zone| time_zone.as_ref().to_owned());
+            subscriber.clone()
+        }))
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool> {
+        let deleted = self.subscribers.lock().unwrap().remove(&id).is_some();
+        self.tokens
+            .lock()
+            .unwrap()
+            .retain(|_, (subscriber_id, _)| *subscriber_id != id);
+        Ok(deleted)
+    }
+
+    async fn count_by_status(&self) -> Result<Vec<(SubscriberStatus, u64)>> {
+        let mut counts = HashMap::new();
+        for subscriber in self.subscribers.lock().unwrap().values() {
+            *counts.entry(subscriber.status).or_insert(0) += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|(status, _)| status.as_str());
+        Ok(counts)
+    }
+
+    async fn store_token(
+        &self,
+        subscriber_id: ObjectId,
+        list: Option<&str>,
+        subscription_token: &str,
+    ) -> Result<()> {
+        self.tokens.lock().unwrap().insert(
+            subscription_token.to_owned(),
+            (subscriber_id, list.map(str::to_owned)),
+        );
+        Ok(())
+    }
+
+    async fn find_by_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<(ObjectId, Option<String>)>> {
+        Ok(self.tokens.lock().unwrap().get(subscription_token).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemorySubscriberRepository, SubscriberFilter, SubscriberRepository};
+    use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriberStatus};
+    use claim::assert_none;
+    use mongodb::bson::oid::ObjectId;
+
+    fn new_subscriber(email: &str) -> NewSubscriber {
+        NewSubscriber {
+            email: SubscriberEmail::parse(email.into()).unwrap(),
+            name: SubscriberName::parse("le guin".into()).unwrap(),
+            time_zone: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn new_subscribers_are_pending_confirmation() {
+        let repository = InMemorySubscriberRepository::default();
+        let (subscriber, created) = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap();
+        assert!(created);
+        assert_eq!(subscriber.status, SubscriberStatus::PendingConfirmation);
+    }
+
+    #[tokio::test]
+    async fn inserting_a_known_email_returns_the_existing_subscriber() {
+        let repository = InMemorySubscriberRepository::default();
+        let first = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository
+            .update_status(first.id, SubscriberStatus::Confirmed)
+            .await
+            .unwrap();
+
+        let (second, created) = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap();
+
+        assert!(!created);
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.status, SubscriberStatus::Confirmed);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_status() {
+        let repository = InMemorySubscriberRepository::default();
+        let confirmed = repository
+            .insert(&new_subscriber("confirmed@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository
+            .update_status(confirmed.id, SubscriberStatus::Confirmed)
+            .await
+            .unwrap();
+        repository
+            .insert(&new_subscriber("pending@example.com"))
+            .await
+            .unwrap();
+
+        let listed = repository
+            .list(Some(SubscriberStatus::Confirmed))
+            .await
+            .unwrap();
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, confirmed.id);
+        assert_eq!(repository.list(None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pages_resume_after_the_cursor() {
+        let repository = InMemorySubscriberRepository::default();
+        for i in 0..5 {
+            repository
+                .insert(&new_subscriber(&format!("{}@example.com", i)))
+                .await
+                .unwrap();
+        }
+        let filter = SubscriberFilter::default();
+
+        let first = repository.page(&filter, None, 3).await.unwrap();
+        let second = repository
+            .page(&filter, Some(first[2].id), 3)
+            .await
+            .unwrap();
+
+        assert_eq!(first.len(), 3);
+        assert_eq!(second.len(), 2);
+        assert!(first.iter().all(|a| second.iter().all(|b| a.id < b.id)));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_subscriber_drops_their_tokens() {
+        let repository = InMemorySubscriberRepository::default();
+        let subscriber = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository
+            .store_token(subscriber.id, None, "token")
+            .await
+            .unwrap();
+
+        assert!(repository.delete(subscriber.id).await.unwrap());
+
+        assert_none!(repository.find_by_token("token").await.unwrap());
+        assert!(!repository.delete(subscriber.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn tokens_resolve_to_their_subscriber() {
+        let repository = InMemorySubscriberRepository::default();
+        let subscriber = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository
+            .store_token(subscriber.id, None, "token")
+            .await
+            .unwrap();
+
+        repository
+            .store_token(subscriber.id, Some("weekly"), "weekly-token")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.find_by_token("token").await.unwrap(),
+            Some((subscriber.id, None))
+        );
+        assert_eq!(
+            repository.find_by_token("weekly-token").await.unwrap(),
+            Some((subscriber.id, Some("weekly".into())))
+        );
+        assert_none!(repository.find_by_token("unknown").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn joining_a_list_twice_keeps_the_first_membership() {
+        let repository = InMemorySubscriberRepository::default();
+        let subscriber = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository.join_list(subscriber.id, "weekly").await.unwrap();
+        repository
+            .update_membership_status(subscriber.id, "weekly", SubscriberStatus::Confirmed)
+            .await
+            .unwrap();
+
+        let subscriber = repository
+            .join_list(subscriber.id, "weekly")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(subscriber.lists.len(), 1);
+        assert_eq!(
+            subscriber.membership("weekly").unwrap().status,
+            SubscriberStatus::Confirmed
+        );
+        assert_none!(repository
+            .join_list(ObjectId::new(), "weekly")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn members_are_listed_per_list_without_suppressed_addresses() {
+        let repository = InMemorySubscriberRepository::default();
+        let mut ids = Vec::new();
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            let subscriber = repository.insert(&new_subscriber(email)).await.unwrap().0;
+            repository.join_list(subscriber.id, "weekly").await.unwrap();
+            repository
+                .update_membership_status(subscriber.id, "weekly", SubscriberStatus::Confirmed)
+                .await
+                .unwrap();
+            ids.push(subscriber.id);
+        }
+        repository
+            .update_status(ids[1], SubscriberStatus::Bounced)
+            .await
+            .unwrap();
+        repository
+            .update_membership_status(ids[2], "weekly", SubscriberStatus::Unsubscribed)
+            .await
+            .unwrap();
+
+        let members = repository
+            .members("weekly", SubscriberStatus::Confirmed)
+            .await
+            .unwrap();
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].id, ids[0]);
+        assert!(repository
+            .members("monthly", SubscriberStatus::Confirmed)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn status_changes_follow_the_allowed_transitions() {
+        let repository = InMemorySubscriberRepository::default();
+        let subscriber = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+
+        assert!(repository
+            .update_status(subscriber.id, SubscriberStatus::Confirmed)
+            .await
+            .unwrap());
+        assert!(repository
+            .update_status(subscriber.id, SubscriberStatus::Bounced)
+            .await
+            .unwrap());
+        for status in [SubscriberStatus::Confirmed, SubscriberStatus::Unsubscribed] {
+            assert!(!repository
+                .update_status(subscriber.id, status)
+                .await
+                .unwrap());
+        }
+
+        let subscriber = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+        assert_eq!(subscriber.status, SubscriberStatus::Bounced);
+        assert!(!repository
+            .update_status(ObjectId::new(), SubscriberStatus::Confirmed)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn memberships_of_suppressed_addresses_do_not_change() {
+        let repository = InMemorySubscriberRepository::default();
+        let subscriber = repository
+            .insert(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap()
+            .0;
+        repository.join_list(subscriber.id, "weekly").await.unwrap();
+        repository
+            .update_status(subscriber.id, SubscriberStatus::Complained)
+            .await
+            .unwrap();
+
+        for status in [SubscriberStatus::Confirmed, SubscriberStatus::Unsubscribed] {
+            assert!(!repository
+                .update_membership_status(subscriber.id, "weekly", status)
+                .await
+                .unwrap());
+        }
+        let subscriber = repository.find_by_id(subscriber.id).await.unwrap().unwrap();
+        assert_eq!(
+            subscriber.membership("weekly").unwrap().status,
+            SubscriberStatus::PendingConfirmation
+        );
+    }
+}
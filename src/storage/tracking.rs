@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+
+/// Something a subscriber did with a newsletter issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackingEvent {
+    /// The tracking pixel was loaded.
+    Open,
+    /// A tracked link was followed.
+    Click,
+}
+
+impl TrackingEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackingEvent::Open => "open",
+            TrackingEvent::Click => "click",
+        }
+    }
+}
+
+impl TryFrom<&str> for TrackingEvent {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "open" => Ok(Self::Open),
+            "click" => Ok(Self::Click),
+            other => Err(format!("{} is not a tracking event.", other)),
+        }
+    }
+}
+
+/// How often an event was recorded for an issue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventCount {
+    pub total: u64,
+    /// Subscribers the event was recorded for at least once.
+    pub unique: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IssueEngagement {
+    pub opens: EventCount,
+    pub clicks: EventCount,
+}
+
+#[async_trait]
+pub trait TrackingRepository: Send + Sync {
+    /// Records `event` for `subscriber_id`; `url` is the target of a click.
+    async fn record(
+        &self,
+        issue_id: ObjectId,
+        subscriber_id: ObjectId,
+        event: TrackingEvent,
+        url: Option<&str>,
+    ) -> Result<()>;
+
+    async fn engagement(&self, issue_id: ObjectId) -> Result<IssueEngagement>;
+}
+
+/// Stores one document per event in `tracking_events`.
+pub struct MongoTrackingRepository {
+    db_client: mongodb::Client,
+}
+
+impl MongoTrackingRepository {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn events(&self) -> mongodb::Collection<Document> {
+        self.db_client
+            .database("zero")
+            .collection("tracking_events")
+    }
+}
+
+#[async_trait]
+impl TrackingRepository for MongoTrackingRepository {
+    async fn record(
+        &self,
+        issue_id: ObjectId,
+        subscriber_id: ObjectId,
+        event: TrackingEvent,
+        url: Option<&str>,
+    ) -> Result<()> {
+        self.events()
+            .insert_one(
+                doc! {
+                    "newsletter_issue_id": issue_id,
+                    "subscriber_id": subscriber_id,
+                    "event": event.as_str(),
+                    "url": url,
+                    "recorded_at": Utc::now(),
+                },
+                None,
+            )
+            .await
+            .context("Failed to record a tracking event")?;
+        Ok(())
+    }
+
+    async fn engagement(&self, issue_id: ObjectId) -> Result<IssueEngagement> {
+        let pipeline = vec![
+            doc! { "$match": { "newsletter_issue_id": issue_id } },
+            doc! { "$group": {
+                "_id": "$event",
+                "total": { "$sum": 1 },
+                "subscribers": { "$addToSet": "$subscriber_id" },
+            } },
+            doc! { "$project": { "total": 1, "unique": { "$size": "$subscribers" } } },
+        ];
+        let mut cursor = self
+            .events()
+            .aggregate(pipeline, None)
+            .await
+            .context("Failed to aggregate tracking events")?;
+        let mut engagement = IssueEngagement::default();
+        while cursor.advance().await? {
+            let group = cursor.deserialize_current()?;
+            let count = |field: &str| -> Result<u64> {
+                let count = group
+                    .get_i32(field)
+                    .map(i64::from)
+                    .or_else(|_| group.get_i64(field))?;
+                Ok(count.try_into()?)
+            };
+            let counts = EventCount {
+                total: count("total")?,
+                unique: count("unique")?,
+            };
+            match TrackingEvent::try_from(group.get_str("_id")?).map_err(anyhow::Error::msg)? {
+                TrackingEvent::Open => engagement.opens = counts,
+                TrackingEvent::Click => engagement.clicks = counts,
+            }
+        }
+        Ok(engagement)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryTrackingRepository {
+    events: Mutex<Vec<(ObjectId, ObjectId, TrackingEvent)>>,
+}
+
+#[async_trait]
+impl TrackingRepository for InMemoryTrackingRepository {
+    async fn record(
+        &self,
+        issue_id: ObjectId,
+        subscriber_id: ObjectId,
+        event: TrackingEvent,
+        _url: Option<&str>,
+    ) -> Result<()> {
+        self.events
+            .lock()
+            .unwrap()
+            .push((issue_id, subscriber_id, event));
+        Ok(())
+    }
+
+    async fn engagement(&self, issue_id: ObjectId) -> Result<IssueEngagement> {
+        let events = self.events.lock().unwrap();
+        let mut subscribers: HashMap<TrackingEvent, HashSet<ObjectId>> = HashMap::new();
+        let mut engagement = IssueEngagement::default();
+        for (_, subscriber_id, event) in events.iter().filter(|(id, _, _)| *id == issue_id) {
+            let counts = match event {
+                TrackingEvent::Open => &mut engagement.opens,
+                TrackingEvent::Click => &mut engagement.clicks,
+            };
+            counts.total += 1;
+            if subscribers
+                .entry(*event)
+                .or_default()
+                .insert(*subscriber_id)
+            {
+                counts.unique += 1;
+            }
+        }
+        Ok(engagement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventCount, InMemoryTrackingRepository, TrackingEvent, TrackingRepository};
+    use mongodb::bson::oid::ObjectId;
+
+    #[tokio::test]
+    async fn repeated_events_count_once_per_subscriber() {
+        let repository = InMemoryTrackingRepository::default();
+        let issue_id = ObjectId::new();
+        let (ada, grace) = (ObjectId::new(), ObjectId::new());
+        for subscriber_id in [ada, ada, grace] {
+            repository
+                .record(issue_id, subscriber_id, TrackingEvent::Open, None)
+                .await
+                .unwrap();
+        }
+        repository
+            .record(issue_id, ada, TrackingEvent::Click, Some("https://a.b"))
+            .await
+            .unwrap();
+        repository
+            .record(ObjectId::new(), grace, TrackingEvent::Click, None)
+            .await
+            .unwrap();
+
+        let engagement = repository.engagement(issue_id).await.unwrap();
+
+        assert_eq!(
+            engagement.opens,
+            EventCount {
+                total: 3,
+                unique: 2
+            }
+        );
+        assert_eq!(
+            engagement.clicks,
+            EventCount {
+                total: 1,
+                unique: 1
+            }
+        );
+    }
+}
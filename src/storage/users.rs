@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use mongodb::bson::{doc, oid::ObjectId};
+use secrecy::ExposeSecret;
+
+use crate::domain::HashedPassword;
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: ObjectId,
+    pub username: String,
+    pub password_hash: HashedPassword,
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert(&self, username: &str, password_hash: &HashedPassword) -> Result<ObjectId>;
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>>;
+
+    async fn find_by_id(&self, user_id: ObjectId) -> Result<Option<User>>;
+
+    async fn update_password_hash(
+        &self,
+        user_id: ObjectId,
+        password_hash: &HashedPassword,
+    ) -> Result<()>;
+}
+
+#[derive(serde::Deserialize)]
+struct UserDocument {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    username: String,
+    password_hash: String,
+}
+
+impl TryFrom<UserDocument> for User {
+    type Error = anyhow::Error;
+    fn try_from(document: UserDocument) -> Result<Self> {
+        Ok(Self {
+            id: document.id,
+            username: document.username,
+            password_hash: HashedPassword::parse(document.password_hash).map_err(|e| anyhow!(e))?,
+        })
+    }
+}
+
+/// Stores admin users in the `users` collection.
+pub struct MongoUserRepository {
+    db_client: mongodb::Client,
+}
+
+impl MongoUserRepository {
+    pub fn new(db_client: mongodb::Client) -> Self {
+        Self { db_client }
+    }
+
+    fn users(&self) -> mongodb::Collection<UserDocument> {
+        self.db_client.database("zero").collection("users")
+    }
+
+    async fn find_one(&self, filter: mongodb::bson::Document) -> Result<Option<User>> {
+        self.users()
+            .find_one(filter, None)
+            .await
+            .context("Failed to perform a query to retrieve a stored user.")?
+            .map(User::try_from)
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl UserRepository for MongoUserRepository {
+    async fn insert(&self, username: &str, password_hash: &HashedPassword) -> Result<ObjectId> {
+        let result = self
+            .users()
+            .clone_with_type::<mongodb::bson::Document>()
+            .insert_one(
+                doc! {
+                    "username": username,
+                    "password_hash": password_hash.expose_secret(),
+                    "created": chrono::Utc::now(),
+                },
+                None,
+            )
+            .await
+            .context("Failed to store the new user.")?;
+        result
+            .inserted_id
+            .as_object_id()
+            .ok_or_else(|| anyhow!("The new user was not assigned an ObjectId"))
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.find_one(doc! { "username": username }).await
+    }
+
+    async fn find_by_id(&self, user_id: ObjectId) -> Result<Option<User>> {
+        self.find_one(doc! { "_id": user_id }).await
+    }
+
+    async fn update_password_hash(
+        &self,
+        user_id: ObjectId,
+        password_hash: &HashedPassword,
+    ) -> Result<()> {
+        self.users()
+            .update_one(
+                doc! { "_id": user_id },
+                doc! { "$set": { "password_hash": password_hash.expose_secret() } },
+                None,
+            )
+            .await
+            .context("Failed to change user's password in the database.")?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: Mutex<HashMap<ObjectId, User>>,
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn insert(&self, username: &str, password_hash: &HashedPassword) -> Result<ObjectId> {
+        let mut users = self.users.lock().unwrap();
+        if users.values().any(|u| u.username == username) {
+            return Err(anyhow!("A user named {} already exists.", username));
+        }
+        let user = User {
+            id: ObjectId::new(),
+            username: username.to_owned(),
+            password_hash: password_hash.clone(),
+        };
+        let user_id = user.id;
+        users.insert(user_id, user);
+        Ok(user_id)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>> {
+        let users = self.users.lock().unwrap();
+        Ok(users.values().find(|u| u.username == username).cloned())
+    }
+
+    async fn find_by_id(&self, user_id: ObjectId) -> Result<Option<User>> {
+        Ok(self.users.lock().unwrap().get(&user_id).cloned())
+    }
+
+    async fn update_password_hash(
+        &self,
+        user_id: ObjectId,
+        password_hash: &HashedPassword,
+    ) -> Result<()> {
+        let mut users = self.users.lock().unwrap();
+        let user = users
+            .get_mut(&user_id)
+            .ok_or_else(|| anyhow!("No user with id {}", user_id))?;
+        user.password_hash = password_hash.clone();
+        Ok(())
+    }
+}
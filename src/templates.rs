@@ -0,0 +1,224 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use minijinja::{AutoEscape, Environment, ErrorKind, UndefinedBehavior};
+
+/// The emails the application sends.
+///
+/// Each is rendered from `<name>.html` in the templates directory, with its
+/// fields as variables. The plain-text part comes from `<name>.txt` if there
+/// is one, and is generated from the HTML otherwise.
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+pub enum EmailTemplate<'a> {
+    Confirmation {
+        name: &'a str,
+        confirmation_link: &'a str,
+    },
+    Welcome {
+        name: &'a str,
+    },
+    /// Wraps an issue; `html_content` is trusted and inserted unescaped.
+    Newsletter {
+        title: &'a str,
+        html_content: &'a str,
+        text_content: &'a str,
+        unsubscribe_link: &'a str,
+    },
+    UnsubscribeConfirmation {
+        name: &'a str,
+    },
+}
+
+impl EmailTemplate<'_> {
+    const NAMES: [&'static str; 4] = [
+        "confirmation",
+        "welcome",
+        "newsletter",
+        "unsubscribe_confirmation",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Confirmation { .. } => "confirmation",
+            EmailTemplate::Welcome { .. } => "welcome",
+            EmailTemplate::Newsletter { .. } => "newsletter",
+            EmailTemplate::UnsubscribeConfirmation { .. } => "unsubscribe_confirmation",
+        }
+    }
+}
+
+pub struct RenderedEmail {
+    pub html: String,
+    pub text: String,
+}
+
+/// The compiled email templates.
+pub struct Templates {
+    environment: Environment<'static>,
+}
+
+impl Templates {
+    /// Compiles every `.html` and `.txt` template in `directory`, failing if
+    /// one is invalid or an [`EmailTemplate`] has no HTML template.
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref();
+        let mut environment = Environment::new();
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        // The default escaper also escapes `/`, which mangles links.
+        environment.set_formatter(|out, state, value| match value.as_str() {
+            Some(s) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => out
+                .write_str(&htmlescape::encode_minimal(s))
+                .map_err(|_| ErrorKind::WriteFailure.into()),
+            _ => minijinja::escape_formatter(out, state, value),
+        });
+
+        let entries = std::fs::read_dir(directory)
+            .with_context(|| format!("Failed to read the templates in {}", directory.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let is_template = path
+                .extension()
+                .is_some_and(|extension| extension == "html" || extension == "txt");
+            if !is_template {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .with_context(|| format!("Invalid template name {}", path.display()))?
+                .to_string();
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            environment
+                .add_template_owned(name, source)
+                .with_context(|| format!("Invalid template {}", path.display()))?;
+        }
+
+        for name in EmailTemplate::NAMES {
+            environment
+                .get_template(&format!("{}.html", name))
+                .with_context(|| format!("Missing the {} email template", name))?;
+        }
+        Ok(Self { environment })
+    }
+
+    pub fn render(&self, template: &EmailTemplate<'_>) -> Result<RenderedEmail> {
+        let name = template.name();
+        let html = self
+            .environment
+            .get_template(&format!("{}.html", name))?
+            .render(template)
+            .with_context(|| format!("Failed to render the {} email", name))?;
+        let text = match self.environment.get_template(&format!("{}.txt", name)) {
+            Ok(text_template) => text_template
+                .render(template)
+                .with_context(|| format!("Failed to render the {} email", name))?,
+            Err(_) => html_to_text(&html)?,
+        };
+        Ok(RenderedEmail { html, text })
+    }
+}
+
+/// Wide enough that links are never broken across lines; mail clients wrap
+/// plain text themselves.
+const TEXT_WIDTH: usize = 1_000;
+
+/// Renders `html` as plain text, listing link targets as footnotes.
+pub fn html_to_text(html: &str) -> Result<String> {
+    html2text::config::plain()
+        .string_from_read(html.as_bytes(), TEXT_WIDTH)
+        .context("Failed to convert HTML to plain text")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::templates::{html_to_text, EmailTemplate, Templates};
+
+    const TEMPLATES_DIRECTORY: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/configuration/templates");
+
+    fn every_template() -> Vec<EmailTemplate<'static>> {
+        vec![
+            EmailTemplate::Confirmation {
+                name: "Ursula",
+                confirmation_link:
+                    "https://example.com/subscriptions/confirm?subscription_token=abc",
+            },
+            EmailTemplate::Welcome { name: "Ursula" },
+            EmailTemplate::Newsletter {
+                title: "Issue #1",
+                html_content: "<p>Hello</p>",
+                text_content: "Hello",
+                unsubscribe_link: "https://example.com/subscriptions/unsubscribe?token=abc",
+            },
+            EmailTemplate::UnsubscribeConfirmation { name: "Ursula" },
+        ]
+    }
+
+    #[test]
+    fn every_configured_template_compiles_and_renders() {
+        let templates = Templates::load(TEMPLATES_DIRECTORY).unwrap();
+        for template in every_template() {
+            let rendered = templates
+                .render(&template)
+                .unwrap_or_else(|e| panic!("{}: {:?}", template.name(), e));
+            assert!(!rendered.html.trim().is_empty());
+            assert!(!rendered.text.trim().is_empty());
+        }
+    }
+
+    #[test]
+    fn variables_are_escaped_in_html_but_links_survive() {
+        let templates = Templates::load(TEMPLATES_DIRECTORY).unwrap();
+        let link = "https://example.com/subscriptions/confirm?subscription_token=abc";
+
+        let rendered = templates
+            .render(&EmailTemplate::Confirmation {
+                name: "<b>Ursula</b>",
+                confirmation_link: link,
+            })
+            .unwrap();
+
+        assert!(rendered.html.contains("&lt;b&gt;Ursula&lt;/b&gt;"));
+        assert!(rendered.html.contains(&format!("href=\"{}\"", link)));
+        assert!(rendered.text.contains(link));
+    }
+
+    #[test]
+    fn loading_fails_if_a_template_is_missing_or_invalid() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir(&directory).unwrap();
+        for name in EmailTemplate::NAMES {
+            std::fs::write(directory.join(format!("{}.html", name)), "<p>Hi</p>").unwrap();
+        }
+        std::fs::remove_file(directory.join("welcome.html")).unwrap();
+        assert!(Templates::load(&directory).is_err());
+
+        std::fs::write(directory.join("welcome.html"), "<p>Hi {{ name </p>").unwrap();
+        assert!(Templates::load(&directory).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn html_is_converted_to_readable_text() {
+        let text = html_to_text(
+            r#"<p>Hello <b>there</b></p><p><a href="https://example.com">Visit</a></p>"#,
+        )
+        .unwrap();
+
+        assert!(text.contains("Hello there"));
+        assert!(text.contains("https://example.com"));
+        assert!(!text.contains('<'));
+    }
+
+    #[test]
+    fn long_links_are_not_wrapped() {
+        let link = format!("https://example.com/confirm?token={}", "a".repeat(200));
+
+        let text = html_to_text(&format!(r#"<p><a href="{}">Confirm</a></p>"#, link)).unwrap();
+
+        assert!(text.contains(&link));
+    }
+}
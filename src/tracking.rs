@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use mongodb::bson::oid::ObjectId;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+/// Signs and verifies the tokens behind the open pixel and the link
+/// redirector of newsletter issues.
+///
+/// A token is the base64 encoded issue id, subscriber id and, for clicks,
+/// target URL, followed by an HMAC-SHA256 of them. Nobody can record events
+/// for someone else or turn the redirector into an open redirect.
+#[derive(Clone)]
+pub struct TrackingSigner {
+    base_url: String,
+    secret: Secret<String>,
+}
+
+/// Stands for the recipient's open pixel URL in [`InstrumentedHtml`].
+const OPEN_PIXEL_PLACEHOLDER: &str = "{{open_pixel_url}}";
+
+fn click_placeholder(index: usize) -> String {
+    format!("{{{{click_url_{}}}}}", index)
+}
+
+/// An issue's HTML with every web link routed through the click redirector
+/// and the open pixel appended. Their URLs differ for every recipient, so
+/// they are left as placeholders; see [`TrackingSigner::substitutions`].
+pub struct InstrumentedHtml {
+    pub html: String,
+    /// The target of every click placeholder, by index.
+    links: Vec<String>,
+}
+
+impl InstrumentedHtml {
+    pub fn new(html: &str) -> Self {
+        let mut instrumented = String::with_capacity(html.len());
+        let mut links: Vec<String> = Vec::new();
+        let mut rest = html;
+        while let Some((before, value, quote, after)) = next_href(rest) {
+            instrumented.push_str(before);
+            let url = htmlescape::decode_html(value).unwrap_or_else(|_| value.to_string());
+            if is_web_link(&url) {
+                let index = links
+                    .iter()
+                    .position(|link| *link == url)
+                    .unwrap_or_else(|| {
+                        links.push(url);
+                        links.len() - 1
+                    });
+                instrumented.push_str(&click_placeholder(index));
+            } else {
+                instrumented.push_str(value);
+            }
+            instrumented.push(quote);
+            rest = after;
+        }
+        instrumented.push_str(rest);
+        instrumented.push_str(&format!(
+            r#"<img src="{}" width="1" height="1" alt="">"#,
+            OPEN_PIXEL_PLACEHOLDER
+        ));
+        Self {
+            html: instrumented,
+            links,
+        }
+    }
+}
+
+/// Who a verified tracking token was issued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackedRecipient {
+    pub issue_id: ObjectId,
+    pub subscriber_id: ObjectId,
+}
+
+impl TrackingSigner {
+    pub fn new(base_url: String, secret: Secret<String>) -> Self {
+        Self { base_url, secret }
+    }
+
+    /// Open and click tokens are signed under different labels so one cannot
+    /// be passed off as the other.
+    fn mac(&self, label: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(label);
+        mac.update(b".");
+        mac.update(payload);
+        mac
+    }
+
+    fn sign(&self, label: &[u8], payload: &[u8]) -> String {
+        let signature = self.mac(label, payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    fn verify(&self, label: &[u8], token: &str) -> Result<Vec<u8>, String> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| "Malformed tracking token.".to_string())?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| "Malformed tracking token.".to_string())?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| "Malformed tracking token.".to_string())?;
+        self.mac(label, &payload)
+            .verify_slice(&signature)
+            .map_err(|_| "Invalid tracking token signature.".to_string())?;
+        Ok(payload)
+    }
+
+    pub fn open_token(&self, issue_id: ObjectId, subscriber_id: ObjectId) -> String {
+        self.sign(b"open", &recipient_bytes(issue_id, subscriber_id))
+    }
+
+    pub fn click_token(&self, issue_id: ObjectId, subscriber_id: ObjectId, url: &str) -> String {
+        let mut payload = recipient_bytes(issue_id, subscriber_id);
+        payload.extend_from_slice(url.as_bytes());
+        self.sign(b"click", &payload)
+    }
+
+    pub fn open_pixel_url(&self, issue_id: ObjectId, subscriber_id: ObjectId) -> String {
+        format!(
+            "{}/t/o/{}.gif",
+            self.base_url,
+            self.open_token(issue_id, subscriber_id)
+        )
+    }
+
+    pub fn click_url(&self, issue_id: ObjectId, subscriber_id: ObjectId, url: &str) -> String {
+        format!(
+            "{}/t/c/{}",
+            self.base_url,
+            self.click_token(issue_id, subscriber_id, url)
+        )
+    }
+
+    pub fn verify_open(&self, token: &str) -> Result<TrackedRecipient, String> {
+        let payload = self.verify(b"open", token)?;
+        if payload.len() != 24 {
+            return Err("Malformed tracking token.".into());
+        }
+        Ok(parse_recipient(&payload))
+    }
+
+    /// Returns who clicked and the URL they are headed to.
+    pub fn verify_click(&self, token: &str) -> Result<(TrackedRecipient, String), String> {
+        let payload = self.verify(b"click", token)?;
+        if payload.len() < 24 {
+            return Err("Malformed tracking token.".into());
+        }
+        let url = String::from_utf8(payload[24..].to_vec())
+            .map_err(|_| "Malformed tracking token.".to_string())?;
+        Ok((parse_recipient(&payload), url))
+    }
+
+    /// The tracked URLs that replace the placeholders of `instrumented` for
+    /// one recipient.
+    pub fn substitutions(
+        &self,
+        instrumented: &InstrumentedHtml,
+        issue_id: ObjectId,
+        subscriber_id: ObjectId,
+    ) -> BTreeMap<String, String> {
+        let mut substitutions: BTreeMap<_, _> = instrumented
+            .links
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                (
+                    click_placeholder(index),
+                    self.click_url(issue_id, subscriber_id, url),
+                )
+            })
+            .collect();
+        substitutions.insert(
+            OPEN_PIXEL_PLACEHOLDER.into(),
+            self.open_pixel_url(issue_id, subscriber_id),
+        );
+        substitutions
+    }
+}
+
+fn recipient_bytes(issue_id: ObjectId, subscriber_id: ObjectId) -> Vec<u8> {
+    [issue_id.bytes(), subscriber_id.bytes()].concat()
+}
+
+/// `payload` must hold at least 24 bytes.
+fn parse_recipient(payload: &[u8]) -> TrackedRecipient {
+    let id = |bytes: &[u8]| ObjectId::from_bytes(bytes.try_into().expect("12 bytes"));
+    TrackedRecipient {
+        issue_id: id(&payload[..12]),
+        subscriber_id: id(&payload[12..24]),
+    }
+}
+
+fn is_web_link(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Finds the next quoted `href` attribute value in `html` and splits it
+/// into the text up to and including the opening quote, the value, the
+/// quote and the text after the closing quote.
+fn next_href(html: &str) -> Option<(&str, &str, char, &str)> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut from = 0;
+    loop {
+        let name = from + lowercase[from..].find("href")?;
+        from = name + "href".len();
+        let preceded_by_space = lowercase[..name]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_whitespace);
+        let after_name = lowercase[from..].trim_start();
+        let Some(after_equals) = after_name.strip_prefix('=') else {
+            continue;
+        };
+        let after_equals = after_equals.trim_start();
+        let Some(quote) = after_equals
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+        else {
+            continue;
+        };
+        if !preceded_by_space {
+            continue;
+        }
+        let value_start = html.len() - after_equals.len() + 1;
+        let value_end = value_start + html[value_start..].find(quote)?;
+        return Some((
+            &html[..value_start],
+            &html[value_start..value_end],
+            quote,
+            &html[value_end + 1..],
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{InstrumentedHtml, TrackedRecipient, TrackingSigner};
+    use claim::assert_err;
+    use mongodb::bson::oid::ObjectId;
+    use secrecy::Secret;
+
+    fn signer(secret: &str) -> TrackingSigner {
+        TrackingSigner::new("http://localhost".into(), Secret::new(secret.into()))
+    }
+
+    fn recipient() -> TrackedRecipient {
+        TrackedRecipient {
+            issue_id: ObjectId::new(),
+            subscriber_id: ObjectId::new(),
+        }
+    }
+
+    #[test]
+    fn signed_tokens_are_verified() {
+        let signer = signer("secret");
+        let r = recipient();
+
+        let open = signer.open_token(r.issue_id, r.subscriber_id);
+        let click = signer.click_token(r.issue_id, r.subscriber_id, "https://example.com/?a=1");
+
+        assert_eq!(signer.verify_open(&open), Ok(r));
+        assert_eq!(
+            signer.verify_click(&click),
+            Ok((r, "https://example.com/?a=1".to_string()))
+        );
+    }
+
+    #[test]
+    fn open_and_click_tokens_are_not_interchangeable() {
+        let signer = signer("secret");
+        let r = recipient();
+        let open = signer.open_token(r.issue_id, r.subscriber_id);
+        let click = signer.click_token(r.issue_id, r.subscriber_id, "");
+
+        assert_err!(signer.verify_click(&open));
+        assert_err!(signer.verify_open(&click));
+    }
+
+    #[test]
+    fn forged_and_malformed_tokens_are_rejected() {
+        let r = recipient();
+        let token = signer("secret").click_token(r.issue_id, r.subscriber_id, "https://a.b");
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!(
+            "{}.{}",
+            signer("secret")
+                .click_token(r.issue_id, r.subscriber_id, "https://evil.example")
+                .split_once('.')
+                .unwrap()
+                .0,
+            signature
+        );
+
+        assert_err!(signer("other secret").verify_click(&token));
+        assert_err!(signer("secret").verify_click(&forged));
+        for token in ["", "no-separator", "!!.c2lnbmF0dXJl", "."] {
+            assert_err!(signer("secret").verify_open(token));
+            assert_err!(signer("secret").verify_click(token));
+        }
+    }
+
+    #[test]
+    fn web_links_are_rewritten_and_a_pixel_is_appended() {
+        let signer = signer("secret");
+        let r = recipient();
+        let html = concat!(
+            r#"<a href="https://example.com/?a=1&amp;b=2">One</a>"#,
+            r#"<A class="x" HREF = 'http://example.com'>Two</A>"#,
+            r#"<a href="mailto:editor@example.com">Mail</a>"#,
+            r#"<a data-href="https://example.com/skip">Three</a>"#,
+            r#"<a href="http://example.com">Two again</a>"#,
+        );
+
+        let instrumented = InstrumentedHtml::new(html);
+        let substitutions = signer.substitutions(&instrumented, r.issue_id, r.subscriber_id);
+
+        assert_eq!(
+            instrumented.html,
+            concat!(
+                r#"<a href="{{click_url_0}}">One</a>"#,
+                r#"<A class="x" HREF = '{{click_url_1}}'>Two</A>"#,
+                r#"<a href="mailto:editor@example.com">Mail</a>"#,
+                r#"<a data-href="https://example.com/skip">Three</a>"#,
+                r#"<a href="{{click_url_1}}">Two again</a>"#,
+                r#"<img src="{{open_pixel_url}}" width="1" height="1" alt="">"#,
+            )
+        );
+        let click = |url| signer.click_url(r.issue_id, r.subscriber_id, url);
+        assert_eq!(
+            substitutions,
+            BTreeMap::from([
+                (
+                    "{{click_url_0}}".into(),
+                    click("https://example.com/?a=1&b=2")
+                ),
+                ("{{click_url_1}}".into(), click("http://example.com")),
+                (
+                    "{{open_pixel_url}}".into(),
+                    signer.open_pixel_url(r.issue_id, r.subscriber_id)
+                ),
+            ])
+        );
+    }
+}
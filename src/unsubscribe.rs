@@ -0,0 +1,146 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use mongodb::bson::oid::ObjectId;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+/// Signs and verifies the per-subscriber tokens embedded in unsubscribe links.
+///
+/// A token is the subscriber id and, for a list other than the main list, the
+/// list's slug, followed by an HMAC-SHA256 of them, so it can be verified
+/// without a database lookup and cannot be forged for another subscriber or
+/// list.
+#[derive(Clone)]
+pub struct UnsubscribeSigner {
+    base_url: String,
+    secret: Secret<String>,
+}
+
+impl UnsubscribeSigner {
+    pub fn new(base_url: String, secret: Secret<String>) -> Self {
+        Self { base_url, secret }
+    }
+
+    /// Tokens for the main list sign the subscriber id alone, as they did
+    /// before there were other lists.
+    fn mac(&self, subscriber_id: ObjectId, list: Option<&str>) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(&subscriber_id.bytes());
+        if let Some(list) = list {
+            mac.update(b".");
+            mac.update(list.as_bytes());
+        }
+        mac
+    }
+
+    /// A token unsubscribing `subscriber_id` from `list`, or from the main
+    /// list without one.
+    pub fn token(&self, subscriber_id: ObjectId, list: Option<&str>) -> String {
+        let signature =
+            URL_SAFE_NO_PAD.encode(self.mac(subscriber_id, list).finalize().into_bytes());
+        match list {
+            Some(list) => format!("{}.{}.{}", subscriber_id.to_hex(), list, signature),
+            None => format!("{}.{}", subscriber_id.to_hex(), signature),
+        }
+    }
+
+    pub fn link(&self, subscriber_id: ObjectId, list: Option<&str>) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            self.base_url,
+            self.token(subscriber_id, list)
+        )
+    }
+
+    /// Returns the id of the subscriber `token` was issued for and the list
+    /// it unsubscribes them from, if not the main list.
+    pub fn verify(&self, token: &str) -> Result<(ObjectId, Option<String>), String> {
+        let malformed = || "Malformed unsubscribe token.".to_string();
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+        let (id, list) = match payload.split_once('.') {
+            Some((id, list)) => (id, Some(list)),
+            None => (payload, None),
+        };
+        let subscriber_id = ObjectId::parse_str(id).map_err(|_| malformed())?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| malformed())?;
+        self.mac(subscriber_id, list)
+            .verify_slice(&signature)
+            .map_err(|_| "Invalid unsubscribe token signature.".to_string())?;
+        Ok((subscriber_id, list.map(str::to_owned)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsubscribeSigner;
+    use claim::assert_err;
+    use mongodb::bson::oid::ObjectId;
+    use secrecy::Secret;
+
+    fn signer(secret: &str) -> UnsubscribeSigner {
+        UnsubscribeSigner::new("http://localhost".into(), Secret::new(secret.into()))
+    }
+
+    #[test]
+    fn a_signed_token_is_verified() {
+        let signer = signer("secret");
+        let subscriber_id = ObjectId::new();
+        let token = signer.token(subscriber_id, None);
+        assert_eq!(signer.verify(&token), Ok((subscriber_id, None)));
+    }
+
+    #[test]
+    fn a_list_token_is_verified_for_that_list_only() {
+        let signer = signer("secret");
+        let subscriber_id = ObjectId::new();
+        let token = signer.token(subscriber_id, Some("weekly"));
+        assert_eq!(
+            signer.verify(&token),
+            Ok((subscriber_id, Some("weekly".to_string())))
+        );
+
+        let forged = token.replace(".weekly.", ".monthly.");
+        assert_err!(signer.verify(&forged));
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let main_list = format!("{}.{}", subscriber_id.to_hex(), signature);
+        assert_err!(signer.verify(&main_list));
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_is_rejected() {
+        let signer = signer("secret");
+        let token = signer.token(ObjectId::new(), None);
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", ObjectId::new().to_hex(), signature);
+        assert_err!(signer.verify(&forged));
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = signer("secret").token(ObjectId::new(), None);
+        assert_err!(signer("other secret").verify(&token));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let signer = signer("secret");
+        for token in ["", "no-separator", "not-an-id.c2lnbmF0dXJl", "."] {
+            assert_err!(signer.verify(token));
+        }
+    }
+
+    #[test]
+    fn links_point_to_the_unsubscribe_route() {
+        let signer = signer("secret");
+        let subscriber_id = ObjectId::new();
+        assert_eq!(
+            signer.link(subscriber_id, None),
+            format!(
+                "http://localhost/subscriptions/unsubscribe?token={}",
+                signer.token(subscriber_id, None)
+            )
+        );
+    }
+}
@@ -1,19 +1,365 @@
-use actix_web::web;
-use mongodb::bson::doc;
-use secrecy::ExposeSecret;
-use std::net::TcpListener;
-use std::sync::Once;
+use chrono::Timelike;
+use secrecy::Secret;
+use std::sync::{Arc, Once};
+use wiremock::matchers::{any, body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero::{
-    configuration::get_configuration,
+    authentication::create_user,
+    configuration::{
+        ApplicationSettings, DatabaseSettings, EmailClientSettings, EmailProvider, Settings,
+    },
+    domain::{Password, SendTime, SubscriberStatus},
     email_client::EmailClient,
+    idempotency::IdempotencyKey,
+    issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    issue_scheduler::try_publish_due_issue,
+    routes::WebhookVerifier,
+    session_store::InMemorySessionStore,
+    startup::Application,
+    storage::{DeliveryQueue, InMemoryDeliveryQueue, Storage, Subscriber, UserRepository},
     telemetry::{get_subscriber, init_subscriber},
+    templates::Templates,
+    tracking::TrackingSigner,
+    unsubscribe::UnsubscribeSigner,
 };
 
 static TRACING: Once = Once::new();
 
 pub struct TestApp {
     pub address: String,
-    pub db_client: web::Data<mongodb::Client>,
+    pub port: u16,
+    pub storage: Storage,
+    pub delivery_queue: Arc<InMemoryDeliveryQueue>,
+    pub email_server: MockServer,
+    pub test_user: TestUser,
+    pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub templates: Templates,
+    pub unsubscribe_signer: UnsubscribeSigner,
+    pub tracking_signer: TrackingSigner,
+    pub webhook_verifier: WebhookVerifier,
+}
+
+pub struct TestUser {
+    pub username: String,
+    pub password: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            username: uuid::Uuid::new_v4().to_string(),
+            password: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    async fn store(&self, users: &dyn UserRepository) {
+        create_user(
+            users,
+            &self.username,
+            Password::parse(Secret::new(self.password.clone())).unwrap(),
+        )
+        .await
+        .expect("Failed to store test user");
+    }
+}
+
+pub struct ConfirmationLinks {
+    pub html: reqwest::Url,
+    pub plain_text: reqwest::Url,
+}
+
+impl TestApp {
+    pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions", self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletters(&self, body: serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletters_with_idempotency_key(
+        &self,
+        body: &serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Starts a request against the subscriber management API, authenticated as the test user.
+    pub fn api_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(method, format!("{}/api/v1{}", self.address, path))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                self.delivery_queue.as_ref(),
+                self.storage.subscribers.as_ref(),
+                &self.email_client,
+                &self.templates,
+                &self.unsubscribe_signer,
+                &self.tracking_signer,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Subscribes a fresh address and returns it along with its confirmation links.
+    pub async fn create_unconfirmed_subscriber(&self) -> (String, ConfirmationLinks) {
+        let email = format!("{}@gmail.com", uuid::Uuid::new_v4());
+        let body = format!("name=le%20guin&email={}", email.replace('@', "%40"));
+
+        let _mock_guard = Mock::given(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(body_string_contains(email.as_str()))
+            .respond_with(ResponseTemplate::new(200))
+            .named("Create unconfirmed subscriber")
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+        self.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+
+        let email_request = self
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        (email, self.get_confirmation_links(&email_request))
+    }
+
+    pub async fn create_confirmed_subscriber(&self) -> String {
+        let (email, confirmation_links) = self.create_unconfirmed_subscriber().await;
+
+        let _mock_guard = Mock::given(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(body_string_contains(email.as_str()))
+            .respond_with(ResponseTemplate::new(200))
+            .named("Welcome confirmed subscriber")
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+        reqwest::get(confirmation_links.html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        email
+    }
+
+    /// Posts `events` to the email events webhook, signed like the provider does.
+    pub async fn post_email_events(&self, events: &serde_json::Value) -> reqwest::Response {
+        let body = serde_json::to_vec(events).unwrap();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = self.webhook_verifier.signature(&timestamp, &body).unwrap();
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email-events", self.address))
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Timestamp", timestamp)
+            .header("X-Webhook-Signature", signature)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Returns the body of every email request addressed to `email`. Batch
+    /// requests are narrowed down to `email`'s copy, with its substitutions
+    /// and headers applied the way the provider does.
+    pub async fn emails_sent_to(&self, email: &str) -> Vec<serde_json::Value> {
+        self.email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+            .filter_map(|b| {
+                let personalization = b["Personalizations"]
+                    .as_array()?
+                    .iter()
+                    .find(|p| p["To"] == email)?
+                    .clone();
+                Some(personalize(b, personalization))
+            })
+            .collect()
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/logout", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/dashboard", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_change_password(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/password", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn login_as_test_user(&self) {
+        self.post_login(&serde_json::json!({
+            "username": &self.test_user.username,
+            "password": &self.test_user.password,
+        }))
+        .await;
+    }
+
+    pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let get_link = |s: &str| {
+            let links: Vec<_> = linkify::LinkFinder::new()
+                .links(s)
+                .filter(|l| *l.kind() == linkify::LinkKind::Url)
+                .collect();
+            assert_eq!(links.len(), 1);
+            let mut link = reqwest::Url::parse(links[0].as_str()).unwrap();
+            assert_eq!(link.host_str().unwrap(), "localhost");
+            link.set_port(Some(self.port)).unwrap();
+            link
+        };
+        let html = get_link(body["Content"][0]["content"].as_str().unwrap());
+        let plain_text = get_link(body["Content"][1]["content"].as_str().unwrap());
+        ConfirmationLinks { html, plain_text }
+    }
+}
+
+/// Settings for an application that binds a random port and leaves delivery
+/// to `TestApp::dispatch_all_pending_emails`.
+fn test_configuration(email_server_uri: String) -> Settings {
+    Settings {
+        // Never connected to: the tests run on in-memory storage
+        database: DatabaseSettings {
+            port: 27017,
+            host: "localhost".into(),
+            database_name: "zero".into(),
+            database_password: Secret::new("unused".into()),
+        },
+        application: ApplicationSettings {
+            port: 0,
+            host: "localhost".into(),
+            base_url: "http://localhost".into(),
+            hmac_secret: Secret::new(format!(
+                "{}{}",
+                uuid::Uuid::new_v4().simple(),
+                uuid::Uuid::new_v4().simple()
+            )),
+            shutdown_timeout_seconds: 1,
+            run_delivery_worker: false,
+            templates_directory: concat!(env!("CARGO_MANIFEST_DIR"), "/configuration/templates")
+                .into(),
+        },
+        email_client: EmailClientSettings {
+            provider: EmailProvider::HttpApi,
+            base_url: email_server_uri,
+            client_secret: Secret::new(uuid::Uuid::new_v4().to_string()),
+            sender_email: "newsletter@example.com".into(),
+            sender_name: None,
+            reply_to_email: None,
+            webhook_secret: Some(Secret::new(uuid::Uuid::new_v4().to_string())),
+            smtp_username: None,
+            dev_output_dir: None,
+            timeout_milliseconds: 2_000,
+            // Delivery failures are exercised through the queue's own retries.
+            max_retries: 0,
+            retry_base_delay_milliseconds: 0,
+            retry_max_delay_milliseconds: 0,
+            circuit_breaker_threshold: 0,
+            circuit_breaker_cooldown_seconds: 0,
+        },
+    }
 }
 
 async fn spawn_app() -> TestApp {
@@ -29,30 +375,67 @@ async fn spawn_app() -> TestApp {
         }
     });
 
-    let listener = TcpListener::bind("localhost:0").expect("Failed to bind port");
-    let port = listener.local_addr().unwrap().port();
+    let email_server = MockServer::start().await;
+    let configuration = test_configuration(email_server.uri());
+    let email_client = configuration.email_client.client().unwrap();
+    let templates = Templates::load(&configuration.application.templates_directory).unwrap();
+    let hmac_secret = configuration.application.hmac_secret.clone();
+    let webhook_verifier = WebhookVerifier::new(configuration.email_client.webhook_secret.clone());
+
+    let delivery_queue = Arc::new(InMemoryDeliveryQueue::default());
+    let storage = Storage {
+        delivery_queue: delivery_queue.clone(),
+        ..Storage::in_memory()
+    };
+    let application = Application::build_with_storage(
+        configuration,
+        storage.clone(),
+        InMemorySessionStore::default(),
+    )
+    .expect("Failed to build application");
+    let port = application.port();
     let address = format!("http://localhost:{port}");
+    tokio::spawn(application.run_until_stopped());
 
-    let configuration = get_configuration().expect("Failed to read configuration");
-    let db_client =
-        mongodb::Client::with_uri_str(configuration.database.connection_string().expose_secret())
-            .await
-            .expect("Failed to connect to Mongodb");
-    let db_client = web::Data::new(db_client);
-    let email_sender = configuration
-        .email_client
-        .sender()
-        .expect("Invalid sender email");
-    let email_client = EmailClient::new(
-        configuration.email_client.base_url.clone(),
-        configuration.email_client.client_secret(),
-        email_sender,
+    let unsubscribe_signer = UnsubscribeSigner::new(address.clone(), hmac_secret.clone());
+    let tracking_signer = TrackingSigner::new(address.clone(), hmac_secret);
+    let test_user = TestUser::generate();
+    test_user.store(storage.users.as_ref()).await;
+
+    let api_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+
+    TestApp {
+        address,
+        port,
+        storage,
+        delivery_queue,
+        email_server,
+        test_user,
+        api_client,
+        email_client,
+        templates,
+        unsubscribe_signer,
+        tracking_signer,
+        webhook_verifier,
+    }
+}
+
+#[tokio::test]
+async fn the_application_refuses_a_short_hmac_secret() {
+    let mut configuration = test_configuration("http://localhost".into());
+    configuration.application.hmac_secret = Secret::new("too short".into());
+
+    let application = Application::build_with_storage(
+        configuration,
+        Storage::in_memory(),
+        InMemorySessionStore::default(),
     );
 
-    let server = zero::startup::run(listener, db_client.clone(), email_client)
-        .expect("Failed to bind address");
-    let _ = tokio::spawn(server);
-    TestApp { address, db_client }
+    assert!(application.is_err());
 }
 
 #[tokio::test]
@@ -71,33 +454,22 @@ async fn health_check_works() {
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
-    use zero::routes::FormData;
-
     let app = spawn_app().await;
-    let client = reqwest::Client::new();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
 
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
-    let response = client
-        .post(format!("{}/subscriptions", app.address))
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(body)
-        .send()
-        .await
-        .expect("Failed to execute request");
+    let response = app.post_subscriptions(body.into()).await;
 
     assert_eq!(200, response.status().as_u16());
 
-    if let Some(saved) = app
-        .db_client
-        .database("zero")
-        .collection::<FormData>("subscriptions")
-        .find_one(doc! {"email": "ursula_le_guin@gmail.com"}, None)
-        .await
-        .expect("Failed to get subscriptions")
-    {
-        assert_eq!(saved.email, "ursula_le_guin@gmail.com");
-        assert_eq!(saved.name, "le guin");
-    }
+    let saved = find_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
 }
 
 #[tokio::test]
@@ -135,11 +507,15 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
         ("name=&email=ursula_le_guin%40gmail.com", "empty name"),
         ("name=Ursula&email=", "empty email"),
         ("name=Ursula&email=definitly-not-an-email", "invalid email"),
+        (
+            "name=Ursula%0D%0ABcc%3A%20a%40example.com&email=ursula_le_guin%40gmail.com",
+            "line break in the name",
+        ),
     ];
 
     for (body, description) in test_cases {
         let response = client
-            .post(&format!("{}/subscriptions", app.address))
+            .post(format!("{}/subscriptions", app.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -154,3 +530,2198 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
         );
     }
 }
+
+#[tokio::test]
+async fn subscribe_persists_the_new_subscriber_as_pending_confirmation() {
+    let app = spawn_app().await;
+    let email = format!("{}%40gmail.com", uuid::Uuid::new_v4());
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(format!("name=le%20guin&email={email}"))
+        .await;
+
+    let saved = find_subscriber(&app, &email.replace("%40", "@")).await;
+
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, SubscriberStatus::PendingConfirmation);
+}
+
+#[tokio::test]
+async fn subscribe_describes_invalid_data_as_problem_details() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=definitly-not-an-email".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], 400);
+    assert_eq!(problem["code"], "subscription.invalid_data");
+    assert!(problem["detail"]
+        .as_str()
+        .unwrap()
+        .contains("definitly-not-an-email"));
+}
+
+#[tokio::test]
+async fn subscribe_fails_if_the_confirmation_email_cannot_be_sent() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "subscription.email_failure");
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json_and_returns_the_subscriber() {
+    let app = spawn_app().await;
+    let email = format!("{}@gmail.com", uuid::Uuid::new_v4());
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({"name": "le guin", "email": &email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["email"], email.as_str());
+    assert_eq!(subscriber["name"], "le guin");
+    assert_eq!(subscriber["status"], "pending_confirmation");
+    assert_eq!(
+        subscriber["id"],
+        find_subscriber(&app, &email).await.id.to_hex()
+    );
+}
+
+#[tokio::test]
+async fn subscribing_again_with_json_only_acknowledges_the_request() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({"name": "someone else", "email": &email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let acknowledgement: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(acknowledgement["email"], email.as_str());
+    for field in ["id", "name", "status", "created", "time_zone", "lists"] {
+        assert!(acknowledgement.get(field).is_none(), "{}", field);
+    }
+}
+
+#[tokio::test]
+async fn subscribe_rejects_invalid_json_data_with_problem_details() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({"name": "", "email": "ursula@gmail.com"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "subscription.invalid_data");
+}
+
+#[tokio::test]
+async fn subscribe_sends_a_confirmation_email_with_a_link() {
+    let app = spawn_app().await;
+    let email = format!("{}%40gmail.com", uuid::Uuid::new_v4());
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(format!("name=le%20guin&email={email}"))
+        .await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    assert_eq!(confirmation_links.html, confirmation_links.plain_text);
+}
+
+#[tokio::test]
+async fn confirmations_without_token_are_rejected_with_a_400() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/subscriptions/confirm", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn confirmations_with_an_unknown_token_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
+    let app = spawn_app().await;
+    let email = format!("{}%40gmail.com", uuid::Uuid::new_v4());
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(format!("name=le%20guin&email={email}"))
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = find_subscriber(&app, &email.replace("%40", "@")).await;
+
+    assert_eq!(saved.status, SubscriberStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn confirming_a_subscription_sends_a_single_welcome_email() {
+    let app = spawn_app().await;
+    let (email, confirmation_links) = app.create_unconfirmed_subscriber().await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1]["Subject"], "You're subscribed!");
+    assert!(sent[1]["Content"][0]["content"]
+        .as_str()
+        .unwrap()
+        .contains("Hi le guin"));
+}
+
+fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), location);
+}
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
+    let app = spawn_app().await;
+    let (email, _) = app.create_unconfirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletters(newsletter_request_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    // Only the confirmation email should have reached the unconfirmed address
+    assert_eq!(app.emails_sent_to(&email).await.len(), 1);
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_to_confirmed_subscribers() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletters(newsletter_request_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[2]["Subject"], "Newsletter title");
+}
+
+#[tokio::test]
+async fn publishing_returns_before_the_issue_is_delivered() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    let response = app.post_newsletters(newsletter_request_body()).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert!(receipt["enqueued"].as_u64().unwrap() >= 1);
+    // Only the confirmation and welcome emails
+    assert_eq!(app.emails_sent_to(&email).await.len(), 2);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later() {
+    let app = spawn_app().await;
+    let first = app.create_confirmed_subscriber().await;
+    let second = app.create_confirmed_subscriber().await;
+
+    Mock::given(body_string_contains("Newsletter title"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    let pending_tasks = app.delivery_queue.pending_tasks();
+    for email in [first, second] {
+        let pending = pending_tasks
+            .iter()
+            .find(|t| t.subscriber_email == email)
+            .expect("The failed delivery was dropped");
+        assert_eq!(pending.n_retries, 1);
+        assert!(
+            pending.execute_after > chrono::Utc::now(),
+            "The retry was not delayed"
+        );
+    }
+}
+
+#[tokio::test]
+async fn an_issue_goes_out_to_every_recipient_in_one_batch_request() {
+    let app = spawn_app().await;
+    let recipients = [
+        app.create_confirmed_subscriber().await,
+        app.create_confirmed_subscriber().await,
+        app.create_confirmed_subscriber().await,
+    ];
+    Mock::given(body_string_contains("Newsletter title"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    let mut unsubscribe_links = std::collections::HashSet::new();
+    for email in &recipients {
+        let sent = app.emails_sent_to(email).await;
+        assert_eq!(sent.len(), 3);
+        let html = sent[2]["Content"][0]["content"].as_str().unwrap();
+        assert!(html.contains("<p>Newsletter body as HTML</p>"));
+        assert!(!html.contains("{{"));
+        let unsubscribe_link = sent[2]["Headers"]["List-Unsubscribe"].as_str().unwrap();
+        unsubscribe_links.insert(unsubscribe_link.to_owned());
+    }
+    assert_eq!(unsubscribe_links.len(), recipients.len());
+    assert!(app.delivery_queue.pending_tasks().is_empty());
+}
+
+#[tokio::test]
+async fn an_issue_left_half_enqueued_is_picked_up_again_without_duplicates() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let subscriber = find_subscriber(&app, &email).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue_id = app
+        .delivery_queue
+        .insert_issue(
+            None,
+            "Newsletter title",
+            "Newsletter body as plain text",
+            "<p>Newsletter body as HTML</p>",
+            Some(SendTime::At(chrono::Utc::now())),
+            chrono::Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+    // A scheduler that queued one delivery and then died
+    app.delivery_queue
+        .claim_due_issue(chrono::Duration::zero())
+        .await
+        .unwrap()
+        .unwrap();
+    app.delivery_queue
+        .enqueue(
+            issue_id,
+            vec![(subscriber.id, email.clone())],
+            chrono::Utc::now(),
+        )
+        .await
+        .unwrap();
+
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(app.emails_sent_to(&email).await.len(), 2);
+    assert_eq!(app.delivery_queue.pending_tasks()[0].n_retries, 0);
+
+    assert!(matches!(
+        try_publish_due_issue(
+            app.storage.subscribers.as_ref(),
+            app.delivery_queue.as_ref()
+        )
+        .await
+        .unwrap(),
+        ExecutionOutcome::TaskCompleted
+    ));
+    assert_eq!(app.delivery_queue.pending_tasks().len(), 1);
+    let issue = app
+        .delivery_queue
+        .get_issue(issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.recipients, 1);
+    assert!(issue.enqueuing_until.is_none());
+}
+
+#[tokio::test]
+async fn deliveries_of_a_missing_issue_are_dropped() {
+    let app = spawn_app().await;
+    app.delivery_queue
+        .enqueue(
+            mongodb::bson::oid::ObjectId::new(),
+            vec![(
+                mongodb::bson::oid::ObjectId::new(),
+                "ursula@example.com".into(),
+            )],
+            chrono::Utc::now(),
+        )
+        .await
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.dispatch_all_pending_emails().await;
+
+    assert!(app.delivery_queue.pending_tasks().is_empty());
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_invalid_data() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "content": {
+                    "text": "Newsletter body as plain text",
+                    "html": "<p>Newsletter body as HTML</p>",
+                }
+            }),
+            "missing title",
+        ),
+        (
+            serde_json::json!({"title": "Newsletter!"}),
+            "missing content",
+        ),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        let response = app.post_newsletters(invalid_body).await;
+
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload was {error_message}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn requests_missing_authorization_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn non_existing_user_is_rejected() {
+    let app = spawn_app().await;
+    let username = uuid::Uuid::new_v4().to_string();
+    let password = uuid::Uuid::new_v4().to_string();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .basic_auth(username, Some(password))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn invalid_password_is_rejected() {
+    let app = spawn_app().await;
+    let password = uuid::Uuid::new_v4().to_string();
+    assert_ne!(app.test_user.password, password);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .basic_auth(&app.test_user.username, Some(password))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn an_error_flash_message_is_set_on_failure() {
+    let app = spawn_app().await;
+
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+
+    // The flash message is gone once displayed
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("Authentication failed"));
+}
+
+#[tokio::test]
+async fn redirect_to_admin_dashboard_after_login_success() {
+    let app = spawn_app().await;
+
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn anonymous_users_are_redirected_to_the_login_form() {
+    let app = spawn_app().await;
+
+    let response = app.post_logout().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logout_clears_session_state() {
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    // The session no longer carries a user, so admin routes bounce again
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(!app
+        .get_login_html()
+        .await
+        .contains("successfully logged out"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/dashboard", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_admin_dashboard_greets_the_logged_in_user() {
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn the_admin_dashboard_counts_subscribers_by_status() {
+    let app = spawn_app().await;
+    app.create_unconfirmed_subscriber().await;
+    app.create_confirmed_subscriber().await;
+    app.login_as_test_user().await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("<td>pending_confirmation</td>"));
+    assert!(html_page.contains("<td>confirmed</td>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_change_password_form() {
+    let app = spawn_app().await;
+
+    let response = app.get_change_password().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_your_password() {
+    let app = spawn_app().await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": uuid::Uuid::new_v4().to_string(),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn new_password_fields_must_match() {
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": uuid::Uuid::new_v4().to_string(),
+            "new_password_check": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn current_password_must_be_valid() {
+    let app = spawn_app().await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+    app.login_as_test_user().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": uuid::Uuid::new_v4().to_string(),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+}
+
+#[tokio::test]
+async fn new_password_must_be_long_enough() {
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "too-short",
+            "new_password_check": "too-short",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("at least 12 characters long"));
+}
+
+#[tokio::test]
+async fn changing_password_works() {
+    let app = spawn_app().await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+    app.login_as_test_user().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn newsletter_creation_is_idempotent() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let body = newsletter_request_body();
+    let response = app
+        .post_newsletters_with_idempotency_key(&body, &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let first_receipt = response.text().await.unwrap();
+
+    let response = app
+        .post_newsletters_with_idempotency_key(&body, &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert_eq!(response.text().await.unwrap(), first_receipt);
+    app.dispatch_all_pending_emails().await;
+
+    // The confirmation and welcome emails and a single copy of the issue
+    assert_eq!(app.emails_sent_to(&email).await.len(), 3);
+}
+
+#[tokio::test]
+async fn concurrent_newsletter_submissions_are_handled_gracefully() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let body = newsletter_request_body();
+    let response1 = app.post_newsletters_with_idempotency_key(&body, &idempotency_key);
+    let response2 = app.post_newsletters_with_idempotency_key(&body, &idempotency_key);
+    let (response1, response2) = tokio::join!(response1, response2);
+
+    assert_eq!(response1.status(), response2.status());
+    assert_eq!(
+        response1.text().await.unwrap(),
+        response2.text().await.unwrap()
+    );
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(app.emails_sent_to(&email).await.len(), 3);
+}
+
+#[tokio::test]
+async fn retrying_a_failed_newsletter_submission_resumes_the_same_issue() {
+    let app = spawn_app().await;
+    let first = app.create_confirmed_subscriber().await;
+    let second = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let user_id = app
+        .storage
+        .users
+        .find_by_username(&app.test_user.username)
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let idempotency_key = IdempotencyKey::try_from(uuid::Uuid::new_v4().to_string()).unwrap();
+    // A submission that stored its issue, queued one delivery and then failed
+    let idempotency = app.storage.idempotency.as_ref();
+    assert!(idempotency
+        .try_claim(user_id, &idempotency_key)
+        .await
+        .unwrap());
+    let issue_id = app
+        .delivery_queue
+        .insert_issue(
+            None,
+            "Newsletter title",
+            "Newsletter body as plain text",
+            "<p>Newsletter body as HTML</p>",
+            None,
+            chrono::Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+    idempotency
+        .save_issue(user_id, &idempotency_key, issue_id)
+        .await
+        .unwrap();
+    let first_id = find_subscriber(&app, &first).await.id;
+    app.delivery_queue
+        .enqueue(
+            issue_id,
+            vec![(first_id, first.clone())],
+            chrono::Utc::now(),
+        )
+        .await
+        .unwrap();
+    idempotency
+        .release(user_id, &idempotency_key)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_newsletters_with_idempotency_key(&newsletter_request_body(), idempotency_key.as_ref())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(receipt["issue_id"], issue_id.to_hex());
+    assert_eq!(receipt["enqueued"], 2);
+    app.dispatch_all_pending_emails().await;
+    for email in [&first, &second] {
+        assert_eq!(app.emails_sent_to(email).await.len(), 3);
+    }
+}
+
+#[tokio::test]
+async fn invalid_idempotency_keys_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_newsletters_with_idempotency_key(&newsletter_request_body(), &"a".repeat(50))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+/// The copy of the email in `body` that `personalization`'s recipient gets.
+fn personalize(
+    mut body: serde_json::Value,
+    personalization: serde_json::Value,
+) -> serde_json::Value {
+    let substitute = |text: &serde_json::Value| {
+        let text = text.as_str().unwrap().to_string();
+        personalization["Substitutions"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .fold(text, |text, (placeholder, value)| {
+                text.replace(placeholder, value.as_str().unwrap())
+            })
+    };
+    body["Subject"] = substitute(&body["Subject"]).into();
+    for content in body["Content"].as_array_mut().unwrap() {
+        content["content"] = substitute(&content["content"]).into();
+    }
+    for (name, value) in personalization["Headers"].as_object().into_iter().flatten() {
+        body["Headers"][name] = value.clone();
+    }
+    body["Personalizations"] = serde_json::json!([personalization]);
+    body
+}
+
+async fn find_subscriber(app: &TestApp, email: &str) -> Subscriber {
+    app.storage
+        .subscribers
+        .find_by_email(email)
+        .await
+        .unwrap()
+        .expect("No subscriber saved")
+}
+
+#[tokio::test]
+async fn newsletter_emails_carry_list_unsubscribe_headers() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    let sent = app.emails_sent_to(&email).await;
+    let headers = &sent[2]["Headers"];
+    let list_unsubscribe = headers["List-Unsubscribe"].as_str().unwrap();
+    assert!(list_unsubscribe.starts_with(&format!(
+        "<{}/subscriptions/unsubscribe?token=",
+        app.address
+    )));
+    assert_eq!(
+        headers["List-Unsubscribe-Post"],
+        "List-Unsubscribe=One-Click"
+    );
+    let unsubscribe_link = list_unsubscribe
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    assert!(sent[2]["Content"][0]["content"]
+        .as_str()
+        .unwrap()
+        .contains(unsubscribe_link));
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_updates_the_subscriber_status() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+    let sent = app.emails_sent_to(&email).await;
+    let unsubscribe_link = sent[2]["Headers"]["List-Unsubscribe"]
+        .as_str()
+        .unwrap()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string();
+
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Unsubscribed
+    );
+}
+
+#[tokio::test]
+async fn unsubscribing_sends_a_single_confirmation_email() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let subscriber_id = find_subscriber(&app, &email).await.id;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = reqwest::Client::new()
+            .post(app.unsubscribe_signer.link(subscriber_id, None))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[2]["Subject"], "You have been unsubscribed");
+}
+
+#[tokio::test]
+async fn subscribing_again_after_unsubscribing_asks_for_confirmation_again() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let subscriber_id = find_subscriber(&app, &email).await.id;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    reqwest::Client::new()
+        .post(app.unsubscribe_signer.link(subscriber_id, None))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email={}",
+            email.replace('@', "%40")
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::PendingConfirmation
+    );
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 4);
+    assert_eq!(sent[3]["Subject"], "Welcome!");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn following_the_unsubscribe_link_asks_for_confirmation() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let subscriber_id = find_subscriber(&app, &email).await.id;
+
+    let response = reqwest::get(app.unsubscribe_signer.link(subscriber_id, None))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("<form"));
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn hard_bounces_and_complaints_take_subscribers_off_the_list() {
+    let app = spawn_app().await;
+    let bounced = app.create_confirmed_subscriber().await;
+    let complained = app.create_confirmed_subscriber().await;
+    let soft_bounced = app.create_confirmed_subscriber().await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([
+            {"type": "bounce", "email": bounced, "bounce_type": "hard"},
+            {"type": "complaint", "email": complained},
+            {"type": "bounce", "email": soft_bounced, "bounce_type": "soft"},
+            {"type": "delivered", "email": soft_bounced},
+            {"type": "open", "email": soft_bounced},
+            {"type": "bounce", "email": "unknown@example.com"},
+        ]))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, &bounced).await.status,
+        SubscriberStatus::Bounced
+    );
+    assert_eq!(
+        find_subscriber(&app, &complained).await.status,
+        SubscriberStatus::Complained
+    );
+    assert_eq!(
+        find_subscriber(&app, &soft_bounced).await.status,
+        SubscriberStatus::Confirmed
+    );
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let receipt: serde_json::Value = app
+        .post_newsletters(newsletter_request_body())
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(receipt["enqueued"], 1);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_cannot_be_confirmed_or_unsubscribed_again() {
+    let app = spawn_app().await;
+    let (email, confirmation_links) = app.create_unconfirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.post_email_events(&serde_json::json!([
+        {"type": "bounce", "email": email, "bounce_type": "hard"},
+    ]))
+    .await
+    .error_for_status()
+    .unwrap();
+    let subscriber_id = find_subscriber(&app, &email).await.id;
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = reqwest::Client::new()
+        .post(app.unsubscribe_signer.link(subscriber_id, None))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Bounced
+    );
+    // Only the confirmation and the welcome email
+    assert_eq!(app.emails_sent_to(&email).await.len(), 2);
+    let receipt: serde_json::Value = app
+        .post_newsletters(newsletter_request_body())
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(receipt["enqueued"], 0);
+}
+
+#[tokio::test]
+async fn an_old_confirmation_link_does_not_confirm_an_unsubscribed_address() {
+    let app = spawn_app().await;
+    let (email, confirmation_links) = app.create_unconfirmed_subscriber().await;
+    let subscriber_id = find_subscriber(&app, &email).await.id;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    reqwest::Client::new()
+        .post(app.unsubscribe_signer.link(subscriber_id, None))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Unsubscribed
+    );
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1]["Subject"], "You have been unsubscribed");
+}
+
+#[tokio::test]
+async fn email_events_with_an_invalid_signature_are_rejected() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let body = serde_json::json!([{"type": "complaint", "email": email}]).to_string();
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    let forged = WebhookVerifier::new(Some(Secret::new("guessed".into())))
+        .signature(&timestamp, body.as_bytes())
+        .unwrap();
+
+    for signature in [None, Some(forged)] {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/webhooks/email-events", app.address))
+            .header("X-Webhook-Timestamp", &timestamp)
+            .body(body.clone());
+        if let Some(signature) = signature {
+            request = request.header("X-Webhook-Signature", signature);
+        }
+        let response = request.send().await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert_eq!(
+        find_subscriber(&app, &email).await.status,
+        SubscriberStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn malformed_email_events_are_rejected_with_a_400() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_email_events(&serde_json::json!({"type": "bounce"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn unsubscribing_with_a_forged_token_is_rejected() {
+    let app = spawn_app().await;
+    let token = format!("{}.Zm9yZ2Vk", mongodb::bson::oid::ObjectId::new().to_hex());
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, token
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_application_stops_serving_once_shutdown_is_requested() {
+    let email_server = MockServer::start().await;
+    let application = Application::build_with_storage(
+        test_configuration(email_server.uri()),
+        Storage::in_memory(),
+        InMemorySessionStore::default(),
+    )
+    .expect("Failed to build application");
+    let address = format!("http://localhost:{}/health_check", application.port());
+    let (trigger_shutdown, shutdown) = tokio::sync::oneshot::channel::<()>();
+    let application = tokio::spawn(application.run_until(async {
+        let _ = shutdown.await;
+    }));
+    let response = reqwest::get(&address).await.unwrap();
+    assert!(response.status().is_success());
+
+    trigger_shutdown.send(()).unwrap();
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), application)
+        .await
+        .expect("The application did not stop in time")
+        .unwrap()
+        .unwrap();
+    assert!(reqwest::get(&address).await.is_err());
+}
+
+#[tokio::test]
+async fn the_subscriber_api_requires_authentication() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/api/v1/subscribers", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_page_by_page() {
+    let app = spawn_app().await;
+    let mut confirmed = Vec::new();
+    for _ in 0..3 {
+        confirmed.push(app.create_confirmed_subscriber().await);
+    }
+    app.create_unconfirmed_subscriber().await;
+
+    let mut listed = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("status", "confirmed".to_string()), ("limit", "2".into())];
+        if let Some(cursor) = &cursor {
+            query.push(("cursor", cursor.clone()));
+        }
+        let response = app
+            .api_request(reqwest::Method::GET, "/subscribers")
+            .query(&query)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let page: serde_json::Value = response.json().await.unwrap();
+        for subscriber in page["data"].as_array().unwrap() {
+            assert_eq!(subscriber["status"], "confirmed");
+            listed.push(subscriber["email"].as_str().unwrap().to_string());
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    assert_eq!(listed, confirmed);
+}
+
+#[tokio::test]
+async fn listing_subscribers_rejects_unknown_statuses() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_request(reqwest::Method::GET, "/subscribers?status=sleeping")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "subscriber.invalid_data");
+}
+
+#[tokio::test]
+async fn listing_subscribers_rejects_invalid_pagination() {
+    let app = spawn_app().await;
+
+    for query in ["cursor=not-an-id", "limit=0", "limit=1000"] {
+        let response = app
+            .api_request(reqwest::Method::GET, &format!("/subscribers?{}", query))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 400, "{}", query);
+        let problem: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(problem["code"], "request.invalid_data", "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_fetched_renamed_and_deleted() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let path = format!("/subscribers/{}", find_subscriber(&app, &email).await.id);
+
+    let response = app
+        .api_request(reqwest::Method::GET, &path)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["email"], email.as_str());
+
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({"name": "Ursula K. Le Guin"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, &email).await.name,
+        "Ursula K. Le Guin"
+    );
+
+    let response = app
+        .api_request(reqwest::Method::DELETE, &path)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .api_request(reqwest::Method::GET, &path)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "subscriber.not_found");
+}
+
+#[tokio::test]
+async fn queued_issues_are_not_delivered_to_a_deleted_subscriber() {
+    let app = spawn_app().await;
+    let deleted = app.create_confirmed_subscriber().await;
+    let kept = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(newsletter_request_body()).await;
+    let path = format!("/subscribers/{}", find_subscriber(&app, &deleted).await.id);
+
+    let response = app
+        .api_request(reqwest::Method::DELETE, &path)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 204);
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(app.emails_sent_to(&deleted).await.len(), 2);
+    assert_eq!(app.emails_sent_to(&kept).await.len(), 3);
+}
+
+#[tokio::test]
+async fn renaming_a_subscriber_validates_the_name() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let path = format!("/subscribers/{}", find_subscriber(&app, &email).await.id);
+
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({"name": "<script>"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(find_subscriber(&app, &email).await.name, "le guin");
+}
+
+#[tokio::test]
+async fn subscribers_can_pick_a_time_zone() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "time_zone": "Mars/Olympus_Mons",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "time_zone": "Europe/Rome",
+        }))
+        .await;
+    assert!(response.status().is_success());
+    let subscriber = find_subscriber(&app, "ursula_le_guin@gmail.com").await;
+    assert_eq!(subscriber.time_zone.as_deref(), Some("Europe/Rome"));
+}
+
+#[tokio::test]
+async fn a_subscriber_time_zone_can_be_changed_and_cleared() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let path = format!("/subscribers/{}", find_subscriber(&app, &email).await.id);
+    let patch = |body: serde_json::Value| {
+        app.api_request(reqwest::Method::PATCH, &path)
+            .json(&body)
+            .send()
+    };
+
+    let response = patch(serde_json::json!({"time_zone": "Asia/Tokyo"}))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["time_zone"], "Asia/Tokyo");
+    assert_eq!(subscriber["name"], "le guin");
+
+    let response = patch(serde_json::json!({"time_zone": "Tokyo"}))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        find_subscriber(&app, &email).await.time_zone.as_deref(),
+        Some("Asia/Tokyo")
+    );
+
+    let response = patch(serde_json::json!({"time_zone": null})).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(find_subscriber(&app, &email).await.time_zone, None);
+
+    let response = patch(serde_json::json!({})).await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+fn scheduled_newsletter_request_body(send_at: chrono::DateTime<chrono::Utc>) -> serde_json::Value {
+    let mut body = newsletter_request_body();
+    body["send_at"] = serde_json::json!(send_at);
+    body
+}
+
+#[tokio::test]
+async fn scheduled_issues_wait_until_they_are_due() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    let send_at = chrono::Utc::now() + chrono::Duration::hours(1);
+
+    let response = app
+        .post_newsletters(scheduled_newsletter_request_body(send_at))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(receipt["status"], "scheduled");
+    assert_eq!(receipt["enqueued"], 0);
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(app.emails_sent_to(&email).await.len(), 2);
+    assert!(matches!(
+        try_publish_due_issue(
+            app.storage.subscribers.as_ref(),
+            app.delivery_queue.as_ref()
+        )
+        .await
+        .unwrap(),
+        ExecutionOutcome::EmptyQueue
+    ));
+
+    let response = app
+        .api_request(reqwest::Method::GET, "/issues/scheduled")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let scheduled: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(scheduled["data"][0]["id"], receipt["issue_id"]);
+    assert_eq!(scheduled["data"][0]["status"], "scheduled");
+}
+
+#[tokio::test]
+async fn due_issues_are_published_by_the_scheduler() {
+    let app = spawn_app().await;
+    let email = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue_id = app
+        .delivery_queue
+        .insert_issue(
+            None,
+            "Newsletter title",
+            "Newsletter body as plain text",
+            "<p>Newsletter body as HTML</p>",
+            Some(SendTime::At(
+                chrono::Utc::now() - chrono::Duration::minutes(1),
+            )),
+            chrono::Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        try_publish_due_issue(
+            app.storage.subscribers.as_ref(),
+            app.delivery_queue.as_ref()
+        )
+        .await
+        .unwrap(),
+        ExecutionOutcome::TaskCompleted
+    ));
+    app.dispatch_all_pending_emails().await;
+
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[2]["Subject"], "Newsletter title");
+    let issue = app
+        .delivery_queue
+        .get_issue(issue_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.status.as_str(), "published");
+}
+
+#[tokio::test]
+async fn a_send_at_in_the_past_publishes_right_away() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let response = app
+        .post_newsletters(scheduled_newsletter_request_body(
+            chrono::Utc::now() - chrono::Duration::hours(1),
+        ))
+        .await;
+
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(receipt["status"], "published");
+    assert_eq!(receipt["enqueued"], 1);
+}
+
+#[tokio::test]
+async fn scheduled_issues_can_be_rescheduled_and_cancelled() {
+    let app = spawn_app().await;
+    let response = app
+        .post_newsletters(scheduled_newsletter_request_body(
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        ))
+        .await;
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    let path = format!("/issues/{}", receipt["issue_id"].as_str().unwrap());
+    let monday = chrono::Utc::now() + chrono::Duration::days(3);
+
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({ "send_at": monday }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        issue["send_at"]
+            .as_str()
+            .unwrap()
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap(),
+        monday
+    );
+
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({ "send_at": chrono::Utc::now() - chrono::Duration::days(1) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let cancel = format!("{}/cancel", path);
+    let response = app
+        .api_request(reqwest::Method::POST, &cancel)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["status"], "cancelled");
+
+    let response = app
+        .api_request(reqwest::Method::POST, &cancel)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 409);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "issue.not_scheduled");
+    let scheduled: serde_json::Value = app
+        .api_request(reqwest::Method::GET, "/issues/scheduled")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(scheduled["data"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn issues_cannot_be_rescheduled_to_a_local_time_that_has_passed_somewhere() {
+    let app = spawn_app().await;
+    let response = app
+        .post_newsletters(scheduled_newsletter_request_body(
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        ))
+        .await;
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    let path = format!("/issues/{}", receipt["issue_id"].as_str().unwrap());
+    let in_hours = |hours| {
+        (chrono::Utc::now() + chrono::Duration::hours(hours))
+            .naive_utc()
+            .with_nanosecond(0)
+            .unwrap()
+    };
+
+    // Still ahead in UTC, but already past in UTC+14
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({ "send_at_local": in_hours(2) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "issue.invalid_data");
+
+    let local = in_hours(15);
+    let response = app
+        .api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({ "send_at_local": local }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["send_at_local"], serde_json::json!(local));
+}
+
+#[tokio::test]
+async fn local_send_times_are_delivered_per_time_zone() {
+    let app = spawn_app().await;
+    let tokyo = app.create_confirmed_subscriber().await;
+    let utc = app.create_confirmed_subscriber().await;
+    let path = format!("/subscribers/{}", find_subscriber(&app, &tokyo).await.id);
+    app.api_request(reqwest::Method::PATCH, &path)
+        .json(&serde_json::json!({"time_zone": "Asia/Tokyo"}))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    // Already due in the earliest time zones, but not yet in Tokyo or UTC
+    let local = (chrono::Utc::now() + chrono::Duration::hours(12))
+        .naive_utc()
+        .with_nanosecond(0)
+        .unwrap();
+    let mut body = newsletter_request_body();
+    body["send_at_local"] = serde_json::json!(local);
+
+    let response = app.post_newsletters(body).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(receipt["status"], "scheduled");
+    assert_eq!(receipt["send_at_local"], serde_json::json!(local));
+    assert!(matches!(
+        try_publish_due_issue(
+            app.storage.subscribers.as_ref(),
+            app.delivery_queue.as_ref()
+        )
+        .await
+        .unwrap(),
+        ExecutionOutcome::TaskCompleted
+    ));
+    let due_at = |email: &str| {
+        app.delivery_queue
+            .pending_tasks()
+            .into_iter()
+            .find(|task| task.subscriber_email == email)
+            .expect("No delivery queued")
+            .execute_after
+    };
+    assert_eq!(due_at(&tokyo), local.and_utc() - chrono::Duration::hours(9));
+    assert_eq!(due_at(&utc), local.and_utc());
+}
+
+#[tokio::test]
+async fn an_issue_takes_either_an_absolute_or_a_local_send_time() {
+    let app = spawn_app().await;
+    let mut body = scheduled_newsletter_request_body(chrono::Utc::now());
+    body["send_at_local"] = serde_json::json!("2026-10-19T09:00:00");
+
+    let response = app.post_newsletters(body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+/// Creates the `weekly` list, sent as "The Weekly" with replies going to
+/// `editor@example.com`.
+async fn create_weekly_list(app: &TestApp) {
+    app.api_request(reqwest::Method::POST, "/lists")
+        .json(&serde_json::json!({
+            "slug": "weekly",
+            "name": "The Weekly",
+            "description": "A digest, every Monday",
+            "sender_name": "The Weekly",
+            "reply_to": "editor@example.com",
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Subscribes a fresh address to the `weekly` list, confirms it and returns
+/// the address.
+async fn create_weekly_member(app: &TestApp) -> String {
+    let email = format!("{}@gmail.com", uuid::Uuid::new_v4());
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": email,
+            "list": "weekly",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    email
+}
+
+#[tokio::test]
+async fn mailing_lists_can_be_created_and_listed() {
+    let app = spawn_app().await;
+    create_weekly_list(&app).await;
+
+    let response = app
+        .api_request(reqwest::Method::POST, "/lists")
+        .json(&serde_json::json!({"slug": "weekly", "name": "Another weekly"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 409);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "list.already_exists");
+
+    for body in [
+        serde_json::json!({"slug": "Weekly Digest", "name": "Digest"}),
+        serde_json::json!({"slug": "digest", "name": " "}),
+        serde_json::json!({"slug": "digest", "name": "Digest", "reply_to": "not an email"}),
+    ] {
+        let response = app
+            .api_request(reqwest::Method::POST, "/lists")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 400, "{}", body);
+    }
+
+    let lists: serde_json::Value = app
+        .api_request(reqwest::Method::GET, "/lists")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(lists["data"].as_array().unwrap().len(), 1);
+    assert_eq!(lists["data"][0]["slug"], "weekly");
+    assert_eq!(lists["data"][0]["sender_name"], "The Weekly");
+}
+
+#[tokio::test]
+async fn joining_a_list_is_confirmed_separately_from_the_main_list() {
+    let app = spawn_app().await;
+    create_weekly_list(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let email = create_weekly_member(&app).await;
+
+    let sent = app.emails_sent_to(&email).await;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0]["From"]["name"], "The Weekly");
+    assert_eq!(sent[0]["ReplyTo"]["email"], "editor@example.com");
+    let subscriber = find_subscriber(&app, &email).await;
+    assert_eq!(subscriber.status, SubscriberStatus::PendingConfirmation);
+    assert_eq!(
+        subscriber.membership("weekly").unwrap().status,
+        SubscriberStatus::Confirmed
+    );
+
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": email,
+            "list": "weekly",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = find_subscriber(&app, &email).await;
+    assert_eq!(
+        subscriber.membership("weekly").unwrap().status,
+        SubscriberStatus::Confirmed
+    );
+    assert_eq!(app.emails_sent_to(&email).await.len(), 2);
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&list=monthly".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {"text": "text", "html": "<p>html</p>"},
+            "list": "monthly",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_list_reach_only_its_members() {
+    let app = spawn_app().await;
+    create_weekly_list(&app).await;
+    let main_list_subscriber = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let member = create_weekly_member(&app).await;
+    let mut body = newsletter_request_body();
+    body["list"] = serde_json::json!("weekly");
+
+    let response = app.post_newsletters(body).await;
+
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(receipt["enqueued"], 1);
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(app.emails_sent_to(&main_list_subscriber).await.len(), 2);
+    let sent = app.emails_sent_to(&member).await;
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[2]["Subject"], "Newsletter title");
+    assert_eq!(sent[2]["From"]["name"], "The Weekly");
+
+    // The issue's unsubscribe link only takes the member off the weekly list
+    let subscriber = find_subscriber(&app, &member).await;
+    let unsubscribe_link = app.unsubscribe_signer.link(subscriber.id, Some("weekly"));
+    assert_eq!(
+        sent[2]["Headers"]["List-Unsubscribe"],
+        format!("<{}>", unsubscribe_link)
+    );
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = find_subscriber(&app, &member).await;
+    assert_eq!(
+        subscriber.membership("weekly").unwrap().status,
+        SubscriberStatus::Unsubscribed
+    );
+    assert_eq!(subscriber.status, SubscriberStatus::PendingConfirmation);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_keep_their_list_memberships_and_get_no_list_issues() {
+    let app = spawn_app().await;
+    create_weekly_list(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let member = create_weekly_member(&app).await;
+    app.post_email_events(&serde_json::json!([{"type": "complaint", "email": member}]))
+        .await
+        .error_for_status()
+        .unwrap();
+    let subscriber_id = find_subscriber(&app, &member).await.id;
+
+    let response = reqwest::Client::new()
+        .post(app.unsubscribe_signer.link(subscriber_id, Some("weekly")))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = find_subscriber(&app, &member).await;
+    assert_eq!(subscriber.status, SubscriberStatus::Complained);
+    assert_eq!(
+        subscriber.membership("weekly").unwrap().status,
+        SubscriberStatus::Confirmed
+    );
+    assert_eq!(app.emails_sent_to(&member).await.len(), 2);
+    let mut body = newsletter_request_body();
+    body["list"] = serde_json::json!("weekly");
+    let receipt: serde_json::Value = app.post_newsletters(body).await.json().await.unwrap();
+    assert_eq!(receipt["enqueued"], 0);
+}
+
+#[tokio::test]
+async fn queued_deliveries_are_dropped_for_recipients_who_left_since() {
+    let app = spawn_app().await;
+    create_weekly_list(&app).await;
+    let staying = app.create_confirmed_subscriber().await;
+    let unsubscribed = app.create_confirmed_subscriber().await;
+    let complained = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let member = create_weekly_member(&app).await;
+    let mut weekly_body = newsletter_request_body();
+    weekly_body["list"] = serde_json::json!("weekly");
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_newsletters(weekly_body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let unsubscribed_id = find_subscriber(&app, &unsubscribed).await.id;
+    let member_id = find_subscriber(&app, &member).await.id;
+    for link in [
+        app.unsubscribe_signer.link(unsubscribed_id, None),
+        app.unsubscribe_signer.link(member_id, Some("weekly")),
+    ] {
+        reqwest::Client::new()
+            .post(link)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    app.post_email_events(&serde_json::json!([{"type": "complaint", "email": complained}]))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let newsletters_sent_to = |sent: Vec<serde_json::Value>| {
+        sent.iter()
+            .filter(|email| email["Subject"] == "Newsletter title")
+            .count()
+    };
+    assert_eq!(newsletters_sent_to(app.emails_sent_to(&staying).await), 1);
+    for email in [&unsubscribed, &complained, &member] {
+        assert_eq!(newsletters_sent_to(app.emails_sent_to(email).await), 0);
+    }
+    assert!(app.delivery_queue.pending_tasks().is_empty());
+}
+
+/// Publishes an issue linking to `https://example.com/post`, delivers it to
+/// a new confirmed subscriber and returns the issue id and the HTML sent.
+async fn deliver_tracked_issue(app: &TestApp) -> (String, String) {
+    let email = app.create_confirmed_subscriber().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Read https://example.com/post",
+                "html": r#"<p>Read <a href="https://example.com/post">this</a></p>"#,
+            }
+        }))
+        .await;
+    let receipt: serde_json::Value = response.json().await.unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let sent = app.emails_sent_to(&email).await;
+    let html = sent[2]["Content"][0]["content"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    (receipt["issue_id"].as_str().unwrap().to_string(), html)
+}
+
+fn tracking_links(html: &str, prefix: &str) -> Vec<String> {
+    linkify::LinkFinder::new()
+        .links(html)
+        .map(|l| l.as_str().to_string())
+        .filter(|l| l.starts_with(prefix))
+        .collect()
+}
+
+#[tokio::test]
+async fn newsletter_links_are_tracked_and_an_open_pixel_is_embedded() {
+    let app = spawn_app().await;
+
+    let (_, html) = deliver_tracked_issue(&app).await;
+
+    assert!(!html.contains(r#"href="https://example.com/post""#));
+    assert_eq!(
+        tracking_links(&html, &format!("{}/t/c/", app.address)).len(),
+        1
+    );
+    assert_eq!(
+        tracking_links(&html, &format!("{}/t/o/", app.address)).len(),
+        1
+    );
+    // The unsubscribe link is left alone
+    assert!(html.contains(&format!("{}/subscriptions/unsubscribe", app.address)));
+}
+
+#[tokio::test]
+async fn tracked_links_redirect_and_opens_and_clicks_are_reported_per_issue() {
+    let app = spawn_app().await;
+    let (issue_id, html) = deliver_tracked_issue(&app).await;
+    let click = &tracking_links(&html, &format!("{}/t/c/", app.address))[0];
+    let pixel = &tracking_links(&html, &format!("{}/t/o/", app.address))[0];
+
+    let response = app.api_client.get(click).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers()["Location"], "https://example.com/post");
+    for _ in 0..2 {
+        let response = app.api_client.get(pixel).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+    }
+
+    let response = app
+        .api_request(reqwest::Method::GET, &format!("/issues/{}/stats", issue_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let stats: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(stats["recipients"], 1);
+    assert_eq!(stats["unique_opens"], 1);
+    assert_eq!(stats["total_opens"], 2);
+    assert_eq!(stats["unique_clicks"], 1);
+    assert_eq!(stats["open_rate"], 1.0);
+    assert_eq!(stats["click_rate"], 1.0);
+}
+
+#[tokio::test]
+async fn forged_click_tokens_are_not_redirected() {
+    let app = spawn_app().await;
+    let (_, html) = deliver_tracked_issue(&app).await;
+    let click = &tracking_links(&html, &format!("{}/t/c/", app.address))[0];
+    let forger = TrackingSigner::new(app.address.clone(), Secret::new("forged".into()));
+    let forged = forger.click_url(
+        mongodb::bson::oid::ObjectId::new(),
+        mongodb::bson::oid::ObjectId::new(),
+        "https://evil.example",
+    );
+
+    for link in [forged, format!("{}x", click)] {
+        let response = app.api_client.get(&link).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn issue_stats_require_credentials_and_an_existing_issue() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/api/v1/issues/{}/stats",
+        app.address,
+        mongodb::bson::oid::ObjectId::new()
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    for issue_id in [mongodb::bson::oid::ObjectId::new().to_hex(), "nope".into()] {
+        let response = app
+            .api_request(reqwest::Method::GET, &format!("/issues/{}/stats", issue_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        let problem: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(problem["code"], "issue.not_found");
+    }
+}
+
+/// Regenerate with `UPDATE_OPENAPI=1 cargo test openapi`.
+#[tokio::test]
+async fn the_committed_openapi_spec_matches_the_routes() {
+    let app = spawn_app().await;
+    let response = reqwest::get(format!("{}/openapi.json", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let served: serde_json::Value = response.json().await.unwrap();
+
+    let spec_path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        let mut spec = serde_json::to_string_pretty(&served).unwrap();
+        spec.push('\n');
+        std::fs::write(spec_path, spec).unwrap();
+    }
+    let committed: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(spec_path).unwrap()).unwrap();
+    assert!(
+        served == committed,
+        "openapi.json is out of date, run `UPDATE_OPENAPI=1 cargo test openapi` to regenerate it"
+    );
+}
+
+#[tokio::test]
+async fn the_api_docs_render_the_openapi_spec() {
+    let app = spawn_app().await;
+    let response = reqwest::get(format!("{}/docs", app.address)).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Security-Policy"],
+        "default-src 'none'; style-src 'unsafe-inline'"
+    );
+    let html = response.text().await.unwrap();
+    assert!(html.contains("/openapi.json"));
+    assert!(html.contains("<code>/subscriptions</code>"));
+    assert!(html.contains(r#"<details id="schema-FormData">"#));
+    assert!(!html.contains("<script"));
+}